@@ -0,0 +1,34 @@
+-- Your SQL goes here
+CREATE TABLE webhooks (
+    id UUID PRIMARY KEY,
+    url VARCHAR NOT NULL,
+    payload_format VARCHAR NOT NULL,
+    secret VARCHAR NOT NULL,
+    added_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    user_id UUID NOT NULL,
+
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+CREATE TABLE webhook_deliveries (
+    id UUID PRIMARY KEY,
+    webhook_id UUID NOT NULL,
+    event VARCHAR NOT NULL,
+    event_key VARCHAR NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR NOT NULL,
+    attempts INTEGER DEFAULT 0 NOT NULL,
+    response_code INTEGER,
+    last_error VARCHAR,
+    created_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    updated_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    UNIQUE(webhook_id, event_key),
+    CONSTRAINT fk_webhooks
+        FOREIGN KEY(webhook_id)
+            REFERENCES webhooks(id)
+            ON DELETE CASCADE
+);
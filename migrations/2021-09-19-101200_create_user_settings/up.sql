@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE user_settings (
+    user_id UUID PRIMARY KEY,
+    notification_channels VARCHAR[] DEFAULT '{webhook}' NOT NULL,
+    lead_times INTEGER[] DEFAULT '{3}' NOT NULL,
+    quiet_hours_start SMALLINT,
+    quiet_hours_end SMALLINT,
+    timezone VARCHAR DEFAULT 'UTC' NOT NULL,
+    delivery_mode VARCHAR DEFAULT 'immediate' NOT NULL,
+    updated_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE user_settings
+ALTER COLUMN notification_channels SET DEFAULT '{webhook}';
+
+DROP TABLE digests;
+DROP TABLE release_date_changes;
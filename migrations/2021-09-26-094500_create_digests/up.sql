@@ -0,0 +1,33 @@
+-- Your SQL goes here
+CREATE TABLE release_date_changes (
+    id UUID PRIMARY KEY,
+    wished_game_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    title VARCHAR NOT NULL,
+    old_release_date BIGINT NOT NULL,
+    new_release_date BIGINT NOT NULL,
+    changed_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    CONSTRAINT fk_wished_games
+        FOREIGN KEY(wished_game_id)
+            REFERENCES wished_games(id)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+CREATE TABLE digests (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    sent_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+ALTER TABLE user_settings
+ALTER COLUMN notification_channels SET DEFAULT '{webhook,email}';
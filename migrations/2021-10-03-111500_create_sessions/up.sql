@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE sessions (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    last_seen TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    user_agent VARCHAR,
+    ip_address VARCHAR,
+
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
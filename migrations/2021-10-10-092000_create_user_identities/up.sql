@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TABLE user_identities (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    provider VARCHAR NOT NULL,
+    subject VARCHAR NOT NULL,
+    email VARCHAR,
+    created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    last_login TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    UNIQUE(provider, subject),
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+-- Users are found by provider and subject now, two providers may share an email
+ALTER TABLE users DROP CONSTRAINT users_email_key;
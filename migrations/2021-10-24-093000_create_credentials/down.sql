@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE credential_tokens;
+DROP TABLE credentials;
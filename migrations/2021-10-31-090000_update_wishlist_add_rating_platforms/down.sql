@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_wished_games_platform_slugs;
+DROP INDEX idx_wished_games_user_release_date;
+DROP INDEX idx_wished_games_user_added_on;
+
+ALTER TABLE wished_games
+DROP COLUMN platform_slugs,
+DROP COLUMN total_rating;
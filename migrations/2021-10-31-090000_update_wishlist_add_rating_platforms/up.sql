@@ -0,0 +1,19 @@
+-- Your SQL goes here
+ALTER TABLE wished_games
+ADD COLUMN total_rating DOUBLE PRECISION,
+ADD COLUMN platform_slugs VARCHAR[] DEFAULT '{}' NOT NULL;
+
+-- Copy them out of the IGDB info so they can be filtered and sorted on
+UPDATE wished_games SET
+    total_rating = (igdb_info->>'total_rating')::DOUBLE PRECISION,
+    platform_slugs = CASE
+        WHEN jsonb_typeof(igdb_info->'platforms') = 'array' THEN ARRAY(
+            SELECT platform->>'slug'
+            FROM jsonb_array_elements(igdb_info->'platforms') AS platform
+        )
+        ELSE '{}'
+    END;
+
+CREATE INDEX idx_wished_games_user_added_on ON wished_games (user_id, added_on);
+CREATE INDEX idx_wished_games_user_release_date ON wished_games (user_id, pc_release_date);
+CREATE INDEX idx_wished_games_platform_slugs ON wished_games USING GIN (platform_slugs);
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_wished_games_user_status;
+
+ALTER TABLE wished_games
+DROP CONSTRAINT wished_games_status_check,
+DROP COLUMN finished_on,
+DROP COLUMN started_on,
+DROP COLUMN status;
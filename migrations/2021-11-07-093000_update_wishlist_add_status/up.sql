@@ -0,0 +1,9 @@
+-- Your SQL goes here
+ALTER TABLE wished_games
+ADD COLUMN status VARCHAR DEFAULT 'wishlist' NOT NULL,
+ADD COLUMN started_on TIMESTAMP,
+ADD COLUMN finished_on TIMESTAMP,
+ADD CONSTRAINT wished_games_status_check
+    CHECK (status IN ('wishlist', 'backlog', 'playing', 'completed', 'dropped'));
+
+CREATE INDEX idx_wished_games_user_status ON wished_games (user_id, status);
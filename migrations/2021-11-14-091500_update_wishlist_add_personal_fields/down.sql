@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_wished_games_user_priority;
+
+ALTER TABLE wished_games
+DROP CONSTRAINT wished_games_max_price_check,
+DROP CONSTRAINT wished_games_personal_rating_check,
+DROP CONSTRAINT wished_games_priority_check,
+DROP COLUMN max_price_cents,
+DROP COLUMN personal_rating,
+DROP COLUMN notes,
+DROP COLUMN priority;
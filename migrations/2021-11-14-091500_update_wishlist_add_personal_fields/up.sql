@@ -0,0 +1,11 @@
+-- Your SQL goes here
+ALTER TABLE wished_games
+ADD COLUMN priority SMALLINT DEFAULT 3 NOT NULL,
+ADD COLUMN notes TEXT DEFAULT '' NOT NULL,
+ADD COLUMN personal_rating SMALLINT,
+ADD COLUMN max_price_cents INTEGER,
+ADD CONSTRAINT wished_games_priority_check CHECK (priority BETWEEN 1 AND 5),
+ADD CONSTRAINT wished_games_personal_rating_check CHECK (personal_rating BETWEEN 1 AND 10),
+ADD CONSTRAINT wished_games_max_price_check CHECK (max_price_cents >= 0);
+
+CREATE INDEX idx_wished_games_user_priority ON wished_games (user_id, priority);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE wished_game_tags;
+DROP TABLE tags;
+DROP TABLE game_list_entries;
+DROP TABLE game_lists;
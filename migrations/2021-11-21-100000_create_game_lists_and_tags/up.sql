@@ -0,0 +1,61 @@
+-- Your SQL goes here
+CREATE TABLE game_lists (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    name VARCHAR NOT NULL,
+    created_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    UNIQUE(user_id, name),
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+CREATE TABLE game_list_entries (
+    list_id UUID NOT NULL,
+    wished_game_id UUID NOT NULL,
+    added_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    PRIMARY KEY(list_id, wished_game_id),
+    CONSTRAINT fk_game_lists
+        FOREIGN KEY(list_id)
+            REFERENCES game_lists(id)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_wished_games
+        FOREIGN KEY(wished_game_id)
+            REFERENCES wished_games(id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX idx_game_list_entries_wished_game ON game_list_entries (wished_game_id);
+
+CREATE TABLE tags (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    name VARCHAR NOT NULL,
+    created_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    UNIQUE(user_id, name),
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+CREATE TABLE wished_game_tags (
+    tag_id UUID NOT NULL,
+    wished_game_id UUID NOT NULL,
+
+    PRIMARY KEY(tag_id, wished_game_id),
+    CONSTRAINT fk_tags
+        FOREIGN KEY(tag_id)
+            REFERENCES tags(id)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_wished_games
+        FOREIGN KEY(wished_game_id)
+            REFERENCES wished_games(id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX idx_wished_game_tags_wished_game ON wished_game_tags (wished_game_id);
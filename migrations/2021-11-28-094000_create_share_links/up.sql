@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE share_links (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    token VARCHAR NOT NULL,
+    list_id UUID,
+    show_notes BOOLEAN NOT NULL DEFAULT FALSE,
+    show_prices BOOLEAN NOT NULL DEFAULT FALSE,
+    created_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    UNIQUE(token),
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_game_lists
+        FOREIGN KEY(list_id)
+            REFERENCES game_lists(id)
+            ON DELETE CASCADE
+);
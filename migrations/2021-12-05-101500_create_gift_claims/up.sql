@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE gift_claims (
+    id UUID PRIMARY KEY,
+    wished_game_id UUID NOT NULL,
+    claimer_name VARCHAR,
+    claimer_ip_hash VARCHAR NOT NULL,
+    created_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    UNIQUE(wished_game_id),
+    CONSTRAINT fk_wished_games
+        FOREIGN KEY(wished_game_id)
+            REFERENCES wished_games(id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX idx_gift_claims_ip_created_on ON gift_claims (claimer_ip_hash, created_on);
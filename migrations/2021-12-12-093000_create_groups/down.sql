@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE group_invites;
+DROP TABLE group_members;
+DROP TABLE groups;
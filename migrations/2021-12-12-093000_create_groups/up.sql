@@ -0,0 +1,51 @@
+-- Your SQL goes here
+CREATE TABLE groups (
+    id UUID PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    owner_id UUID NOT NULL,
+    notify_releases BOOLEAN NOT NULL DEFAULT FALSE,
+    created_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    CONSTRAINT fk_users
+        FOREIGN KEY(owner_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+CREATE TABLE group_members (
+    group_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    joined_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    PRIMARY KEY(group_id, user_id),
+    CONSTRAINT fk_groups
+        FOREIGN KEY(group_id)
+            REFERENCES groups(id)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX idx_group_members_user_id ON group_members (user_id);
+
+CREATE TABLE group_invites (
+    id UUID PRIMARY KEY,
+    group_id UUID NOT NULL,
+    email VARCHAR,
+    token_hash VARCHAR NOT NULL,
+    created_by UUID NOT NULL,
+    expires_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    created_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    UNIQUE(token_hash),
+    CONSTRAINT fk_groups
+        FOREIGN KEY(group_id)
+            REFERENCES groups(id)
+            ON DELETE CASCADE,
+    CONSTRAINT fk_users
+        FOREIGN KEY(created_by)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
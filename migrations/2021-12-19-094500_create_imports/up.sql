@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE imports (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    source VARCHAR NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'pending',
+    input_rows JSONB NOT NULL,
+    report JSONB,
+    error VARCHAR,
+    created_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    updated_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    CHECK (status IN ('pending', 'resolving', 'review', 'committed', 'failed')),
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX idx_imports_user_id ON imports (user_id);
@@ -0,0 +1,23 @@
+-- Your SQL goes here
+CREATE TABLE sync_runs (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    store_name VARCHAR NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'queued',
+    task_id VARCHAR,
+    items_total INTEGER,
+    items_imported INTEGER NOT NULL DEFAULT 0,
+    error VARCHAR,
+    created_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+    started_on TIMESTAMP WITHOUT TIME ZONE,
+    finished_on TIMESTAMP WITHOUT TIME ZONE,
+    updated_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    CHECK (status IN ('queued', 'running', 'succeeded', 'failed')),
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX idx_sync_runs_user_store ON sync_runs (user_id, store_name, created_on);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE user_settings
+ALTER COLUMN notification_channels SET DEFAULT '{webhook,email}';
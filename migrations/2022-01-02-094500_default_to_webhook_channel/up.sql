@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE user_settings
+ALTER COLUMN notification_channels SET DEFAULT '{webhook}';
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_sync_runs_one_active;
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+-- Older duplicates would break the index, only the newest unfinished run stays
+UPDATE sync_runs
+SET status = 'failed',
+    error = 'Replaced by a newer sync',
+    finished_on = timezone('utc', now()),
+    updated_on = timezone('utc', now())
+WHERE status IN ('queued', 'running')
+    AND EXISTS (
+        SELECT 1 FROM sync_runs newer
+        WHERE newer.user_id = sync_runs.user_id
+            AND newer.store_name = sync_runs.store_name
+            AND newer.status IN ('queued', 'running')
+            AND newer.created_on > sync_runs.created_on
+    );
+
+CREATE UNIQUE INDEX idx_sync_runs_one_active ON sync_runs (user_id, store_name)
+WHERE status IN ('queued', 'running');
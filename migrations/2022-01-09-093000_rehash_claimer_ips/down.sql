@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+-- The removed hashes can't be restored
+SELECT 1;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- The old hashes were plain SHA-256 and can be reversed, the rate limit only needs a day of claims
+UPDATE gift_claims SET claimer_ip_hash = '';
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE failed_logins (
+    id UUID PRIMARY KEY,
+    email VARCHAR NOT NULL,
+    ip_address VARCHAR NOT NULL,
+    created_on TIMESTAMP WITHOUT TIME ZONE NOT NULL
+);
+
+CREATE INDEX idx_failed_logins_email ON failed_logins (email, created_on);
+CREATE INDEX idx_failed_logins_ip_address ON failed_logins (ip_address, created_on);
+CREATE INDEX idx_failed_logins_created_on ON failed_logins (created_on);
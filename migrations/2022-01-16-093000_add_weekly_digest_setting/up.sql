@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE user_settings
+ADD COLUMN weekly_digest BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Digest mode users got the digest so far, keep sending it to them
+UPDATE user_settings SET weekly_digest = TRUE WHERE delivery_mode = 'digest';
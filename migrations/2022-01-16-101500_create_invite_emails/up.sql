@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE invite_emails (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    created_on TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+
+    CONSTRAINT fk_users
+        FOREIGN KEY(user_id)
+            REFERENCES users(id)
+            ON DELETE CASCADE
+);
+
+CREATE INDEX idx_invite_emails_user_id ON invite_emails (user_id, created_on);
+CREATE INDEX idx_invite_emails_created_on ON invite_emails (created_on);
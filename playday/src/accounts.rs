@@ -0,0 +1,193 @@
+use anyhow::{anyhow, Result};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{Duration, NaiveDateTime, Utc};
+use tera::{Context, Tera};
+use uuid::Uuid;
+
+use crate::api_tokens::hash_token;
+use crate::mailer::{self, Mailer};
+use crate::models::{CredentialToken, FailedLogin, User};
+
+pub const PURPOSE_VERIFY_EMAIL: &str = "verify_email";
+pub const PURPOSE_RESET_PASSWORD: &str = "reset_password";
+
+const VERIFY_EMAIL_HOURS: i64 = 24;
+const RESET_PASSWORD_HOURS: i64 = 1;
+
+pub const MIN_PASSWORD_LENGTH: usize = 10;
+const MAX_PASSWORD_LENGTH: usize = 256;
+/// Hash of a password nobody has, made with the default Argon2 parameters. Logins for
+/// emails without an account are checked against it, so they take as long to refuse as
+/// wrong passwords.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=4096,t=3,p=1$tzo01yUqNvG7GIJndZ2XCw$5ax3z6h9IxXpVJfWrF7KsSlLVDTyG/fnz2tKjzvbgks";
+
+/// Failed logins count against the email and the address for this long.
+const FAILED_LOGINS_WINDOW_MINUTES: i64 = 15;
+const MAX_FAILED_LOGINS_PER_EMAIL: i64 = 5;
+/// Higher than per email, people behind one NAT share an address.
+const MAX_FAILED_LOGINS_PER_IP: i64 = 20;
+
+/// Emails are compared lowercased, `Foo@Example.com` and `foo@example.com` are one account.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+pub fn is_valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => !local.is_empty() && domain.contains('.') && !email.contains(' '),
+        None => false,
+    }
+}
+
+pub fn validate_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "Passwords must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+    if password.len() > MAX_PASSWORD_LENGTH {
+        return Err("Password is too long".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|error| anyhow!("Hashing password failed: {}", error))?;
+
+    Ok(password_hash.to_string())
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed_hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok(),
+        Err(error) => {
+            log::error!("Invalid password hash in credentials! {}", error);
+            false
+        }
+    }
+}
+
+/// Does the work of a password check for an email without an account.
+pub fn check_dummy_password(password: &str) {
+    verify_password(password, DUMMY_PASSWORD_HASH);
+}
+
+pub fn failed_logins_window_start(now: NaiveDateTime) -> NaiveDateTime {
+    now - Duration::minutes(FAILED_LOGINS_WINDOW_MINUTES)
+}
+
+/// Logins are refused without checking the password once the email or the address has
+/// failed too often in the window.
+pub fn is_login_throttled(failed_by_email: i64, failed_by_ip: i64) -> bool {
+    failed_by_email >= MAX_FAILED_LOGINS_PER_EMAIL || failed_by_ip >= MAX_FAILED_LOGINS_PER_IP
+}
+
+pub fn new_failed_login(email: &str, ip_address: &str) -> FailedLogin {
+    FailedLogin {
+        id: Uuid::new_v4(),
+        email: email.to_string(),
+        ip_address: ip_address.to_string(),
+        created_on: Utc::now().naive_utc(),
+    }
+}
+
+/// Builds a single use token for email links. Returns the row, which only has the hash,
+/// and the token to put in the link.
+pub fn new_token(usr_id: Uuid, purpose: &str) -> (CredentialToken, String) {
+    let token = format!(
+        "{}{}",
+        Uuid::new_v4().to_simple(),
+        Uuid::new_v4().to_simple()
+    );
+    let valid_hours = match purpose {
+        PURPOSE_RESET_PASSWORD => RESET_PASSWORD_HOURS,
+        _ => VERIFY_EMAIL_HOURS,
+    };
+    let now_utc = Utc::now().naive_utc();
+
+    let credential_token = CredentialToken {
+        id: Uuid::new_v4(),
+        user_id: usr_id,
+        purpose: purpose.to_string(),
+        token_hash: hash_token(&token),
+        expires_at: now_utc + Duration::hours(valid_hours),
+        created_at: now_utc,
+    };
+
+    (credential_token, token)
+}
+
+/// Sends the verification and password reset emails for local accounts.
+pub struct AccountMailer {
+    mailer: Mailer,
+    templates: Tera,
+}
+
+impl AccountMailer {
+    pub fn new() -> Result<AccountMailer> {
+        Ok(AccountMailer {
+            mailer: Mailer::new()?,
+            templates: mailer::load_templates()?,
+        })
+    }
+
+    fn send(&self, user: &User, subject: &str, template: &str, link: &str) -> Result<()> {
+        let mut tera_data = Context::new();
+        tera_data.insert("title", subject);
+        tera_data.insert("user_name", &user.name);
+        tera_data.insert("link", link);
+
+        let text_body = self
+            .templates
+            .render(&format!("{}.txt", template), &tera_data)?;
+        let html_body = self
+            .templates
+            .render(&format!("{}.html", template), &tera_data)?;
+
+        self.mailer.send(&user.email, subject, text_body, html_body)
+    }
+
+    pub fn send_verification(&self, user: &User, link: &str) -> Result<()> {
+        self.send(user, "Confirm your Playday account", "verify_email", link)
+    }
+
+    pub fn send_password_reset(&self, user: &User, link: &str) -> Result<()> {
+        self.send(user, "Reset your Playday password", "reset_password", link)
+    }
+
+    /// Sent instead of an error when somebody registers with an email that has an account.
+    pub fn send_account_exists(&self, user: &User, link: &str) -> Result<()> {
+        self.send(user, "You already have a Playday account", "account_exists", link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use argon2::Params;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn dummy_hash_costs_as_much_as_real_hashes() {
+        let dummy_hash = PasswordHash::new(DUMMY_PASSWORD_HASH).unwrap();
+        let real_hash = hash_password("correct horse battery").unwrap();
+        let real_hash = PasswordHash::new(&real_hash).unwrap();
+
+        assert_eq!(dummy_hash.algorithm, real_hash.algorithm);
+        assert_eq!(dummy_hash.version, real_hash.version);
+        assert_eq!(
+            Params::try_from(&dummy_hash).unwrap(),
+            Params::try_from(&real_hash).unwrap()
+        );
+    }
+}
@@ -4,11 +4,30 @@ use chrono::Utc;
 use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager};
 use diesel::result::Error;
+use diesel::sql_types::{Float8, Integer, Nullable, SmallInt, VarChar};
 use uuid::Uuid;
 
 use crate::dtos;
-use crate::models::{GameStore, User, WishedGame};
+use crate::models::{
+    ApiToken, Credential, CredentialToken, Digest, FailedLogin, GameList, GameListEntry, GameStore, GiftClaim, Group, GroupInvite, GroupMember, Import, InviteEmail, ReleaseDateChange, Session, ShareLink, SyncRun, Tag, User,
+    UserIdentity, UserSettings, Webhook, WebhookDelivery, WishedGame, WishedGameTag,
+};
 use crate::types;
+use crate::wishlist::{self, GameStatus, ReleaseStatus, SortKey, WishlistQuery, WishlistSort};
+
+sql_function! {
+    #[sql_name = "coalesce"]
+    fn coalesce_float8(x: Nullable<Float8>, y: Float8) -> Float8;
+}
+sql_function! {
+    #[sql_name = "coalesce"]
+    fn coalesce_int2(x: Nullable<SmallInt>, y: SmallInt) -> SmallInt;
+}
+sql_function! {
+    #[sql_name = "coalesce"]
+    fn coalesce_int4(x: Nullable<Integer>, y: Integer) -> Integer;
+}
+sql_function!(fn lower(x: VarChar) -> VarChar);
 
 pub fn run_migrations(conn: &PgConnection) {
     let _ = diesel_migrations::run_pending_migrations(&*conn);
@@ -28,14 +47,11 @@ pub fn establish_connection() -> PgConnection {
     PgConnection::establish(&database_url).expect(&format!("Error connecting to db"))
 }
 
-pub fn get_user_by_email(db_conn: &PgConnection, user_email: &str) -> Result<Option<User>, Error> {
-    // It is common when using Diesel with Actix web to import schema-related
-    // modules inside a function's scope (rather than the normal module's scope)
-    // to prevent import collisions and namespace pollution.
+pub fn get_user_by_id(db_conn: &PgConnection, usr_id: Uuid) -> Result<Option<User>, Error> {
     use crate::schema::users::dsl::*;
 
     let user = users
-        .filter(email.eq(user_email.to_owned()))
+        .filter(id.eq(usr_id))
         .first::<User>(db_conn)
         .optional()?;
 
@@ -53,6 +69,20 @@ pub fn update_user_login_time(db_conn: &PgConnection, user_id: Uuid) -> Result<b
     Ok(true)
 }
 
+pub fn update_user_name(
+    db_conn: &PgConnection,
+    user_id: Uuid,
+    new_name: &str,
+) -> Result<bool, Error> {
+    use crate::schema::users::dsl::*;
+
+    diesel::update(users.filter(id.eq(user_id)))
+        .set(name.eq(new_name))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
 pub fn create_user(db_conn: &PgConnection, new_user: &User) -> Result<bool, Error> {
     // It is common when using Diesel with Actix web to import schema-related
     // modules inside a function's scope (rather than the normal module's scope)
@@ -66,6 +96,75 @@ pub fn create_user(db_conn: &PgConnection, new_user: &User) -> Result<bool, Erro
     Ok(true)
 }
 
+pub fn get_user_identity(
+    db_conn: &PgConnection,
+    provider_name: &str,
+    subject_id: &str,
+) -> Result<Option<UserIdentity>, Error> {
+    use crate::schema::user_identities::dsl::*;
+
+    let identity = user_identities
+        .filter(provider.eq(provider_name).and(subject.eq(subject_id)))
+        .first::<UserIdentity>(db_conn)
+        .optional()?;
+
+    Ok(identity)
+}
+
+pub fn create_user_identity(
+    db_conn: &PgConnection,
+    identity: &UserIdentity,
+) -> Result<bool, Error> {
+    use crate::schema::user_identities::dsl::*;
+
+    diesel::insert_into(user_identities)
+        .values(identity)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn update_user_identity_login(
+    db_conn: &PgConnection,
+    identity_id: Uuid,
+    identity_email: Option<&str>,
+) -> Result<bool, Error> {
+    use crate::schema::user_identities::dsl::*;
+
+    diesel::update(user_identities.filter(id.eq(identity_id)))
+        .set((
+            email.eq(identity_email),
+            last_login.eq(Utc::now().naive_utc()),
+        ))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Users from before identities were tracked, matched by email once to link their account.
+/// Local accounts are never matched, they have a password of their own.
+pub fn get_unlinked_user_by_email(
+    db_conn: &PgConnection,
+    user_email: &str,
+) -> Result<Option<User>, Error> {
+    use crate::schema::users::dsl::*;
+    use crate::schema::{credentials, user_identities};
+    use diesel::dsl::{exists, not};
+
+    let user = users
+        .filter(email.eq(user_email))
+        .filter(not(exists(
+            user_identities::table.filter(user_identities::user_id.eq(id)),
+        )))
+        .filter(not(exists(
+            credentials::table.filter(credentials::user_id.eq(id)),
+        )))
+        .first::<User>(db_conn)
+        .optional()?;
+
+    Ok(user)
+}
+
 pub fn add_games_to_wishlist(
     db_conn: &PgConnection,
     games: &Vec<WishedGame>,
@@ -89,10 +188,204 @@ pub fn get_games_from_wishlist(
 
     let results = wished_games
         .filter(user_id.eq(usr_id))
+        .order((added_on.asc(), id.asc()))
+        .load::<WishedGame>(db_conn)?;
+    Ok(results)
+}
+
+/// Which of `game_ids` (IGDB ids) are on the user's wishlist already.
+pub fn get_wished_igdb_ids(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    game_ids: &[i32],
+) -> Result<Vec<i32>, Error> {
+    use crate::schema::wished_games::dsl::*;
+
+    let results = wished_games
+        .filter(user_id.eq(usr_id).and(igdb_id.eq_any(game_ids)))
+        .select(igdb_id)
+        .load::<i32>(db_conn)?;
+    Ok(results)
+}
+
+/// Orders the query by `$column` (then id) and starts it after the cursor, if it is one for `$key`.
+macro_rules! sort_wishlist_page {
+    ($sql_query:ident, $query:expr, $key:path, $column:expr) => {{
+        let after = match &$query.after {
+            Some(cursor) => match &cursor.key {
+                $key(value) => Some((value.clone(), cursor.id)),
+                _ => None,
+            },
+            None => None,
+        };
+
+        if let Some((value, last_id)) = after {
+            $sql_query = if $query.descending {
+                $sql_query.filter(
+                    $column
+                        .lt(value.clone())
+                        .or($column.eq(value).and(id.lt(last_id))),
+                )
+            } else {
+                $sql_query.filter(
+                    $column
+                        .gt(value.clone())
+                        .or($column.eq(value).and(id.gt(last_id))),
+                )
+            };
+        }
+
+        if $query.descending {
+            $sql_query.order(($column.desc(), id.desc()))
+        } else {
+            $sql_query.order(($column.asc(), id.asc()))
+        }
+    }};
+}
+
+/// One page of the user's wishlist, fetches `limit + 1` games so the caller can tell
+/// whether there is a next page.
+pub fn get_wishlist_page(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    query: &WishlistQuery,
+) -> Result<Vec<WishedGame>, Error> {
+    use crate::schema::wished_games::dsl::*;
+
+    let mut sql_query = wished_games.filter(user_id.eq(usr_id)).into_boxed();
+
+    if let Some(game_status) = query.status {
+        sql_query = sql_query.filter(status.eq(game_status.as_str()));
+    }
+
+    sql_query = match query.owned {
+        Some(true) => sql_query.filter(status.ne(GameStatus::Wishlist.as_str())),
+        Some(false) => sql_query.filter(status.eq(GameStatus::Wishlist.as_str())),
+        None => sql_query,
+    };
+
+    let now = Utc::now().timestamp();
+    sql_query = match query.release {
+        Some(ReleaseStatus::Released) => {
+            sql_query.filter(pc_release_date.gt(0).and(pc_release_date.le(now)))
+        }
+        Some(ReleaseStatus::Unreleased) => sql_query.filter(pc_release_date.gt(now)),
+        Some(ReleaseStatus::Tbd) => sql_query.filter(pc_release_date.eq(0)),
+        None => sql_query,
+    };
+
+    if let Some(platform) = &query.platform {
+        sql_query = sql_query.filter(platform_slugs.contains(vec![platform.to_owned()]));
+    }
+
+    if let Some(list) = query.list {
+        use crate::schema::game_list_entries;
+
+        sql_query = sql_query.filter(
+            id.eq_any(
+                game_list_entries::table
+                    .select(game_list_entries::wished_game_id)
+                    .filter(game_list_entries::list_id.eq(list)),
+            ),
+        );
+    }
+    if let Some(tag) = query.tag {
+        use crate::schema::wished_game_tags;
+
+        sql_query = sql_query.filter(
+            id.eq_any(
+                wished_game_tags::table
+                    .select(wished_game_tags::wished_game_id)
+                    .filter(wished_game_tags::tag_id.eq(tag)),
+            ),
+        );
+    }
+
+    if let Some(min_priority) = query.min_priority {
+        sql_query = sql_query.filter(priority.ge(min_priority));
+    }
+    if let Some(min_personal_rating) = query.min_personal_rating {
+        sql_query = sql_query.filter(personal_rating.ge(min_personal_rating));
+    }
+
+    if let Some(search) = &query.search {
+        let escaped = search
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        sql_query = sql_query.filter(title.ilike(format!("%{}%", escaped)));
+    }
+
+    sql_query = match query.sort {
+        WishlistSort::ReleaseDate => {
+            sort_wishlist_page!(sql_query, query, SortKey::ReleaseDate, pc_release_date)
+        }
+        WishlistSort::AddedOn => sort_wishlist_page!(sql_query, query, SortKey::AddedOn, added_on),
+        WishlistSort::Title => sort_wishlist_page!(sql_query, query, SortKey::Title, lower(title)),
+        WishlistSort::Rating => sort_wishlist_page!(
+            sql_query,
+            query,
+            SortKey::Rating,
+            coalesce_float8(total_rating, wishlist::MISSING_RATING)
+        ),
+        WishlistSort::Priority => {
+            sort_wishlist_page!(sql_query, query, SortKey::Priority, priority)
+        }
+        WishlistSort::PersonalRating => sort_wishlist_page!(
+            sql_query,
+            query,
+            SortKey::PersonalRating,
+            coalesce_int2(personal_rating, wishlist::MISSING_PERSONAL_RATING)
+        ),
+        WishlistSort::MaxPrice => sort_wishlist_page!(
+            sql_query,
+            query,
+            SortKey::MaxPrice,
+            coalesce_int4(max_price_cents, wishlist::MISSING_MAX_PRICE)
+        ),
+    };
+
+    let results = sql_query
+        .limit(query.limit + 1)
         .load::<WishedGame>(db_conn)?;
     Ok(results)
 }
 
+pub fn get_wished_game(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    game_id: Uuid,
+) -> Result<Option<WishedGame>, Error> {
+    use crate::schema::wished_games::dsl::*;
+
+    let game = wished_games
+        .filter(id.eq(game_id).and(user_id.eq(usr_id)))
+        .first::<WishedGame>(db_conn)
+        .optional()?;
+
+    Ok(game)
+}
+
+/// Saves the fields a user can edit. Release data is left alone, it may have been
+/// refreshed by the worker since `game` was read.
+pub fn update_wished_game(db_conn: &PgConnection, game: &WishedGame) -> Result<WishedGame, Error> {
+    use crate::schema::wished_games::dsl::*;
+
+    let game = diesel::update(wished_games.filter(id.eq(game.id).and(user_id.eq(game.user_id))))
+        .set((
+            status.eq(&game.status),
+            started_on.eq(game.started_on),
+            finished_on.eq(game.finished_on),
+            priority.eq(game.priority),
+            notes.eq(&game.notes),
+            personal_rating.eq(game.personal_rating),
+            max_price_cents.eq(game.max_price_cents),
+        ))
+        .get_result::<WishedGame>(db_conn)?;
+
+    Ok(game)
+}
+
 pub fn remove_game_from_wishlist(
     db_conn: &PgConnection,
     usr_id: Uuid,
@@ -112,15 +405,104 @@ pub fn get_all_wishlist_games(db_conn: &PgConnection) -> Result<Vec<WishedGame>,
     Ok(results)
 }
 
+/// Games still on the wishlist with a PC release date in the future, or in the last day.
+/// Release dates are midnight UTC, the release day lasts longer than that for most
+/// timezones and the callers check the day in the user's timezone.
 pub fn get_future_wishlist_games(db_conn: &PgConnection) -> Result<Vec<WishedGame>, Error> {
     use crate::schema::wished_games::dsl::*;
 
+    let since = Utc::now() - chrono::Duration::days(1);
     let results = wished_games
-        .filter(pc_release_date.gt(Utc::now().timestamp()))
+        .filter(status.eq(GameStatus::Wishlist.as_str()))
+        .filter(pc_release_date.gt(since.timestamp()))
         .load::<WishedGame>(db_conn)?;
     Ok(results)
 }
 
+/// Games that are not out yet, including those without a PC release date so far.
+pub fn get_unreleased_wishlist_games(db_conn: &PgConnection) -> Result<Vec<WishedGame>, Error> {
+    use crate::schema::wished_games::dsl::*;
+
+    let results = wished_games
+        .filter(pc_release_date.gt(Utc::now().timestamp()).or(pc_release_date.eq(0)))
+        .load::<WishedGame>(db_conn)?;
+    Ok(results)
+}
+
+/// Games still on the user's wishlist with a PC release date in `[from_date, to_date)`.
+pub fn get_wishlist_games_releasing_between(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    from_date: i64,
+    to_date: i64,
+) -> Result<Vec<WishedGame>, Error> {
+    use crate::schema::wished_games::dsl::*;
+
+    let results = wished_games
+        .filter(user_id.eq(usr_id))
+        .filter(status.eq(GameStatus::Wishlist.as_str()))
+        .filter(pc_release_date.ge(from_date).and(pc_release_date.lt(to_date)))
+        .order(pc_release_date.asc())
+        .load::<WishedGame>(db_conn)?;
+    Ok(results)
+}
+
+pub fn update_wished_game_release(
+    db_conn: &PgConnection,
+    game_id: Uuid,
+    info: &serde_json::Value,
+    release_date: i64,
+    rating: Option<f64>,
+    platforms: &[String],
+) -> Result<bool, Error> {
+    use crate::schema::wished_games::dsl::*;
+
+    diesel::update(wished_games.filter(id.eq(game_id)))
+        .set((
+            igdb_info.eq(info),
+            pc_release_date.eq(release_date),
+            total_rating.eq(rating),
+            platform_slugs.eq(platforms),
+        ))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn add_release_date_change(
+    db_conn: &PgConnection,
+    change: &ReleaseDateChange,
+) -> Result<bool, Error> {
+    use crate::schema::release_date_changes::dsl::*;
+
+    diesel::insert_into(release_date_changes)
+        .values(change)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_release_date_changes_since(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    since: chrono::NaiveDateTime,
+) -> Result<Vec<ReleaseDateChange>, Error> {
+    use crate::schema::release_date_changes::dsl::*;
+    use crate::schema::wished_games;
+
+    // Only games still on the wishlist, the others are already bought
+    let wished_ids = wished_games::table
+        .select(wished_games::id)
+        .filter(wished_games::status.eq(GameStatus::Wishlist.as_str()));
+
+    let changes = release_date_changes
+        .filter(user_id.eq(usr_id).and(changed_on.gt(since)))
+        .filter(wished_game_id.eq_any(wished_ids))
+        .order(changed_on.asc())
+        .load::<ReleaseDateChange>(db_conn)?;
+    Ok(changes)
+}
+
 pub fn save_epicgames_login(db_conn: &PgConnection, game_store: &GameStore) -> Result<bool, Error> {
     // It is common when using Diesel with Actix web to import schema-related
     // modules inside a function's scope (rather than the normal module's scope)
@@ -163,6 +545,37 @@ pub fn get_game_store_account(
     Ok(store)
 }
 
+pub fn get_game_store_token(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    stre_name: &str,
+) -> Result<Option<serde_json::Value>, Error> {
+    use crate::schema::game_stores::dsl::*;
+
+    let token = game_stores
+        .filter(user_id.eq(usr_id).and(store_name.eq(stre_name)))
+        .select(store_token)
+        .first::<serde_json::Value>(db_conn)
+        .optional()?;
+
+    Ok(token)
+}
+
+pub fn update_game_store_token(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    stre_name: &str,
+    token: &serde_json::Value,
+) -> Result<bool, Error> {
+    use crate::schema::game_stores::dsl::*;
+
+    diesel::update(game_stores.filter(user_id.eq(usr_id).and(store_name.eq(stre_name))))
+        .set((store_token.eq(token), updated_on.eq(Utc::now().naive_utc())))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
 pub fn get_user_game_stores(
     db_conn: &PgConnection,
     usr_id: Uuid,
@@ -202,3 +615,1327 @@ pub fn remove_game_store(
 
     Ok(true)
 }
+
+pub fn create_webhook(db_conn: &PgConnection, webhook: &Webhook) -> Result<bool, Error> {
+    use crate::schema::webhooks::dsl::*;
+
+    diesel::insert_into(webhooks)
+        .values(webhook)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_user_webhooks(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+) -> Result<Vec<dtos::Webhook>, Error> {
+    use crate::schema::webhooks::dsl::*;
+
+    let hooks = webhooks
+        .filter(user_id.eq(usr_id))
+        .select((id, url, payload_format, added_on, user_id))
+        .order(added_on.asc())
+        .load::<dtos::Webhook>(db_conn)?;
+
+    Ok(hooks)
+}
+
+/// Includes the signing secrets, for sending deliveries. Routes list webhooks with
+/// `get_user_webhooks`, which leaves them out.
+pub fn get_webhooks_with_secrets(db_conn: &PgConnection, usr_id: Uuid) -> Result<Vec<Webhook>, Error> {
+    use crate::schema::webhooks::dsl::*;
+
+    let hooks = webhooks
+        .filter(user_id.eq(usr_id))
+        .load::<Webhook>(db_conn)?;
+
+    Ok(hooks)
+}
+
+pub fn get_user_webhook(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    webhook_id: Uuid,
+) -> Result<Option<Webhook>, Error> {
+    use crate::schema::webhooks::dsl::*;
+
+    let hook = webhooks
+        .filter(id.eq(webhook_id).and(user_id.eq(usr_id)))
+        .first::<Webhook>(db_conn)
+        .optional()?;
+
+    Ok(hook)
+}
+
+pub fn get_webhook(db_conn: &PgConnection, webhook_id: Uuid) -> Result<Option<Webhook>, Error> {
+    use crate::schema::webhooks::dsl::*;
+
+    let hook = webhooks
+        .filter(id.eq(webhook_id))
+        .first::<Webhook>(db_conn)
+        .optional()?;
+
+    Ok(hook)
+}
+
+pub fn remove_webhook(db_conn: &PgConnection, usr_id: Uuid, webhook_id: Uuid) -> Result<bool, Error> {
+    use crate::schema::webhooks::dsl::*;
+
+    diesel::delete(webhooks.filter(id.eq(webhook_id).and(user_id.eq(usr_id)))).execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Returns `false` when a delivery with the same `event_key` already exists for the webhook,
+/// which is how we avoid sending the same reminder twice.
+pub fn create_webhook_delivery(
+    db_conn: &PgConnection,
+    delivery: &WebhookDelivery,
+) -> Result<bool, Error> {
+    use crate::schema::webhook_deliveries::dsl::*;
+
+    let inserted = diesel::insert_into(webhook_deliveries)
+        .values(delivery)
+        .on_conflict((webhook_id, event_key))
+        .do_nothing()
+        .execute(db_conn)?;
+
+    Ok(inserted > 0)
+}
+
+pub fn get_webhook_delivery(
+    db_conn: &PgConnection,
+    delivery_id: Uuid,
+) -> Result<Option<WebhookDelivery>, Error> {
+    use crate::schema::webhook_deliveries::dsl::*;
+
+    let delivery = webhook_deliveries
+        .filter(id.eq(delivery_id))
+        .first::<WebhookDelivery>(db_conn)
+        .optional()?;
+
+    Ok(delivery)
+}
+
+pub fn update_webhook_delivery(
+    db_conn: &PgConnection,
+    delivery: &WebhookDelivery,
+) -> Result<bool, Error> {
+    use crate::schema::webhook_deliveries::dsl::*;
+
+    diesel::update(webhook_deliveries.filter(id.eq(delivery.id)))
+        .set(delivery)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_webhook_deliveries(
+    db_conn: &PgConnection,
+    hook_id: Uuid,
+) -> Result<Vec<WebhookDelivery>, Error> {
+    use crate::schema::webhook_deliveries::dsl::*;
+
+    let deliveries = webhook_deliveries
+        .filter(webhook_id.eq(hook_id))
+        .order(created_on.desc())
+        .limit(50)
+        .load::<WebhookDelivery>(db_conn)?;
+
+    Ok(deliveries)
+}
+
+pub fn get_user_settings(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+) -> Result<Option<UserSettings>, Error> {
+    use crate::schema::user_settings::dsl::*;
+
+    let settings = user_settings
+        .filter(user_id.eq(usr_id))
+        .first::<UserSettings>(db_conn)
+        .optional()?;
+
+    Ok(settings)
+}
+
+pub fn save_user_settings(db_conn: &PgConnection, settings: &UserSettings) -> Result<bool, Error> {
+    use crate::schema::user_settings::dsl::*;
+
+    diesel::insert_into(user_settings)
+        .values(settings)
+        .on_conflict(user_id)
+        .do_update()
+        .set(settings)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_all_users(db_conn: &PgConnection) -> Result<Vec<User>, Error> {
+    use crate::schema::users::dsl::*;
+
+    let results = users.load::<User>(db_conn)?;
+    Ok(results)
+}
+
+pub fn get_last_digest_time(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+) -> Result<Option<chrono::NaiveDateTime>, Error> {
+    use crate::schema::digests::dsl::*;
+
+    let last_sent = digests
+        .filter(user_id.eq(usr_id))
+        .select(diesel::dsl::max(sent_on))
+        .first::<Option<chrono::NaiveDateTime>>(db_conn)?;
+    Ok(last_sent)
+}
+
+pub fn add_digest(db_conn: &PgConnection, digest: &Digest) -> Result<bool, Error> {
+    use crate::schema::digests::dsl::*;
+
+    diesel::insert_into(digests).values(digest).execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn create_session(db_conn: &PgConnection, session: &Session) -> Result<bool, Error> {
+    use crate::schema::sessions::dsl::*;
+
+    diesel::insert_into(sessions).values(session).execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_session(db_conn: &PgConnection, session_id: Uuid) -> Result<Option<Session>, Error> {
+    use crate::schema::sessions::dsl::*;
+
+    let session = sessions
+        .filter(id.eq(session_id))
+        .first::<Session>(db_conn)
+        .optional()?;
+
+    Ok(session)
+}
+
+pub fn update_session_last_seen(db_conn: &PgConnection, session_id: Uuid) -> Result<bool, Error> {
+    use crate::schema::sessions::dsl::*;
+
+    diesel::update(sessions.filter(id.eq(session_id)))
+        .set(last_seen.eq(Utc::now().naive_utc()))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_user_sessions(db_conn: &PgConnection, usr_id: Uuid) -> Result<Vec<Session>, Error> {
+    use crate::schema::sessions::dsl::*;
+
+    let results = sessions
+        .filter(user_id.eq(usr_id))
+        .order(last_seen.desc())
+        .load::<Session>(db_conn)?;
+
+    Ok(results)
+}
+
+pub fn remove_session(db_conn: &PgConnection, usr_id: Uuid, session_id: Uuid) -> Result<bool, Error> {
+    use crate::schema::sessions::dsl::*;
+
+    diesel::delete(sessions.filter(id.eq(session_id).and(user_id.eq(usr_id)))).execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Removes the user's sessions last seen before `idle_cutoff` or created before `max_age_cutoff`.
+pub fn remove_expired_sessions(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    idle_cutoff: chrono::NaiveDateTime,
+    max_age_cutoff: chrono::NaiveDateTime,
+) -> Result<bool, Error> {
+    use crate::schema::sessions::dsl::*;
+
+    diesel::delete(
+        sessions
+            .filter(user_id.eq(usr_id))
+            .filter(last_seen.lt(idle_cutoff).or(created_at.lt(max_age_cutoff))),
+    )
+    .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn remove_user_sessions(db_conn: &PgConnection, usr_id: Uuid) -> Result<bool, Error> {
+    use crate::schema::sessions::dsl::*;
+
+    diesel::delete(sessions.filter(user_id.eq(usr_id))).execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn create_api_token(db_conn: &PgConnection, api_token: &ApiToken) -> Result<bool, Error> {
+    use crate::schema::api_tokens::dsl::*;
+
+    diesel::insert_into(api_tokens)
+        .values(api_token)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_user_api_tokens(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+) -> Result<Vec<dtos::ApiToken>, Error> {
+    use crate::schema::api_tokens::dsl::*;
+
+    let tokens = api_tokens
+        .filter(user_id.eq(usr_id))
+        .select((id, name, token_prefix, scopes, created_at, last_used_at))
+        .order(created_at.asc())
+        .load::<dtos::ApiToken>(db_conn)?;
+
+    Ok(tokens)
+}
+
+pub fn get_api_token_by_hash(
+    db_conn: &PgConnection,
+    hash: &str,
+) -> Result<Option<ApiToken>, Error> {
+    use crate::schema::api_tokens::dsl::*;
+
+    let api_token = api_tokens
+        .filter(token_hash.eq(hash))
+        .first::<ApiToken>(db_conn)
+        .optional()?;
+
+    Ok(api_token)
+}
+
+pub fn update_api_token_last_used(db_conn: &PgConnection, token_id: Uuid) -> Result<bool, Error> {
+    use crate::schema::api_tokens::dsl::*;
+
+    diesel::update(api_tokens.filter(id.eq(token_id)))
+        .set(last_used_at.eq(Utc::now().naive_utc()))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn remove_api_token(db_conn: &PgConnection, usr_id: Uuid, token_id: Uuid) -> Result<bool, Error> {
+    use crate::schema::api_tokens::dsl::*;
+
+    diesel::delete(api_tokens.filter(id.eq(token_id).and(user_id.eq(usr_id)))).execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn create_credential(db_conn: &PgConnection, credential: &Credential) -> Result<bool, Error> {
+    use crate::schema::credentials::dsl::*;
+
+    diesel::insert_into(credentials)
+        .values(credential)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_credential_by_email(
+    db_conn: &PgConnection,
+    cred_email: &str,
+) -> Result<Option<Credential>, Error> {
+    use crate::schema::credentials::dsl::*;
+
+    let credential = credentials
+        .filter(email.eq(cred_email))
+        .first::<Credential>(db_conn)
+        .optional()?;
+
+    Ok(credential)
+}
+
+pub fn update_credential_password(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    new_password_hash: &str,
+) -> Result<bool, Error> {
+    use crate::schema::credentials::dsl::*;
+
+    diesel::update(credentials.filter(user_id.eq(usr_id)))
+        .set((
+            password_hash.eq(new_password_hash),
+            updated_on.eq(Utc::now().naive_utc()),
+        ))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn mark_credential_verified(db_conn: &PgConnection, usr_id: Uuid) -> Result<bool, Error> {
+    use crate::schema::credentials::dsl::*;
+
+    let now_utc = Utc::now().naive_utc();
+    diesel::update(credentials.filter(user_id.eq(usr_id).and(email_verified_at.is_null())))
+        .set((email_verified_at.eq(now_utc), updated_on.eq(now_utc)))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn create_credential_token(
+    db_conn: &PgConnection,
+    credential_token: &CredentialToken,
+) -> Result<bool, Error> {
+    use crate::schema::credential_tokens::dsl::*;
+
+    diesel::insert_into(credential_tokens)
+        .values(credential_token)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Deletes and returns the token if it exists, has the purpose and hasn't expired. Tokens
+/// are single use, other tokens for the same purpose are dropped along with it.
+pub fn take_credential_token(
+    db_conn: &PgConnection,
+    hash: &str,
+    token_purpose: &str,
+) -> Result<Option<CredentialToken>, Error> {
+    use crate::schema::credential_tokens::dsl::*;
+
+    db_conn.transaction(|| {
+        let credential_token = credential_tokens
+            .filter(token_hash.eq(hash).and(purpose.eq(token_purpose)))
+            .filter(expires_at.gt(Utc::now().naive_utc()))
+            .first::<CredentialToken>(db_conn)
+            .optional()?;
+
+        if let Some(credential_token) = &credential_token {
+            diesel::delete(
+                credential_tokens.filter(
+                    user_id
+                        .eq(credential_token.user_id)
+                        .and(purpose.eq(token_purpose)),
+                ),
+            )
+            .execute(db_conn)?;
+        }
+
+        Ok(credential_token)
+    })
+}
+
+/// Deletes the user's tokens for the purpose, links already sent stop working.
+pub fn revoke_credential_tokens(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    token_purpose: &str,
+) -> Result<bool, Error> {
+    use crate::schema::credential_tokens::dsl::*;
+
+    diesel::delete(credential_tokens.filter(user_id.eq(usr_id).and(purpose.eq(token_purpose))))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Records a failed password login and drops the ones too old to count anymore.
+pub fn create_failed_login(
+    db_conn: &PgConnection,
+    failed_login: &FailedLogin,
+    window_start: chrono::NaiveDateTime,
+) -> Result<bool, Error> {
+    use crate::schema::failed_logins::dsl::*;
+
+    diesel::delete(failed_logins.filter(created_on.le(window_start))).execute(db_conn)?;
+    diesel::insert_into(failed_logins)
+        .values(failed_login)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Counts the failed logins since `since` for the email and for the address.
+pub fn count_failed_logins_since(
+    db_conn: &PgConnection,
+    login_email: &str,
+    login_ip_address: &str,
+    since: chrono::NaiveDateTime,
+) -> Result<(i64, i64), Error> {
+    use crate::schema::failed_logins::dsl::*;
+
+    let by_email = failed_logins
+        .filter(email.eq(login_email).and(created_on.gt(since)))
+        .count()
+        .get_result(db_conn)?;
+    let by_ip_address = failed_logins
+        .filter(ip_address.eq(login_ip_address).and(created_on.gt(since)))
+        .count()
+        .get_result(db_conn)?;
+
+    Ok((by_email, by_ip_address))
+}
+
+pub fn delete_failed_logins_by_email(
+    db_conn: &PgConnection,
+    login_email: &str,
+) -> Result<bool, Error> {
+    use crate::schema::failed_logins::dsl::*;
+
+    diesel::delete(failed_logins.filter(email.eq(login_email))).execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn create_game_list(db_conn: &PgConnection, list: &GameList) -> Result<bool, Error> {
+    use crate::schema::game_lists::dsl::*;
+
+    diesel::insert_into(game_lists)
+        .values(list)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_user_game_lists(db_conn: &PgConnection, usr_id: Uuid) -> Result<Vec<GameList>, Error> {
+    use crate::schema::game_lists::dsl::*;
+
+    let results = game_lists
+        .filter(user_id.eq(usr_id))
+        .order(name.asc())
+        .load::<GameList>(db_conn)?;
+    Ok(results)
+}
+
+pub fn get_game_list(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    list_id: Uuid,
+) -> Result<Option<GameList>, Error> {
+    use crate::schema::game_lists::dsl::*;
+
+    let list = game_lists
+        .filter(id.eq(list_id).and(user_id.eq(usr_id)))
+        .first::<GameList>(db_conn)
+        .optional()?;
+
+    Ok(list)
+}
+
+pub fn rename_game_list(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    list_id: Uuid,
+    new_name: &str,
+) -> Result<Option<GameList>, Error> {
+    use crate::schema::game_lists::dsl::*;
+
+    let list = diesel::update(game_lists.filter(id.eq(list_id).and(user_id.eq(usr_id))))
+        .set(name.eq(new_name))
+        .get_result::<GameList>(db_conn)
+        .optional()?;
+
+    Ok(list)
+}
+
+pub fn remove_game_list(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    list_id: Uuid,
+) -> Result<bool, Error> {
+    use crate::schema::game_lists::dsl::*;
+
+    diesel::delete(game_lists.filter(id.eq(list_id).and(user_id.eq(usr_id)))).execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn add_game_to_list(db_conn: &PgConnection, entry: &GameListEntry) -> Result<bool, Error> {
+    use crate::schema::game_list_entries::dsl::*;
+
+    diesel::insert_into(game_list_entries)
+        .values(entry)
+        .on_conflict((list_id, wished_game_id))
+        .do_nothing()
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn remove_game_from_list(
+    db_conn: &PgConnection,
+    lst_id: Uuid,
+    game_id: Uuid,
+) -> Result<bool, Error> {
+    use crate::schema::game_list_entries::dsl::*;
+
+    diesel::delete(game_list_entries.filter(list_id.eq(lst_id).and(wished_game_id.eq(game_id))))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// `(wished_game_id, list_id)` of every list the games are in.
+pub fn get_lists_of_games(
+    db_conn: &PgConnection,
+    game_ids: &[Uuid],
+) -> Result<Vec<(Uuid, Uuid)>, Error> {
+    use crate::schema::game_list_entries::dsl::*;
+
+    let results = game_list_entries
+        .select((wished_game_id, list_id))
+        .filter(wished_game_id.eq_any(game_ids))
+        .load::<(Uuid, Uuid)>(db_conn)?;
+    Ok(results)
+}
+
+pub fn create_tag(db_conn: &PgConnection, tag: &Tag) -> Result<bool, Error> {
+    use crate::schema::tags::dsl::*;
+
+    diesel::insert_into(tags).values(tag).execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_user_tags(db_conn: &PgConnection, usr_id: Uuid) -> Result<Vec<Tag>, Error> {
+    use crate::schema::tags::dsl::*;
+
+    let results = tags
+        .filter(user_id.eq(usr_id))
+        .order(name.asc())
+        .load::<Tag>(db_conn)?;
+    Ok(results)
+}
+
+pub fn get_tag(db_conn: &PgConnection, usr_id: Uuid, tag_id: Uuid) -> Result<Option<Tag>, Error> {
+    use crate::schema::tags::dsl::*;
+
+    let tag = tags
+        .filter(id.eq(tag_id).and(user_id.eq(usr_id)))
+        .first::<Tag>(db_conn)
+        .optional()?;
+
+    Ok(tag)
+}
+
+pub fn rename_tag(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    tag_id: Uuid,
+    new_name: &str,
+) -> Result<Option<Tag>, Error> {
+    use crate::schema::tags::dsl::*;
+
+    let tag = diesel::update(tags.filter(id.eq(tag_id).and(user_id.eq(usr_id))))
+        .set(name.eq(new_name))
+        .get_result::<Tag>(db_conn)
+        .optional()?;
+
+    Ok(tag)
+}
+
+pub fn remove_tag(db_conn: &PgConnection, usr_id: Uuid, tag_id: Uuid) -> Result<bool, Error> {
+    use crate::schema::tags::dsl::*;
+
+    diesel::delete(tags.filter(id.eq(tag_id).and(user_id.eq(usr_id)))).execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn add_tag_to_game(db_conn: &PgConnection, game_tag: &WishedGameTag) -> Result<bool, Error> {
+    use crate::schema::wished_game_tags::dsl::*;
+
+    diesel::insert_into(wished_game_tags)
+        .values(game_tag)
+        .on_conflict((tag_id, wished_game_id))
+        .do_nothing()
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn remove_tag_from_game(
+    db_conn: &PgConnection,
+    tg_id: Uuid,
+    game_id: Uuid,
+) -> Result<bool, Error> {
+    use crate::schema::wished_game_tags::dsl::*;
+
+    diesel::delete(wished_game_tags.filter(tag_id.eq(tg_id).and(wished_game_id.eq(game_id))))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// `(wished_game_id, tag_id)` of every tag on the games.
+pub fn get_tags_of_games(
+    db_conn: &PgConnection,
+    game_ids: &[Uuid],
+) -> Result<Vec<(Uuid, Uuid)>, Error> {
+    use crate::schema::wished_game_tags::dsl::*;
+
+    let results = wished_game_tags
+        .select((wished_game_id, tag_id))
+        .filter(wished_game_id.eq_any(game_ids))
+        .load::<(Uuid, Uuid)>(db_conn)?;
+    Ok(results)
+}
+
+pub fn create_share_link(db_conn: &PgConnection, share_link: &ShareLink) -> Result<bool, Error> {
+    use crate::schema::share_links::dsl::*;
+
+    diesel::insert_into(share_links)
+        .values(share_link)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_user_share_links(db_conn: &PgConnection, usr_id: Uuid) -> Result<Vec<ShareLink>, Error> {
+    use crate::schema::share_links::dsl::*;
+
+    let results = share_links
+        .filter(user_id.eq(usr_id))
+        .order(created_on.desc())
+        .load::<ShareLink>(db_conn)?;
+    Ok(results)
+}
+
+pub fn get_share_link_by_token(
+    db_conn: &PgConnection,
+    share_token: &str,
+) -> Result<Option<ShareLink>, Error> {
+    use crate::schema::share_links::dsl::*;
+
+    let share_link = share_links
+        .filter(token.eq(share_token))
+        .first::<ShareLink>(db_conn)
+        .optional()?;
+
+    Ok(share_link)
+}
+
+pub fn remove_share_link(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    share_id: Uuid,
+) -> Result<bool, Error> {
+    use crate::schema::share_links::dsl::*;
+
+    diesel::delete(share_links.filter(id.eq(share_id).and(user_id.eq(usr_id)))).execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// The games a share link shows: those still on the wishlist, most wanted first.
+pub fn get_shared_games(
+    db_conn: &PgConnection,
+    share_link: &ShareLink,
+) -> Result<Vec<WishedGame>, Error> {
+    use crate::schema::wished_games::dsl::*;
+
+    let mut sql_query = wished_games
+        .filter(user_id.eq(share_link.user_id))
+        .filter(status.eq(GameStatus::Wishlist.as_str()))
+        .into_boxed();
+
+    if let Some(list) = share_link.list_id {
+        use crate::schema::game_list_entries;
+
+        sql_query = sql_query.filter(
+            id.eq_any(
+                game_list_entries::table
+                    .select(game_list_entries::wished_game_id)
+                    .filter(game_list_entries::list_id.eq(list)),
+            ),
+        );
+    }
+
+    let results = sql_query
+        .order((priority.desc(), pc_release_date.asc(), title.asc()))
+        .load::<WishedGame>(db_conn)?;
+    Ok(results)
+}
+
+/// Returns false when someone else claimed the game first.
+pub fn create_gift_claim(db_conn: &PgConnection, claim: &GiftClaim) -> Result<bool, Error> {
+    use crate::schema::gift_claims::dsl::*;
+
+    let inserted = diesel::insert_into(gift_claims)
+        .values(claim)
+        .on_conflict(wished_game_id)
+        .do_nothing()
+        .execute(db_conn)?;
+
+    Ok(inserted > 0)
+}
+
+pub fn get_gift_claims_for_games(
+    db_conn: &PgConnection,
+    game_ids: &[Uuid],
+) -> Result<Vec<GiftClaim>, Error> {
+    use crate::schema::gift_claims::dsl::*;
+
+    let results = gift_claims
+        .filter(wished_game_id.eq_any(game_ids))
+        .load::<GiftClaim>(db_conn)?;
+    Ok(results)
+}
+
+pub fn count_gift_claims_by_ip_since(
+    db_conn: &PgConnection,
+    ip_hash: &str,
+    since: chrono::NaiveDateTime,
+) -> Result<i64, Error> {
+    use crate::schema::gift_claims::dsl::*;
+
+    let count = gift_claims
+        .filter(claimer_ip_hash.eq(ip_hash).and(created_on.gt(since)))
+        .count()
+        .get_result(db_conn)?;
+    Ok(count)
+}
+
+pub fn remove_gift_claim(db_conn: &PgConnection, claim_id: Uuid) -> Result<bool, Error> {
+    use crate::schema::gift_claims::dsl::*;
+
+    diesel::delete(gift_claims.filter(id.eq(claim_id))).execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn remove_gift_claims_for_game(db_conn: &PgConnection, game_id: Uuid) -> Result<bool, Error> {
+    use crate::schema::gift_claims::dsl::*;
+
+    diesel::delete(gift_claims.filter(wished_game_id.eq(game_id))).execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Adds the group along with its owner as the first member.
+pub fn create_group(db_conn: &PgConnection, group: &Group) -> Result<bool, Error> {
+    use crate::schema::{group_members, groups};
+
+    db_conn.transaction(|| {
+        diesel::insert_into(groups::table)
+            .values(group)
+            .execute(db_conn)?;
+        diesel::insert_into(group_members::table)
+            .values(&GroupMember {
+                group_id: group.id,
+                user_id: group.owner_id,
+                joined_on: group.created_on,
+            })
+            .execute(db_conn)?;
+
+        Ok(true)
+    })
+}
+
+pub fn get_user_groups(db_conn: &PgConnection, usr_id: Uuid) -> Result<Vec<Group>, Error> {
+    use crate::schema::group_members;
+    use crate::schema::groups::dsl::*;
+
+    let results = groups
+        .filter(
+            id.eq_any(
+                group_members::table
+                    .select(group_members::group_id)
+                    .filter(group_members::user_id.eq(usr_id)),
+            ),
+        )
+        .order(name.asc())
+        .load::<Group>(db_conn)?;
+    Ok(results)
+}
+
+/// The group, as long as the user is one of its members.
+pub fn get_group(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    grp_id: Uuid,
+) -> Result<Option<Group>, Error> {
+    use crate::schema::group_members;
+    use crate::schema::groups::dsl::*;
+
+    let group = groups
+        .filter(id.eq(grp_id))
+        .filter(
+            id.eq_any(
+                group_members::table
+                    .select(group_members::group_id)
+                    .filter(group_members::user_id.eq(usr_id)),
+            ),
+        )
+        .first::<Group>(db_conn)
+        .optional()?;
+
+    Ok(group)
+}
+
+pub fn get_group_by_id(db_conn: &PgConnection, grp_id: Uuid) -> Result<Option<Group>, Error> {
+    use crate::schema::groups::dsl::*;
+
+    let group = groups
+        .filter(id.eq(grp_id))
+        .first::<Group>(db_conn)
+        .optional()?;
+
+    Ok(group)
+}
+
+/// Only the owner can change the group.
+pub fn update_group(db_conn: &PgConnection, group: &Group) -> Result<bool, Error> {
+    use crate::schema::groups::dsl::*;
+
+    diesel::update(groups.filter(id.eq(group.id).and(owner_id.eq(group.owner_id))))
+        .set((
+            name.eq(&group.name),
+            notify_releases.eq(group.notify_releases),
+        ))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn remove_group(db_conn: &PgConnection, usr_id: Uuid, grp_id: Uuid) -> Result<bool, Error> {
+    use crate::schema::groups::dsl::*;
+
+    diesel::delete(groups.filter(id.eq(grp_id).and(owner_id.eq(usr_id)))).execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Groups that asked for release reminders of the games their members share.
+pub fn get_notifying_groups(db_conn: &PgConnection) -> Result<Vec<Group>, Error> {
+    use crate::schema::groups::dsl::*;
+
+    let results = groups
+        .filter(notify_releases.eq(true))
+        .load::<Group>(db_conn)?;
+    Ok(results)
+}
+
+pub fn get_members_of_groups(
+    db_conn: &PgConnection,
+    group_ids: &[Uuid],
+) -> Result<Vec<GroupMember>, Error> {
+    use crate::schema::group_members::dsl::*;
+
+    let results = group_members
+        .filter(group_id.eq_any(group_ids))
+        .load::<GroupMember>(db_conn)?;
+    Ok(results)
+}
+
+pub fn get_group_members(
+    db_conn: &PgConnection,
+    grp_id: Uuid,
+) -> Result<Vec<dtos::GroupMember>, Error> {
+    use crate::schema::group_members::dsl::*;
+    use crate::schema::users;
+
+    let results = group_members
+        .inner_join(users::table.on(users::id.eq(user_id)))
+        .filter(group_id.eq(grp_id))
+        .select((users::id, users::name, joined_on))
+        .order(joined_on.asc())
+        .load::<dtos::GroupMember>(db_conn)?;
+    Ok(results)
+}
+
+pub fn count_group_members(db_conn: &PgConnection, grp_id: Uuid) -> Result<i64, Error> {
+    use crate::schema::group_members::dsl::*;
+
+    let count = group_members
+        .filter(group_id.eq(grp_id))
+        .count()
+        .get_result(db_conn)?;
+    Ok(count)
+}
+
+/// Returns false when the user already is a member.
+pub fn add_group_member(db_conn: &PgConnection, member: &GroupMember) -> Result<bool, Error> {
+    use crate::schema::group_members::dsl::*;
+
+    let inserted = diesel::insert_into(group_members)
+        .values(member)
+        .on_conflict((group_id, user_id))
+        .do_nothing()
+        .execute(db_conn)?;
+
+    Ok(inserted > 0)
+}
+
+pub fn remove_group_member(
+    db_conn: &PgConnection,
+    grp_id: Uuid,
+    usr_id: Uuid,
+) -> Result<bool, Error> {
+    use crate::schema::group_members::dsl::*;
+
+    diesel::delete(group_members.filter(group_id.eq(grp_id).and(user_id.eq(usr_id))))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn create_group_invite(db_conn: &PgConnection, invite: &GroupInvite) -> Result<bool, Error> {
+    use crate::schema::group_invites::dsl::*;
+
+    diesel::insert_into(group_invites)
+        .values(invite)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Invites that can still be used.
+pub fn get_group_invites(
+    db_conn: &PgConnection,
+    grp_id: Uuid,
+) -> Result<Vec<dtos::GroupInvite>, Error> {
+    use crate::schema::group_invites::dsl::*;
+
+    let results = group_invites
+        .filter(group_id.eq(grp_id))
+        .filter(expires_at.gt(Utc::now().naive_utc()))
+        .select((id, group_id, email, created_by, expires_at, created_on))
+        .order(created_on.desc())
+        .load::<dtos::GroupInvite>(db_conn)?;
+    Ok(results)
+}
+
+pub fn get_group_invite_by_hash(
+    db_conn: &PgConnection,
+    hash: &str,
+) -> Result<Option<GroupInvite>, Error> {
+    use crate::schema::group_invites::dsl::*;
+
+    let invite = group_invites
+        .filter(token_hash.eq(hash))
+        .filter(expires_at.gt(Utc::now().naive_utc()))
+        .first::<GroupInvite>(db_conn)
+        .optional()?;
+
+    Ok(invite)
+}
+
+pub fn remove_group_invite(
+    db_conn: &PgConnection,
+    grp_id: Uuid,
+    invite_id: Uuid,
+) -> Result<bool, Error> {
+    use crate::schema::group_invites::dsl::*;
+
+    diesel::delete(group_invites.filter(id.eq(invite_id).and(group_id.eq(grp_id))))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Records an emailed invite and drops the ones too old to count anymore.
+pub fn create_invite_email(
+    db_conn: &PgConnection,
+    invite_email: &InviteEmail,
+    window_start: chrono::NaiveDateTime,
+) -> Result<bool, Error> {
+    use crate::schema::invite_emails::dsl::*;
+
+    diesel::delete(invite_emails.filter(created_on.le(window_start))).execute(db_conn)?;
+    diesel::insert_into(invite_emails)
+        .values(invite_email)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn count_invite_emails_since(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    since: chrono::NaiveDateTime,
+) -> Result<i64, Error> {
+    use crate::schema::invite_emails::dsl::*;
+
+    invite_emails
+        .filter(user_id.eq(usr_id).and(created_on.gt(since)))
+        .count()
+        .get_result(db_conn)
+}
+
+/// Everything the members of the group still have on their wishlists.
+pub fn get_group_wishlist_games(
+    db_conn: &PgConnection,
+    grp_id: Uuid,
+) -> Result<Vec<WishedGame>, Error> {
+    use crate::schema::group_members;
+    use crate::schema::wished_games::dsl::*;
+
+    let results = wished_games
+        .filter(status.eq(GameStatus::Wishlist.as_str()))
+        .filter(
+            user_id.eq_any(
+                group_members::table
+                    .select(group_members::user_id)
+                    .filter(group_members::group_id.eq(grp_id)),
+            ),
+        )
+        .load::<WishedGame>(db_conn)?;
+    Ok(results)
+}
+
+pub fn create_import(db_conn: &PgConnection, import: &Import) -> Result<bool, Error> {
+    use crate::schema::imports::dsl::*;
+
+    diesel::insert_into(imports)
+        .values(import)
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn get_import(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    import_id: Uuid,
+) -> Result<Option<Import>, Error> {
+    use crate::schema::imports::dsl::*;
+
+    let import = imports
+        .filter(id.eq(import_id).and(user_id.eq(usr_id)))
+        .first::<Import>(db_conn)
+        .optional()?;
+
+    Ok(import)
+}
+
+pub fn get_import_by_id(db_conn: &PgConnection, import_id: Uuid) -> Result<Option<Import>, Error> {
+    use crate::schema::imports::dsl::*;
+
+    let import = imports
+        .filter(id.eq(import_id))
+        .first::<Import>(db_conn)
+        .optional()?;
+
+    Ok(import)
+}
+
+/// Moves the import from `from_status` to `to_status`. Returns false when it was not in
+/// `from_status` anymore, so two requests can't both commit an import.
+pub fn change_import_status(
+    db_conn: &PgConnection,
+    import_id: Uuid,
+    from_status: &str,
+    to_status: &str,
+) -> Result<bool, Error> {
+    use crate::schema::imports::dsl::*;
+
+    let updated = diesel::update(imports.filter(id.eq(import_id).and(status.eq(from_status))))
+        .set((status.eq(to_status), updated_on.eq(Utc::now().naive_utc())))
+        .execute(db_conn)?;
+
+    Ok(updated > 0)
+}
+
+pub fn save_import_report(
+    db_conn: &PgConnection,
+    import_id: Uuid,
+    new_status: &str,
+    new_report: &serde_json::Value,
+) -> Result<bool, Error> {
+    use crate::schema::imports::dsl::*;
+
+    diesel::update(imports.filter(id.eq(import_id)))
+        .set((
+            status.eq(new_status),
+            report.eq(new_report),
+            updated_on.eq(Utc::now().naive_utc()),
+        ))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn fail_import(
+    db_conn: &PgConnection,
+    import_id: Uuid,
+    failed_status: &str,
+    reason: &str,
+) -> Result<bool, Error> {
+    use crate::schema::imports::dsl::*;
+
+    diesel::update(imports.filter(id.eq(import_id)))
+        .set((
+            status.eq(failed_status),
+            error.eq(reason),
+            updated_on.eq(Utc::now().naive_utc()),
+        ))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Fails the import if it is still in one of `waiting_statuses` and hasn't changed
+/// since `cutoff`. Returns false when the worker got to it after all.
+pub fn fail_stale_import(
+    db_conn: &PgConnection,
+    import_id: Uuid,
+    waiting_statuses: &[&str],
+    cutoff: chrono::NaiveDateTime,
+    failed_status: &str,
+    reason: &str,
+) -> Result<bool, Error> {
+    use crate::schema::imports::dsl::*;
+
+    let updated = diesel::update(
+        imports
+            .filter(id.eq(import_id))
+            .filter(status.eq_any(waiting_statuses.to_vec()))
+            .filter(updated_on.lt(cutoff)),
+    )
+    .set((
+        status.eq(failed_status),
+        error.eq(reason),
+        updated_on.eq(Utc::now().naive_utc()),
+    ))
+    .execute(db_conn)?;
+
+    Ok(updated > 0)
+}
+
+/// Returns false when the user already has a queued or running sync of the store, the
+/// unique index allows only one.
+pub fn create_sync_run(db_conn: &PgConnection, run: &SyncRun) -> Result<bool, Error> {
+    use crate::schema::sync_runs::dsl::*;
+
+    let inserted = diesel::insert_into(sync_runs)
+        .values(run)
+        .on_conflict_do_nothing()
+        .execute(db_conn)?;
+
+    Ok(inserted > 0)
+}
+
+pub fn get_sync_run(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    stre_name: &str,
+    run_id: Uuid,
+) -> Result<Option<SyncRun>, Error> {
+    use crate::schema::sync_runs::dsl::*;
+
+    let run = sync_runs
+        .filter(id.eq(run_id))
+        .filter(user_id.eq(usr_id).and(store_name.eq(stre_name)))
+        .first::<SyncRun>(db_conn)
+        .optional()?;
+
+    Ok(run)
+}
+
+pub fn get_sync_run_by_id(db_conn: &PgConnection, run_id: Uuid) -> Result<Option<SyncRun>, Error> {
+    use crate::schema::sync_runs::dsl::*;
+
+    let run = sync_runs
+        .filter(id.eq(run_id))
+        .first::<SyncRun>(db_conn)
+        .optional()?;
+
+    Ok(run)
+}
+
+pub fn get_latest_sync_run(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    stre_name: &str,
+) -> Result<Option<SyncRun>, Error> {
+    use crate::schema::sync_runs::dsl::*;
+
+    let run = sync_runs
+        .filter(user_id.eq(usr_id).and(store_name.eq(stre_name)))
+        .order(created_on.desc())
+        .first::<SyncRun>(db_conn)
+        .optional()?;
+
+    Ok(run)
+}
+
+pub fn set_sync_run_task(
+    db_conn: &PgConnection,
+    run_id: Uuid,
+    celery_task_id: &str,
+) -> Result<bool, Error> {
+    use crate::schema::sync_runs::dsl::*;
+
+    diesel::update(sync_runs.filter(id.eq(run_id)))
+        .set(task_id.eq(celery_task_id))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+/// Moves a queued run to running. Returns false when it was picked up already.
+pub fn start_sync_run(
+    db_conn: &PgConnection,
+    run_id: Uuid,
+    queued_status: &str,
+    running_status: &str,
+) -> Result<bool, Error> {
+    use crate::schema::sync_runs::dsl::*;
+
+    let now_utc = Utc::now().naive_utc();
+    let updated = diesel::update(sync_runs.filter(id.eq(run_id).and(status.eq(queued_status))))
+        .set((
+            status.eq(running_status),
+            started_on.eq(now_utc),
+            updated_on.eq(now_utc),
+        ))
+        .execute(db_conn)?;
+
+    Ok(updated > 0)
+}
+
+/// Also moves `updated_on`, which keeps a long sync from looking lost.
+pub fn update_sync_run_progress(
+    db_conn: &PgConnection,
+    run_id: Uuid,
+    total: Option<i32>,
+    imported: i32,
+) -> Result<bool, Error> {
+    use crate::schema::sync_runs::dsl::*;
+
+    diesel::update(sync_runs.filter(id.eq(run_id)))
+        .set((
+            items_total.eq(total),
+            items_imported.eq(imported),
+            updated_on.eq(Utc::now().naive_utc()),
+        ))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
+
+pub fn finish_sync_run(
+    db_conn: &PgConnection,
+    run_id: Uuid,
+    final_status: &str,
+    reason: Option<&str>,
+) -> Result<bool, Error> {
+    use crate::schema::sync_runs::dsl::*;
+
+    let now_utc = Utc::now().naive_utc();
+    diesel::update(sync_runs.filter(id.eq(run_id)))
+        .set((
+            status.eq(final_status),
+            error.eq(reason),
+            finished_on.eq(now_utc),
+            updated_on.eq(now_utc),
+        ))
+        .execute(db_conn)?;
+
+    Ok(true)
+}
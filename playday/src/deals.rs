@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// CheapShark follows the prices of Steam, GOG, the Epic Games Store and others. Prices
+/// are in US dollars.
+const DEALS_URL: &str = "https://www.cheapshark.com/api/1.0/deals";
+const STORES_URL: &str = "https://www.cheapshark.com/api/1.0/stores";
+const REDIRECT_URL: &str = "https://www.cheapshark.com/redirect";
+/// CheapShark asks to keep the request rate low, lookups are spaced out.
+const LOOKUP_INTERVAL_MS: u64 = 500;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CheapSharkDeal {
+    title: String,
+    #[serde(rename = "storeID")]
+    store_id: String,
+    #[serde(rename = "dealID")]
+    deal_id: String,
+    sale_price: String,
+    normal_price: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CheapSharkStore {
+    #[serde(rename = "storeID")]
+    store_id: String,
+    store_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StoreDeal {
+    pub store_name: String,
+    pub sale_price_cents: i32,
+    pub normal_price_cents: i32,
+    pub url: String,
+}
+
+/// Looks up the current deals of wishlist games. Lookups are cached by title, so a game
+/// on many wishlists is only looked up once per digest run.
+pub struct Deals {
+    client: reqwest::blocking::Client,
+    store_names: HashMap<String, String>,
+    found: HashMap<String, Option<StoreDeal>>,
+    last_lookup: Option<Instant>,
+}
+
+/// Parses prices like `12.99` into cents.
+fn parse_price_cents(price: &str) -> Option<i32> {
+    let (dollars, cents) = match price.trim().split_once('.') {
+        Some((dollars, cents)) => (dollars, cents),
+        None => (price.trim(), "0"),
+    };
+    let cents = match cents.len() {
+        1 => cents.parse::<i32>().ok()? * 10,
+        2 => cents.parse::<i32>().ok()?,
+        _ => return None,
+    };
+
+    Some(dollars.parse::<i32>().ok()? * 100 + cents)
+}
+
+/// The cheapest deal for exactly this title that is below the normal price.
+fn best_deal(
+    title: &str,
+    deals: &[CheapSharkDeal],
+    store_names: &HashMap<String, String>,
+) -> Option<StoreDeal> {
+    deals
+        .iter()
+        .filter(|deal| deal.title.trim().eq_ignore_ascii_case(title.trim()))
+        .filter_map(|deal| {
+            let sale_price_cents = parse_price_cents(&deal.sale_price)?;
+            let normal_price_cents = parse_price_cents(&deal.normal_price)?;
+            if sale_price_cents >= normal_price_cents {
+                return None;
+            }
+
+            Some(StoreDeal {
+                store_name: store_names
+                    .get(&deal.store_id)
+                    .cloned()
+                    .unwrap_or_else(|| "another store".to_string()),
+                sale_price_cents,
+                normal_price_cents,
+                url: format!("{}?dealID={}", REDIRECT_URL, deal.deal_id),
+            })
+        })
+        .min_by_key(|deal| deal.sale_price_cents)
+}
+
+impl Deals {
+    pub fn new() -> Result<Deals> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        let stores: Vec<CheapSharkStore> = client
+            .get(STORES_URL)
+            .send()?
+            .error_for_status()?
+            .json()?;
+
+        Ok(Deals {
+            client,
+            store_names: stores
+                .into_iter()
+                .map(|store| (store.store_id, store.store_name))
+                .collect(),
+            found: HashMap::new(),
+            last_lookup: None,
+        })
+    }
+
+    pub fn find_deal(&mut self, title: &str) -> Result<Option<StoreDeal>> {
+        let key = title.trim().to_lowercase();
+        if let Some(deal) = self.found.get(&key) {
+            return Ok(deal.clone());
+        }
+
+        if let Some(last_lookup) = self.last_lookup {
+            let interval = Duration::from_millis(LOOKUP_INTERVAL_MS);
+            if last_lookup.elapsed() < interval {
+                thread::sleep(interval - last_lookup.elapsed());
+            }
+        }
+        self.last_lookup = Some(Instant::now());
+
+        let deals: Vec<CheapSharkDeal> = self
+            .client
+            .get(DEALS_URL)
+            .query(&[("title", title), ("exact", "1"), ("onSale", "1")])
+            .send()?
+            .error_for_status()?
+            .json()?;
+        let deal = best_deal(title, &deals, &self.store_names);
+        self.found.insert(key, deal.clone());
+
+        Ok(deal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deal(title: &str, store_id: &str, sale_price: &str, normal_price: &str) -> CheapSharkDeal {
+        CheapSharkDeal {
+            title: title.to_string(),
+            store_id: store_id.to_string(),
+            deal_id: format!("{}-{}", store_id, sale_price),
+            sale_price: sale_price.to_string(),
+            normal_price: normal_price.to_string(),
+        }
+    }
+
+    fn store_names() -> HashMap<String, String> {
+        vec![
+            ("1".to_string(), "Steam".to_string()),
+            ("7".to_string(), "GOG".to_string()),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn parses_prices_into_cents() {
+        assert_eq!(parse_price_cents("12.99"), Some(1299));
+        assert_eq!(parse_price_cents("0.00"), Some(0));
+        assert_eq!(parse_price_cents("5.5"), Some(550));
+        assert_eq!(parse_price_cents("20"), Some(2000));
+        assert_eq!(parse_price_cents("1.999"), None);
+        assert_eq!(parse_price_cents("free"), None);
+    }
+
+    #[test]
+    fn picks_the_cheapest_deal_of_the_exact_title() {
+        let deals = vec![
+            deal("Hades", "1", "12.49", "24.99"),
+            deal("Hades", "7", "9.99", "24.99"),
+            deal("Hades II", "1", "1.99", "29.99"),
+        ];
+
+        assert_eq!(
+            best_deal("hades", &deals, &store_names()),
+            Some(StoreDeal {
+                store_name: "GOG".to_string(),
+                sale_price_cents: 999,
+                normal_price_cents: 2499,
+                url: format!("{}?dealID=7-9.99", REDIRECT_URL),
+            })
+        );
+    }
+
+    #[test]
+    fn skips_full_prices_and_unknown_titles() {
+        let deals = vec![deal("Hades", "1", "24.99", "24.99")];
+
+        assert_eq!(best_deal("Hades", &deals, &store_names()), None);
+        assert_eq!(best_deal("Celeste", &deals, &store_names()), None);
+    }
+
+    #[test]
+    fn names_unknown_stores() {
+        let deals = vec![deal("Hades", "99", "9.99", "24.99")];
+
+        assert_eq!(
+            best_deal("Hades", &deals, &store_names()).map(|deal| deal.store_name),
+            Some("another store".to_string())
+        );
+    }
+}
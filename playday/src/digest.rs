@@ -0,0 +1,177 @@
+use std::cmp::Reverse;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::PgConnection;
+use serde::Serialize;
+use tera::{Context, Tera};
+
+use crate::db;
+use crate::deals::Deals;
+use crate::models::{User, UserSettings, WishedGame};
+
+/// How far ahead the digest looks for upcoming releases.
+const UPCOMING_DAYS: i64 = 30;
+/// Released games looked up for deals per digest, the highest priority ones.
+const MAX_DEAL_LOOKUPS: usize = 20;
+
+#[derive(Debug, Serialize)]
+pub struct DigestGame {
+    pub title: String,
+    pub igdb_id: i32,
+    pub release_date: String,
+    pub cover_url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DigestDateChange {
+    pub title: String,
+    pub old_release_date: String,
+    pub new_release_date: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DigestDeal {
+    pub title: String,
+    pub store_name: String,
+    pub sale_price: String,
+    pub normal_price: String,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeeklyDigest {
+    pub user_name: String,
+    pub upcoming: Vec<DigestGame>,
+    pub date_changes: Vec<DigestDateChange>,
+    pub released: Vec<DigestGame>,
+    pub deals: Vec<DigestDeal>,
+}
+
+impl WeeklyDigest {
+    pub fn is_empty(&self) -> bool {
+        self.upcoming.is_empty()
+            && self.date_changes.is_empty()
+            && self.released.is_empty()
+            && self.deals.is_empty()
+    }
+}
+
+fn format_date(tz: &Tz, epoch: i64) -> String {
+    if epoch == 0 {
+        return "TBD".to_string();
+    }
+
+    Utc.timestamp(epoch, 0).with_timezone(tz).format("%d %b %Y").to_string()
+}
+
+fn format_price(cents: i32) -> String {
+    format!("${}.{:02}", cents / 100, cents % 100)
+}
+
+/// Deals on released wishlist games, at or below the max price the user is willing to
+/// pay. A failed lookup only leaves that game out.
+fn find_deals(deals: &mut Deals, released_games: &[WishedGame]) -> Vec<DigestDeal> {
+    let mut games: Vec<&WishedGame> = released_games.iter().collect();
+    games.sort_by_key(|game| Reverse(game.priority));
+
+    games
+        .into_iter()
+        .take(MAX_DEAL_LOOKUPS)
+        .filter_map(|game| match deals.find_deal(&game.title) {
+            Ok(deal) => deal.map(|deal| (game, deal)),
+            Err(error) => {
+                log::warn!("Could not look up deals for {}: {}", game.title, error);
+                None
+            }
+        })
+        .filter(|(game, deal)| {
+            game.max_price_cents
+                .map_or(true, |max_price| deal.sale_price_cents <= max_price)
+        })
+        .map(|(game, deal)| DigestDeal {
+            title: game.title.to_owned(),
+            store_name: deal.store_name,
+            sale_price: format_price(deal.sale_price_cents),
+            normal_price: format_price(deal.normal_price_cents),
+            url: deal.url,
+        })
+        .collect()
+}
+
+fn to_digest_game(tz: &Tz, game: &WishedGame) -> DigestGame {
+    let cover_url = game.igdb_info["cover"]["image_id"].as_str().map(|image_id| {
+        format!(
+            "https://images.igdb.com/igdb/image/upload/t_cover_small/{}.jpg",
+            image_id
+        )
+    });
+
+    DigestGame {
+        title: game.title.to_owned(),
+        igdb_id: game.igdb_id,
+        release_date: format_date(tz, game.pc_release_date),
+        cover_url,
+    }
+}
+
+/// Collects what happened in the user's wishlist since `since`: releases coming up in the
+/// next 30 days, release date changes, games that came out and deals on released games.
+/// The deals are left out without `deals`.
+pub fn build_digest(
+    db_conn: &PgConnection,
+    user: &User,
+    usr_settings: &UserSettings,
+    deals: Option<&mut Deals>,
+    since: NaiveDateTime,
+    now: DateTime<Utc>,
+) -> Result<WeeklyDigest> {
+    let tz = usr_settings.tz();
+    let now_epoch = now.timestamp();
+
+    let upcoming = db::get_wishlist_games_releasing_between(
+        db_conn,
+        user.id,
+        now_epoch,
+        (now + Duration::days(UPCOMING_DAYS)).timestamp(),
+    )?;
+    let released =
+        db::get_wishlist_games_releasing_between(db_conn, user.id, since.timestamp(), now_epoch)?;
+    let date_changes = db::get_release_date_changes_since(db_conn, user.id, since)?;
+    let store_deals = match deals {
+        // Games without a release date are stored as 0
+        Some(deals) => find_deals(
+            deals,
+            &db::get_wishlist_games_releasing_between(db_conn, user.id, 1, now_epoch)?,
+        ),
+        None => Vec::new(),
+    };
+
+    Ok(WeeklyDigest {
+        user_name: user.name.to_owned(),
+        upcoming: upcoming.iter().map(|game| to_digest_game(&tz, game)).collect(),
+        date_changes: date_changes
+            .iter()
+            .map(|change| DigestDateChange {
+                title: change.title.to_owned(),
+                old_release_date: format_date(&tz, change.old_release_date),
+                new_release_date: format_date(&tz, change.new_release_date),
+            })
+            .collect(),
+        released: released.iter().map(|game| to_digest_game(&tz, game)).collect(),
+        deals: store_deals,
+    })
+}
+
+/// Renders the plain-text and HTML bodies of the digest email.
+pub fn render_digest(tera: &Tera, digest: &WeeklyDigest) -> Result<(String, String)> {
+    let mut tera_data = Context::new();
+    tera_data.insert("title", "Your week in Playday");
+    tera_data.insert("digest", digest);
+
+    let text_body = tera.render("weekly_digest.txt", &tera_data)?;
+    let html_body = tera.render("weekly_digest.html", &tera_data)?;
+
+    Ok((text_body, html_body))
+}
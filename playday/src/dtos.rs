@@ -1,4 +1,4 @@
-use super::schema::{game_stores};
+use super::schema::{api_tokens, game_stores, group_invites, webhooks};
 use diesel::{Queryable};
 use serde::{Deserialize, Serialize};
 
@@ -12,3 +12,43 @@ pub struct GameStore {
     pub user_id: uuid::Uuid,
     pub store_user_name: String,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, PartialEq)]
+#[table_name = "webhooks"]
+pub struct Webhook {
+    pub id: uuid::Uuid,
+    pub url: String,
+    pub payload_format: String,
+    pub added_on: chrono::NaiveDateTime,
+    pub user_id: uuid::Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, PartialEq)]
+#[table_name = "api_tokens"]
+pub struct ApiToken {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, QueryableByName, PartialEq)]
+#[table_name = "group_invites"]
+pub struct GroupInvite {
+    pub id: uuid::Uuid,
+    pub group_id: uuid::Uuid,
+    pub email: Option<String>,
+    pub created_by: uuid::Uuid,
+    pub expires_at: chrono::NaiveDateTime,
+    pub created_on: chrono::NaiveDateTime,
+}
+
+/// A member as the rest of the group sees them, without their email.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, PartialEq)]
+pub struct GroupMember {
+    pub user_id: uuid::Uuid,
+    pub name: String,
+    pub joined_on: chrono::NaiveDateTime,
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::igdb::IGDBGame;
+use crate::models::{GameList, Tag, WishedGame};
+
+/// Bumped whenever a field of the export changes meaning or goes away, importers refuse
+/// versions they don't know. Adding a field doesn't bump it, readers ignore fields they
+/// don't know.
+pub const EXPORT_VERSION: u32 = 1;
+
+/// CSV columns, in order. Dates are `YYYY-MM-DD` in UTC and empty when unknown, lists and
+/// tags are separated by `;` with `\` escaping a `;` or `\` in a name. Text that a
+/// spreadsheet would run as a formula gets a leading `'`.
+pub const CSV_COLUMNS: [&str; 14] = [
+    "igdb_id",
+    "title",
+    "status",
+    "pc_release_date",
+    "first_release_date",
+    "priority",
+    "personal_rating",
+    "max_price_cents",
+    "lists",
+    "tags",
+    "notes",
+    "started_on",
+    "finished_on",
+    "added_on",
+];
+
+/// Spreadsheets run cells starting with one of these as formulas.
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExportParams {
+    pub format: Option<ExportFormat>,
+}
+
+/// The whole wishlist, as served by `GET /api/export?format=json`. This is the format
+/// other Playday instances import, keep it in line with `EXPORT_VERSION`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WishlistExport {
+    pub version: u32,
+    pub exported_at: NaiveDateTime,
+    pub games: Vec<ExportedGame>,
+}
+
+/// One wished game. Release dates are unix timestamps like IGDB has them, the other
+/// dates are UTC date times.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedGame {
+    /// The game on IGDB, importers fetch everything else about the game from there.
+    pub igdb_id: i32,
+    pub title: String,
+    /// One of `wishlist`, `backlog`, `playing`, `completed` or `dropped`.
+    pub status: String,
+    /// `0` when there is no PC release date yet.
+    pub pc_release_date: i64,
+    pub first_release_date: Option<i64>,
+    #[serde(default)]
+    pub release_dates: Vec<ExportedReleaseDate>,
+    /// From 1 (low) to 5 (high).
+    pub priority: i16,
+    /// From 1 to 10.
+    pub personal_rating: Option<i16>,
+    pub max_price_cents: Option<i32>,
+    #[serde(default)]
+    pub notes: String,
+    /// Names of the lists and tags, they are matched by name on import.
+    #[serde(default)]
+    pub lists: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub started_on: Option<NaiveDateTime>,
+    pub finished_on: Option<NaiveDateTime>,
+    pub added_on: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedReleaseDate {
+    /// IGDB platform slug, like `win` or `ps5`.
+    pub platform: String,
+    /// Missing for vague dates like "Q4 2022", `human` still has those.
+    pub date: Option<i64>,
+    pub human: String,
+}
+
+/// Names of the lists or tags of each game, from `(game_id, list_id)` pairs.
+fn names_by_game(
+    pairs: &[(Uuid, Uuid)],
+    names: &HashMap<Uuid, &str>,
+) -> HashMap<Uuid, Vec<String>> {
+    let mut by_game: HashMap<Uuid, Vec<String>> = HashMap::new();
+    for (game_id, named_id) in pairs.iter() {
+        if let Some(name) = names.get(named_id) {
+            by_game.entry(*game_id).or_default().push(name.to_string());
+        }
+    }
+    for game_names in by_game.values_mut() {
+        game_names.sort();
+    }
+
+    by_game
+}
+
+pub fn build_export(
+    games: Vec<WishedGame>,
+    lists: &[GameList],
+    tags: &[Tag],
+    game_lists: &[(Uuid, Uuid)],
+    game_tags: &[(Uuid, Uuid)],
+) -> WishlistExport {
+    let list_names = lists
+        .iter()
+        .map(|list| (list.id, list.name.as_str()))
+        .collect();
+    let tag_names = tags.iter().map(|tag| (tag.id, tag.name.as_str())).collect();
+    let mut lists_by_game = names_by_game(game_lists, &list_names);
+    let mut tags_by_game = names_by_game(game_tags, &tag_names);
+
+    let games = games
+        .into_iter()
+        .map(|game| {
+            // Older rows may not hold a full IGDB game, export what the row has then
+            let igdb_game = serde_json::from_value::<IGDBGame>(game.igdb_info).ok();
+            let release_dates = igdb_game
+                .as_ref()
+                .and_then(|igdb_game| igdb_game.release_dates.as_ref())
+                .map(|release_dates| {
+                    release_dates
+                        .iter()
+                        .map(|release_date| ExportedReleaseDate {
+                            platform: release_date.platform.slug.to_owned(),
+                            date: release_date.date,
+                            human: release_date.human.to_owned(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            ExportedGame {
+                igdb_id: game.igdb_id,
+                title: game.title,
+                status: game.status,
+                pc_release_date: game.pc_release_date,
+                first_release_date: igdb_game.and_then(|igdb_game| igdb_game.first_release_date),
+                release_dates,
+                priority: game.priority,
+                personal_rating: game.personal_rating,
+                max_price_cents: game.max_price_cents,
+                notes: game.notes,
+                lists: lists_by_game.remove(&game.id).unwrap_or_default(),
+                tags: tags_by_game.remove(&game.id).unwrap_or_default(),
+                started_on: game.started_on,
+                finished_on: game.finished_on,
+                added_on: game.added_on,
+            }
+        })
+        .collect();
+
+    WishlistExport {
+        version: EXPORT_VERSION,
+        exported_at: Utc::now().naive_utc(),
+        games,
+    }
+}
+
+fn format_timestamp(epoch: i64) -> String {
+    Utc.timestamp(epoch, 0).format("%Y-%m-%d").to_string()
+}
+
+fn format_date(date: Option<NaiveDateTime>) -> String {
+    date.map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn format_number<T: ToString>(number: Option<T>) -> String {
+    number.map(|number| number.to_string()).unwrap_or_default()
+}
+
+/// Keeps user text from being run as a formula when the CSV is opened in a spreadsheet.
+fn format_text(text: &str) -> String {
+    if text.starts_with(&FORMULA_PREFIXES[..]) {
+        format!("'{}", text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn format_names(names: &[String]) -> String {
+    let escaped: Vec<String> = names
+        .iter()
+        .map(|name| name.replace('\\', "\\\\").replace(';', "\\;"))
+        .collect();
+
+    format_text(&escaped.join(";"))
+}
+
+pub fn write_csv(export: &WishlistExport) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&CSV_COLUMNS)?;
+
+    for game in export.games.iter() {
+        let pc_release_date = if game.pc_release_date == 0 {
+            String::new()
+        } else {
+            format_timestamp(game.pc_release_date)
+        };
+
+        writer.write_record(&[
+            game.igdb_id.to_string(),
+            format_text(&game.title),
+            game.status.to_owned(),
+            pc_release_date,
+            game.first_release_date
+                .map(format_timestamp)
+                .unwrap_or_default(),
+            game.priority.to_string(),
+            format_number(game.personal_rating),
+            format_number(game.max_price_cents),
+            format_names(&game.lists),
+            format_names(&game.tags),
+            format_text(&game.notes),
+            format_date(game.started_on),
+            format_date(game.finished_on),
+            format_date(Some(game.added_on)),
+        ])?;
+    }
+
+    Ok(writer.into_inner()?)
+}
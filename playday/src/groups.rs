@@ -0,0 +1,429 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tera::{Context, Tera};
+use uuid::Uuid;
+
+use crate::accounts;
+use crate::api_tokens::hash_token;
+use crate::dtos;
+use crate::mailer::{self, Mailer};
+use crate::models::{Group, GroupInvite, InviteEmail, WishedGame};
+use crate::wishlist;
+
+const INVITE_DAYS: i64 = 7;
+/// Emailed invites count against their sender for this long.
+const INVITE_EMAILS_WINDOW_HOURS: i64 = 24;
+/// Enough to invite a whole group in a day, too few to use the server for spam.
+const MAX_INVITE_EMAILS_PER_WINDOW: i64 = 20;
+pub const MAX_MEMBERS: i64 = 50;
+/// A game only one member wants is not much of a group pick, those are hidden by default.
+const DEFAULT_MIN_MEMBERS: usize = 2;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewGroup {
+    pub name: String,
+    #[serde(default)]
+    pub notify_releases: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupChanges {
+    pub name: Option<String>,
+    pub notify_releases: Option<bool>,
+}
+
+/// Without an email the invite is a link that can be passed around.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewInvite {
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupSummary {
+    #[serde(flatten)]
+    pub group: Group,
+    pub member_count: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupSort {
+    Overlap,
+    ReleaseDate,
+    Priority,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupGamesParams {
+    pub sort: Option<GroupSort>,
+    pub min_members: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WantedBy {
+    pub user_id: Uuid,
+    pub name: String,
+    pub priority: i16,
+}
+
+/// One game across the wishlists of the group. Notes and prices stay private.
+#[derive(Debug, Serialize)]
+pub struct GroupGame {
+    pub igdb_id: i32,
+    pub title: String,
+    pub igdb_info: serde_json::Value,
+    pub pc_release_date: i64,
+    pub member_count: usize,
+    /// Sum of the members' priorities, ties between equally shared games go to the one
+    /// they want more.
+    pub total_priority: i64,
+    pub wanted_by: Vec<WantedBy>,
+}
+
+pub fn new_group(usr_id: Uuid, new_group: NewGroup) -> Result<Group, String> {
+    Ok(Group {
+        id: Uuid::new_v4(),
+        name: wishlist::normalize_name(&new_group.name)?,
+        owner_id: usr_id,
+        notify_releases: new_group.notify_releases,
+        created_on: Utc::now().naive_utc(),
+    })
+}
+
+pub fn apply_changes(group: &mut Group, changes: GroupChanges) -> Result<(), String> {
+    if let Some(name) = changes.name {
+        group.name = wishlist::normalize_name(&name)?;
+    }
+    if let Some(notify_releases) = changes.notify_releases {
+        group.notify_releases = notify_releases;
+    }
+
+    Ok(())
+}
+
+/// Returns the row, which only has the hash, and the token to put in the link.
+pub fn new_invite(
+    group_id: Uuid,
+    usr_id: Uuid,
+    email: Option<String>,
+) -> Result<(GroupInvite, String), String> {
+    let email = email
+        .map(|email| accounts::normalize_email(&email))
+        .filter(|email| !email.is_empty());
+    if let Some(email) = &email {
+        if !accounts::is_valid_email(email) {
+            return Err("Invalid email address".to_string());
+        }
+    }
+
+    let token = format!(
+        "{}{}",
+        Uuid::new_v4().to_simple(),
+        Uuid::new_v4().to_simple()
+    );
+    let now_utc = Utc::now().naive_utc();
+
+    let invite = GroupInvite {
+        id: Uuid::new_v4(),
+        group_id,
+        email,
+        token_hash: hash_token(&token),
+        created_by: usr_id,
+        expires_at: now_utc + Duration::days(INVITE_DAYS),
+        created_on: now_utc,
+    };
+
+    Ok((invite, token))
+}
+
+/// Email invites are for the address they were sent to only.
+pub fn can_accept(invite: &GroupInvite, user_email: &str) -> bool {
+    match &invite.email {
+        Some(email) => *email == accounts::normalize_email(user_email),
+        None => true,
+    }
+}
+
+pub fn invite_emails_window_start(now: NaiveDateTime) -> NaiveDateTime {
+    now - Duration::hours(INVITE_EMAILS_WINDOW_HOURS)
+}
+
+pub fn is_invite_email_throttled(sent_in_window: i64) -> bool {
+    sent_in_window >= MAX_INVITE_EMAILS_PER_WINDOW
+}
+
+pub fn new_invite_email(usr_id: Uuid) -> InviteEmail {
+    InviteEmail {
+        id: Uuid::new_v4(),
+        user_id: usr_id,
+        created_on: Utc::now().naive_utc(),
+    }
+}
+
+/// Games without a release date go last, like they do on the wishlist.
+fn release_key(game: &GroupGame) -> i64 {
+    if game.pc_release_date == 0 {
+        i64::MAX
+    } else {
+        game.pc_release_date
+    }
+}
+
+fn compare_games(sort: GroupSort, a: &GroupGame, b: &GroupGame) -> Ordering {
+    let by_overlap = b.member_count.cmp(&a.member_count);
+    let by_priority = b.total_priority.cmp(&a.total_priority);
+    let by_release = release_key(a).cmp(&release_key(b));
+
+    let ordering = match sort {
+        GroupSort::Overlap => by_overlap.then(by_priority).then(by_release),
+        GroupSort::ReleaseDate => by_release.then(by_overlap).then(by_priority),
+        GroupSort::Priority => by_priority.then(by_overlap).then(by_release),
+    };
+
+    ordering.then_with(|| a.title.to_lowercase().cmp(&b.title.to_lowercase()))
+}
+
+/// Folds the members' wishlists into one entry per IGDB game. `games` should only hold
+/// games still on a wishlist.
+pub fn merge_wishlists(
+    members: &[dtos::GroupMember],
+    games: Vec<WishedGame>,
+    params: &GroupGamesParams,
+) -> Vec<GroupGame> {
+    let names: HashMap<Uuid, &str> = members
+        .iter()
+        .map(|member| (member.user_id, member.name.as_str()))
+        .collect();
+    let mut merged: HashMap<i32, GroupGame> = HashMap::new();
+
+    for game in games {
+        let name = match names.get(&game.user_id) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let group_game = merged.entry(game.igdb_id).or_insert_with(|| GroupGame {
+            igdb_id: game.igdb_id,
+            title: game.title.to_owned(),
+            igdb_info: game.igdb_info.to_owned(),
+            pc_release_date: game.pc_release_date,
+            member_count: 0,
+            total_priority: 0,
+            wanted_by: Vec::new(),
+        });
+
+        group_game.member_count += 1;
+        group_game.total_priority += i64::from(game.priority);
+        group_game.wanted_by.push(WantedBy {
+            user_id: game.user_id,
+            name,
+            priority: game.priority,
+        });
+    }
+
+    let min_members = params.min_members.unwrap_or(DEFAULT_MIN_MEMBERS).max(1);
+    let sort = params.sort.unwrap_or(GroupSort::Overlap);
+
+    let mut group_games: Vec<GroupGame> = merged
+        .into_iter()
+        .map(|(_, group_game)| group_game)
+        .filter(|group_game| group_game.member_count >= min_members)
+        .collect();
+    for group_game in group_games.iter_mut() {
+        group_game.wanted_by.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+    }
+    group_games.sort_by(|a, b| compare_games(sort, a, b));
+
+    group_games
+}
+
+/// Sends group invites. Only set up when the server has SMTP settings, link invites work
+/// without it.
+pub struct InviteMailer {
+    mailer: Mailer,
+    templates: Tera,
+}
+
+impl InviteMailer {
+    pub fn new() -> Result<InviteMailer> {
+        Ok(InviteMailer {
+            mailer: Mailer::new()?,
+            templates: mailer::load_templates()?,
+        })
+    }
+
+    pub fn send_invite(
+        &self,
+        email: &str,
+        inviter_name: &str,
+        group: &Group,
+        link: &str,
+    ) -> Result<()> {
+        let subject = format!("{} invited you to {} on Playday", inviter_name, group.name);
+
+        let mut tera_data = Context::new();
+        tera_data.insert("title", &subject);
+        tera_data.insert("inviter_name", inviter_name);
+        tera_data.insert("group_name", &group.name);
+        tera_data.insert("link", link);
+        tera_data.insert("valid_days", &INVITE_DAYS);
+
+        let text_body = self.templates.render("group_invite.txt", &tera_data)?;
+        let html_body = self.templates.render("group_invite.html", &tera_data)?;
+
+        self.mailer.send(email, &subject, text_body, html_body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(name: &str) -> dtos::GroupMember {
+        dtos::GroupMember {
+            user_id: Uuid::new_v4(),
+            name: name.to_string(),
+            joined_on: Utc::now().naive_utc(),
+        }
+    }
+
+    fn game(
+        member: &dtos::GroupMember,
+        igdb_id: i32,
+        title: &str,
+        pc_release_date: i64,
+        priority: i16,
+    ) -> WishedGame {
+        WishedGame {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            igdb_id,
+            igdb_info: serde_json::Value::Null,
+            added_on: Utc::now().naive_utc(),
+            user_id: member.user_id,
+            pc_release_date,
+            total_rating: None,
+            platform_slugs: Vec::new(),
+            status: "wishlist".to_string(),
+            started_on: None,
+            finished_on: None,
+            priority,
+            notes: String::new(),
+            personal_rating: None,
+            max_price_cents: None,
+        }
+    }
+
+    fn params(sort: Option<GroupSort>, min_members: Option<usize>) -> GroupGamesParams {
+        GroupGamesParams { sort, min_members }
+    }
+
+    fn titles(group_games: &[GroupGame]) -> Vec<&str> {
+        group_games
+            .iter()
+            .map(|group_game| group_game.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn merges_games_by_igdb_id() {
+        let (ann, bob) = (member("Ann"), member("Bob"));
+        let games = vec![
+            game(&ann, 1, "Hades", 100, 1),
+            game(&bob, 1, "Hades", 100, 3),
+            game(&bob, 2, "Celeste", 200, 2),
+        ];
+
+        let group_games = merge_wishlists(&[ann, bob], games, &params(None, Some(1)));
+
+        assert_eq!(titles(&group_games), vec!["Hades", "Celeste"]);
+        assert_eq!(group_games[0].member_count, 2);
+        assert_eq!(group_games[0].total_priority, 4);
+        // Whoever wants it most comes first
+        let wanted_by: Vec<&str> = group_games[0]
+            .wanted_by
+            .iter()
+            .map(|wanted_by| wanted_by.name.as_str())
+            .collect();
+        assert_eq!(wanted_by, vec!["Bob", "Ann"]);
+    }
+
+    #[test]
+    fn hides_games_only_one_member_wants_by_default() {
+        let (ann, bob) = (member("Ann"), member("Bob"));
+        let games = vec![
+            game(&ann, 1, "Hades", 100, 1),
+            game(&bob, 1, "Hades", 100, 1),
+            game(&bob, 2, "Celeste", 200, 1),
+        ];
+
+        let group_games = merge_wishlists(&[ann, bob], games, &params(None, None));
+
+        assert_eq!(titles(&group_games), vec!["Hades"]);
+    }
+
+    #[test]
+    fn skips_games_of_former_members() {
+        let (ann, bob) = (member("Ann"), member("Bob"));
+        let games = vec![
+            game(&ann, 1, "Hades", 100, 1),
+            game(&bob, 1, "Hades", 100, 1),
+        ];
+
+        let group_games = merge_wishlists(&[ann], games, &params(None, Some(1)));
+
+        assert_eq!(group_games.len(), 1);
+        assert_eq!(group_games[0].member_count, 1);
+    }
+
+    #[test]
+    fn sorts_by_release_date_with_unknown_dates_last() {
+        let ann = member("Ann");
+        let games = vec![
+            game(&ann, 1, "Unannounced", 0, 1),
+            game(&ann, 2, "Later", 300, 1),
+            game(&ann, 3, "Sooner", 100, 1),
+        ];
+
+        let group_games = merge_wishlists(
+            &[ann],
+            games,
+            &params(Some(GroupSort::ReleaseDate), Some(1)),
+        );
+
+        assert_eq!(titles(&group_games), vec!["Sooner", "Later", "Unannounced"]);
+    }
+
+    #[test]
+    fn sorts_by_priority_then_overlap() {
+        let (ann, bob) = (member("Ann"), member("Bob"));
+        let games = vec![
+            game(&ann, 1, "Shared", 100, 1),
+            game(&bob, 1, "Shared", 100, 1),
+            game(&ann, 2, "Wanted", 100, 3),
+            game(&bob, 3, "Alone", 100, 2),
+        ];
+
+        let group_games = merge_wishlists(
+            &[ann, bob],
+            games,
+            &params(Some(GroupSort::Priority), Some(1)),
+        );
+
+        assert_eq!(titles(&group_games), vec!["Wanted", "Shared", "Alone"]);
+    }
+
+    #[test]
+    fn throttles_invite_emails_at_the_limit() {
+        assert!(!is_invite_email_throttled(0));
+        assert!(!is_invite_email_throttled(MAX_INVITE_EMAILS_PER_WINDOW - 1));
+        assert!(is_invite_email_throttled(MAX_INVITE_EMAILS_PER_WINDOW));
+    }
+}
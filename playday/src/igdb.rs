@@ -92,6 +92,13 @@ pub struct Platform {
     pub slug: String,
 }
 
+/// A game on another store, `uid` being its id there.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExternalGame {
+    pub game: Option<i32>,
+    pub uid: String,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ReleaseDate {
@@ -118,6 +125,17 @@ pub struct Website {
 const API_URL: &str = "https://api.igdb.com/v4";
 const TOKEN_API_URL: &str = "https://id.twitch.tv/oauth2/token";
 const PC_PLATFORM_ID: i32 = 6;
+/// Categories of `external_games`, the stores we import from.
+pub const EXTERNAL_STEAM: i32 = 1;
+pub const EXTERNAL_GOG: i32 = 5;
+// IGDB returns at most 500 results per request
+const MAX_RESULTS: usize = 500;
+const GAME_FIELDS: &str = "fields first_release_date, involved_companies.company.name, \
+    involved_companies.developer, involved_companies.publisher, \
+    name, cover.image_id, parent_game.*, version_parent.*, total_rating, \
+    release_dates.date, release_dates.*, release_dates.platform.slug, \
+    release_dates.platform.name, release_dates.platform.platform_family, \
+    platforms.name, platforms.slug, websites.*;";
 
 impl IGDB {
     fn generate_access_token() -> std::result::Result<AccessToken, reqwest::Error> {
@@ -173,6 +191,8 @@ impl IGDB {
         }
     }
 
+    /// Quotes and backslashes are dropped from `search_keyword`, they would end or escape
+    /// the search string of the query.
     pub fn search_games(&self, search_keyword: &str) -> Result<Vec<IGDBGame>> {
         // Refresh token if about to expire
         self.get_token();
@@ -182,15 +202,11 @@ impl IGDB {
         let req_url = format!("{url}/games", url = API_URL);
         let req_body = format!(
             "search \"{name_to_search}\"; \
-            fields first_release_date, involved_companies.company.name, \
-            involved_companies.developer, involved_companies.publisher, \
-            name, cover.image_id, parent_game.*, version_parent.*, total_rating, \
-            release_dates.date, release_dates.*, release_dates.platform.slug, \
-            release_dates.platform.name, release_dates.platform.platform_family, \
-            platforms.name, platforms.slug, websites.*; \
+            {fields} \
             where version_parent = null & parent_game = null;\
         ",
-            name_to_search = search_keyword
+            name_to_search = search_keyword.replace(|c| c == '"' || c == '\\', ""),
+            fields = GAME_FIELDS
         );
 
         let resp = self
@@ -201,20 +217,112 @@ impl IGDB {
                 format!("Bearer {}", &token.access_token.clone()),
             )
             .body(req_body)
-            .send()
-            .unwrap();
+            .send()?;
 
         // Print the status code
         log::info!("API Status: {}", resp.status().as_u16());
         //log::info!("API Response: {}", resp.text().unwrap());
 
-        let resp_body = resp.json::<Vec<IGDBGame>>().unwrap();
+        let resp_body = resp.error_for_status()?.json::<Vec<IGDBGame>>()?;
 
         Ok(resp_body)
     }
+
+    pub fn get_games_by_ids(&self, game_ids: &[i32]) -> Result<Vec<IGDBGame>> {
+        let mut games: Vec<IGDBGame> = Vec::new();
+
+        for ids_chunk in game_ids.chunks(MAX_RESULTS) {
+            // Refresh token if about to expire
+            self.get_token();
+
+            let token = self.token.lock().unwrap();
+
+            let ids = ids_chunk
+                .iter()
+                .map(|game_id| game_id.to_string())
+                .collect::<Vec<String>>()
+                .join(",");
+
+            let req_url = format!("{url}/games", url = API_URL);
+            let req_body = format!(
+                "{fields} where id = ({ids}); limit {limit};",
+                fields = GAME_FIELDS,
+                ids = ids,
+                limit = MAX_RESULTS
+            );
+
+            let resp = self
+                .client
+                .post(req_url)
+                .header(
+                    http::header::AUTHORIZATION,
+                    format!("Bearer {}", &token.access_token.clone()),
+                )
+                .body(req_body)
+                .send()?
+                .error_for_status()?;
+
+            games.extend(resp.json::<Vec<IGDBGame>>()?);
+        }
+
+        Ok(games)
+    }
+
+    /// Maps ids of a store, like Steam app ids, to IGDB games. Ids IGDB doesn't know are
+    /// left out. `store_ids` go into the query as they are, callers check they are numeric.
+    pub fn get_external_games(
+        &self,
+        category: i32,
+        store_ids: &[String],
+    ) -> Result<Vec<ExternalGame>> {
+        let mut external_games: Vec<ExternalGame> = Vec::new();
+
+        for ids_chunk in store_ids.chunks(MAX_RESULTS) {
+            // Refresh token if about to expire
+            self.get_token();
+
+            let token = self.token.lock().unwrap();
+
+            let uids = ids_chunk
+                .iter()
+                .map(|store_id| format!("\"{}\"", store_id))
+                .collect::<Vec<String>>()
+                .join(",");
+
+            let req_url = format!("{url}/external_games", url = API_URL);
+            let req_body = format!(
+                "fields game, uid; where category = {category} & uid = ({uids}); limit {limit};",
+                category = category,
+                uids = uids,
+                limit = MAX_RESULTS
+            );
+
+            let resp = self
+                .client
+                .post(req_url)
+                .header(
+                    http::header::AUTHORIZATION,
+                    format!("Bearer {}", &token.access_token.clone()),
+                )
+                .body(req_body)
+                .send()?
+                .error_for_status()?;
+
+            external_games.extend(resp.json::<Vec<ExternalGame>>()?);
+        }
+
+        Ok(external_games)
+    }
 }
 
 impl IGDBGame {
+    pub fn get_platform_slugs(&self) -> Vec<String> {
+        self.platforms
+            .iter()
+            .map(|platform| platform.slug.to_owned())
+            .collect()
+    }
+
     pub fn get_pc_release_date(&self) -> i64 {
         match &self.release_dates {
             None => return 0,
@@ -0,0 +1,739 @@
+use std::collections::{HashMap, HashSet};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{Datelike, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::export::{ExportedGame, WishlistExport, EXPORT_VERSION};
+use crate::igdb::{self, IGDBGame, IGDB};
+use crate::models::{Import, WishedGame};
+use crate::wishlist::{self, GameStatus, WishedGameChanges};
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_RESOLVING: &str = "resolving";
+pub const STATUS_REVIEW: &str = "review";
+pub const STATUS_COMMITTED: &str = "committed";
+pub const STATUS_FAILED: &str = "failed";
+
+pub const MAX_ROWS: usize = 1000;
+/// Request body limit of `POST /api/imports`, exports of big wishlists are well above the
+/// default one.
+pub const MAX_UPLOAD_BYTES: usize = 4 * 1024 * 1024;
+/// How many IGDB games an ambiguous row offers to pick from.
+const MAX_CANDIDATES: usize = 5;
+/// IGDB allows 4 requests a second, title searches are spaced out to stay below that.
+pub const SEARCH_INTERVAL_MS: u64 = 300;
+/// A pending or resolving import that hasn't moved for this long was lost by the worker,
+/// resolving even the biggest import takes far less.
+const STALE_IMPORT_MINUTES: i64 = 30;
+pub const STALE_IMPORT_ERROR: &str = "The import took too long, please start it again";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportSource {
+    Csv,
+    Playday,
+    Steam,
+    Gog,
+}
+
+impl ImportSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportSource::Csv => "csv",
+            ImportSource::Playday => "playday",
+            ImportSource::Steam => "steam",
+            ImportSource::Gog => "gog",
+        }
+    }
+}
+
+/// `content` is the CSV file or the Playday JSON export. Store imports take `app_ids`,
+/// or `content` with the ids separated by commas or new lines.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewImport {
+    pub source: ImportSource,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub app_ids: Vec<String>,
+}
+
+/// One game to import, as it came in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ImportRow {
+    Title {
+        title: String,
+        year: Option<i32>,
+        platform: Option<String>,
+    },
+    IgdbId {
+        igdb_id: i32,
+        title: Option<String>,
+    },
+    Playday {
+        game: ExportedGame,
+    },
+    StoreApp {
+        source: ImportSource,
+        app_id: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candidate {
+    pub igdb_id: i32,
+    pub title: String,
+    pub year: Option<i32>,
+    pub platforms: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum RowOutcome {
+    Matched { game: Candidate },
+    Ambiguous { candidates: Vec<Candidate> },
+    Failed { reason: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowResult {
+    pub row: usize,
+    pub input: ImportRow,
+    pub outcome: RowOutcome,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReportSummary {
+    pub matched: usize,
+    pub ambiguous: usize,
+    pub failed: usize,
+}
+
+/// The import as the API shows it, `summary` and `results` are there once it was resolved.
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub id: Uuid,
+    pub source: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub row_count: usize,
+    pub summary: Option<ReportSummary>,
+    pub results: Vec<RowResult>,
+    pub created_on: NaiveDateTime,
+    pub updated_on: NaiveDateTime,
+}
+
+/// What to add after the review. Matched rows are added unless skipped, ambiguous rows
+/// only when one of their candidates was picked.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CommitChoices {
+    #[serde(default)]
+    pub choices: HashMap<usize, i32>,
+    #[serde(default)]
+    pub skip: Vec<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowFailure {
+    pub row: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CommitResult {
+    pub added: usize,
+    pub already_wished: usize,
+    pub failed: Vec<RowFailure>,
+}
+
+pub fn new_import(usr_id: Uuid, source: ImportSource, rows: &[ImportRow]) -> Result<Import> {
+    let now_utc = Utc::now().naive_utc();
+
+    Ok(Import {
+        id: Uuid::new_v4(),
+        user_id: usr_id,
+        source: source.as_str().to_string(),
+        status: STATUS_PENDING.to_string(),
+        input_rows: serde_json::to_value(rows)?,
+        report: None,
+        error: None,
+        created_on: now_utc,
+        updated_on: now_utc,
+    })
+}
+
+/// Imports still waiting on the worker but last updated before this are stale.
+pub fn stale_cutoff(now: NaiveDateTime) -> NaiveDateTime {
+    now - chrono::Duration::minutes(STALE_IMPORT_MINUTES)
+}
+
+pub const WAITING_STATUSES: [&str; 2] = [STATUS_PENDING, STATUS_RESOLVING];
+
+pub fn is_stale(import: &Import, now: NaiveDateTime) -> bool {
+    WAITING_STATUSES.contains(&import.status.as_str()) && import.updated_on < stale_cutoff(now)
+}
+
+pub fn to_report(import: &Import) -> ImportReport {
+    let results: Vec<RowResult> = import
+        .report
+        .as_ref()
+        .and_then(|report| serde_json::from_value(report.to_owned()).ok())
+        .unwrap_or_default();
+    let summary = import.report.as_ref().map(|_| {
+        let mut summary = ReportSummary::default();
+        for result in results.iter() {
+            match result.outcome {
+                RowOutcome::Matched { .. } => summary.matched += 1,
+                RowOutcome::Ambiguous { .. } => summary.ambiguous += 1,
+                RowOutcome::Failed { .. } => summary.failed += 1,
+            }
+        }
+        summary
+    });
+
+    ImportReport {
+        id: import.id,
+        source: import.source.to_owned(),
+        status: import.status.to_owned(),
+        error: import.error.to_owned(),
+        row_count: import.input_rows.as_array().map_or(0, |rows| rows.len()),
+        summary,
+        results,
+        created_on: import.created_on,
+        updated_on: import.updated_on,
+    }
+}
+
+pub fn parse_rows(new_import: &NewImport) -> Result<Vec<ImportRow>, String> {
+    let rows = match new_import.source {
+        ImportSource::Csv => parse_csv(&new_import.content)?,
+        ImportSource::Playday => parse_playday(&new_import.content)?,
+        ImportSource::Steam | ImportSource::Gog => parse_app_ids(new_import)?,
+    };
+
+    if rows.is_empty() {
+        return Err("There is nothing to import".to_string());
+    }
+    if rows.len() > MAX_ROWS {
+        return Err(format!("Imports can have at most {} games", MAX_ROWS));
+    }
+
+    Ok(rows)
+}
+
+/// Needs a `title` (or `name`) column, `year` and `platform` help pick the right game.
+/// Rows with an `igdb_id`, like those of our own CSV export, skip the search.
+fn parse_csv(content: &str) -> Result<Vec<ImportRow>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|error| format!("Invalid CSV: {}", error))?
+        .clone();
+    let column = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|header| names.contains(&header.to_lowercase().as_str()))
+    };
+    let title_column = column(&["title", "name"]).ok_or("The CSV needs a title column")?;
+    let year_column = column(&["year"]);
+    let platform_column = column(&["platform"]);
+    let igdb_column = column(&["igdb_id"]);
+
+    let mut rows: Vec<ImportRow> = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        // Line 1 has the headers
+        let line = index + 2;
+        let record = record.map_err(|error| format!("Line {}: {}", line, error))?;
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .filter(|value| !value.is_empty())
+        };
+
+        if let Some(igdb_id) = field(igdb_column) {
+            rows.push(ImportRow::IgdbId {
+                igdb_id: igdb_id
+                    .parse()
+                    .map_err(|_| format!("Line {}: igdb_id must be a number", line))?,
+                title: field(Some(title_column)).map(|title| title.to_string()),
+            });
+            continue;
+        }
+
+        let title = match field(Some(title_column)) {
+            Some(title) => title.to_string(),
+            None => continue,
+        };
+        let year = match field(year_column) {
+            Some(year) => Some(
+                year.parse()
+                    .map_err(|_| format!("Line {}: year must be a number", line))?,
+            ),
+            None => None,
+        };
+
+        rows.push(ImportRow::Title {
+            title,
+            year,
+            platform: field(platform_column).map(|platform| platform.to_lowercase()),
+        });
+    }
+
+    Ok(rows)
+}
+
+fn parse_playday(content: &str) -> Result<Vec<ImportRow>, String> {
+    let export: WishlistExport = serde_json::from_str(content)
+        .map_err(|error| format!("Invalid Playday export: {}", error))?;
+    if !(1..=EXPORT_VERSION).contains(&export.version) {
+        return Err(format!(
+            "Exports of version {} can not be imported, this Playday reads up to version {}",
+            export.version, EXPORT_VERSION
+        ));
+    }
+
+    Ok(export
+        .games
+        .into_iter()
+        .map(|game| ImportRow::Playday { game })
+        .collect())
+}
+
+fn parse_app_ids(new_import: &NewImport) -> Result<Vec<ImportRow>, String> {
+    let app_ids: Vec<&str> = if new_import.app_ids.is_empty() {
+        new_import
+            .content
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .collect()
+    } else {
+        new_import
+            .app_ids
+            .iter()
+            .map(|app_id| app_id.as_str())
+            .collect()
+    };
+
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut rows: Vec<ImportRow> = Vec::new();
+    for app_id in app_ids
+        .into_iter()
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+    {
+        // They end up in the IGDB query, nothing but digits gets through
+        if !app_id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("{} is not a valid app id", app_id));
+        }
+        if seen.insert(app_id) {
+            rows.push(ImportRow::StoreApp {
+                source: new_import.source,
+                app_id: app_id.to_string(),
+            });
+        }
+    }
+
+    Ok(rows)
+}
+
+fn release_year(game: &IGDBGame) -> Option<i32> {
+    game.first_release_date
+        .map(|date| Utc.timestamp(date, 0).year())
+}
+
+fn to_candidate(game: &IGDBGame) -> Candidate {
+    Candidate {
+        igdb_id: game.id,
+        title: game.name.to_owned(),
+        year: release_year(game),
+        platforms: game.get_platform_slugs(),
+    }
+}
+
+/// Letters and digits only, so "Half-Life: Alyx" and "half life alyx" are the same game.
+fn normalize_title(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// A title is only a confident match when exactly one search result has that very
+/// name, and fits the year and platform when those were given.
+pub fn match_title(
+    title: &str,
+    year: Option<i32>,
+    platform: Option<&str>,
+    found: &[IGDBGame],
+) -> RowOutcome {
+    if found.is_empty() {
+        return RowOutcome::Failed {
+            reason: "No game with this title on IGDB".to_string(),
+        };
+    }
+
+    let fits = |game: &IGDBGame| {
+        let year_fits = year.map_or(true, |year| release_year(game) == Some(year));
+        let platform_fits = platform.map_or(true, |platform| {
+            game.platforms.iter().any(|game_platform| {
+                game_platform.slug == platform || game_platform.name.to_lowercase() == platform
+            })
+        });
+        year_fits && platform_fits
+    };
+    let mut fitting: Vec<&IGDBGame> = found.iter().filter(|game| fits(game)).collect();
+    // Year or platform may be off, let the user pick from everything the title found
+    if fitting.is_empty() {
+        fitting = found.iter().collect();
+    }
+
+    let wanted = normalize_title(title);
+    let (exact, others): (Vec<&IGDBGame>, Vec<&IGDBGame>) = fitting
+        .into_iter()
+        .partition(|game| normalize_title(&game.name) == wanted);
+    if exact.len() == 1 && fits(exact[0]) {
+        return RowOutcome::Matched {
+            game: to_candidate(exact[0]),
+        };
+    }
+
+    RowOutcome::Ambiguous {
+        candidates: exact
+            .into_iter()
+            .chain(others.into_iter())
+            .take(MAX_CANDIDATES)
+            .map(to_candidate)
+            .collect(),
+    }
+}
+
+/// Looks every row up on IGDB. IGDB and store ids are fetched in bulk, titles are
+/// searched one by one.
+pub fn resolve_rows(igdb_client: &IGDB, rows: &[ImportRow]) -> Result<Vec<RowResult>> {
+    let mut igdb_ids: Vec<i32> = rows
+        .iter()
+        .filter_map(|row| match row {
+            ImportRow::IgdbId { igdb_id, .. } => Some(*igdb_id),
+            ImportRow::Playday { game } => Some(game.igdb_id),
+            _ => None,
+        })
+        .collect();
+
+    let mut store_games: HashMap<(ImportSource, String), i32> = HashMap::new();
+    let stores = [
+        (ImportSource::Steam, igdb::EXTERNAL_STEAM),
+        (ImportSource::Gog, igdb::EXTERNAL_GOG),
+    ];
+    for (store, category) in stores.iter() {
+        let app_ids: Vec<String> = rows
+            .iter()
+            .filter_map(|row| match row {
+                ImportRow::StoreApp { source, app_id } if source == store => {
+                    Some(app_id.to_owned())
+                }
+                _ => None,
+            })
+            .collect();
+        if app_ids.is_empty() {
+            continue;
+        }
+
+        for external_game in igdb_client.get_external_games(*category, &app_ids)? {
+            if let Some(game_id) = external_game.game {
+                store_games.insert((*store, external_game.uid), game_id);
+            }
+        }
+    }
+    igdb_ids.extend(store_games.values());
+    igdb_ids.sort_unstable();
+    igdb_ids.dedup();
+
+    let igdb_games: HashMap<i32, IGDBGame> = igdb_client
+        .get_games_by_ids(&igdb_ids)?
+        .into_iter()
+        .map(|game| (game.id, game))
+        .collect();
+    let by_id = |igdb_id: i32| match igdb_games.get(&igdb_id) {
+        Some(game) => RowOutcome::Matched {
+            game: to_candidate(game),
+        },
+        None => RowOutcome::Failed {
+            reason: "No game with this id on IGDB".to_string(),
+        },
+    };
+
+    let mut results: Vec<RowResult> = Vec::new();
+    let mut searched = false;
+    for (index, row) in rows.iter().enumerate() {
+        let outcome = match row {
+            ImportRow::IgdbId { igdb_id, .. } => by_id(*igdb_id),
+            ImportRow::Playday { game } => by_id(game.igdb_id),
+            ImportRow::StoreApp { source, app_id } => {
+                match store_games.get(&(*source, app_id.to_owned())) {
+                    Some(igdb_id) => by_id(*igdb_id),
+                    None => RowOutcome::Failed {
+                        reason: format!("IGDB has no game for this {} app", source.as_str()),
+                    },
+                }
+            }
+            ImportRow::Title {
+                title,
+                year,
+                platform,
+            } => {
+                if searched {
+                    thread::sleep(Duration::from_millis(SEARCH_INTERVAL_MS));
+                }
+                searched = true;
+
+                let found = igdb_client.search_games(title)?;
+                match_title(title, *year, platform.as_deref(), &found)
+            }
+        };
+
+        results.push(RowResult {
+            row: index,
+            input: row.to_owned(),
+            outcome,
+        });
+    }
+
+    Ok(results)
+}
+
+/// The IGDB id to add for every row that goes in, once per game.
+pub fn selected_rows(
+    results: &[RowResult],
+    choices: &CommitChoices,
+) -> Result<Vec<(usize, i32)>, String> {
+    for (row, igdb_id) in choices.choices.iter() {
+        let result = results
+            .iter()
+            .find(|result| result.row == *row)
+            .ok_or_else(|| format!("There is no row {}", row))?;
+        let is_candidate = match &result.outcome {
+            RowOutcome::Matched { game } => game.igdb_id == *igdb_id,
+            RowOutcome::Ambiguous { candidates } => candidates
+                .iter()
+                .any(|candidate| candidate.igdb_id == *igdb_id),
+            RowOutcome::Failed { .. } => false,
+        };
+        if !is_candidate {
+            return Err(format!("{} is not a candidate of row {}", igdb_id, row));
+        }
+    }
+
+    let mut seen: HashSet<i32> = HashSet::new();
+    let selected = results
+        .iter()
+        .filter(|result| !choices.skip.contains(&result.row))
+        .filter_map(|result| match &result.outcome {
+            RowOutcome::Matched { game } => Some((result.row, game.igdb_id)),
+            RowOutcome::Ambiguous { .. } => choices
+                .choices
+                .get(&result.row)
+                .map(|igdb_id| (result.row, *igdb_id)),
+            RowOutcome::Failed { .. } => None,
+        })
+        .filter(|(_, igdb_id)| seen.insert(*igdb_id))
+        .collect();
+
+    Ok(selected)
+}
+
+/// The wishlist entry for an imported row. Games from a Playday export keep their status,
+/// dates and personal fields.
+pub fn new_imported_game(
+    usr_id: Uuid,
+    row: &ImportRow,
+    igdb_game: &IGDBGame,
+) -> Result<WishedGame, String> {
+    let mut game =
+        wishlist::new_wished_game(usr_id, igdb_game).map_err(|error| error.to_string())?;
+
+    if let ImportRow::Playday { game: exported } = row {
+        let status = GameStatus::parse(&exported.status)
+            .ok_or_else(|| format!("Unknown status {}", exported.status))?;
+        game.status = status.as_str().to_string();
+        game.started_on = exported.started_on;
+        game.finished_on = exported.finished_on;
+        game.added_on = exported.added_on;
+
+        wishlist::apply_changes(
+            &mut game,
+            WishedGameChanges {
+                status: None,
+                priority: Some(exported.priority),
+                notes: Some(exported.notes.to_owned()),
+                personal_rating: Some(exported.personal_rating),
+                max_price_cents: Some(exported.max_price_cents),
+            },
+            Utc::now().naive_utc(),
+        )?;
+    }
+
+    Ok(game)
+}
+
+/// Names of the lists and tags the row brings along.
+pub fn row_labels(row: &ImportRow) -> (&[String], &[String]) {
+    match row {
+        ImportRow::Playday { game } => (game.lists.as_slice(), game.tags.as_slice()),
+        _ => (&[], &[]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::igdb::Platform;
+
+    /// Mid 2017 and mid 2020
+    const IN_2017: i64 = 1_498_000_000;
+    const IN_2020: i64 = 1_593_000_000;
+
+    fn igdb_game(id: i32, name: &str, released: i64, platform_slugs: &[&str]) -> IGDBGame {
+        IGDBGame {
+            id,
+            name: name.to_string(),
+            first_release_date: Some(released),
+            platforms: platform_slugs
+                .iter()
+                .map(|slug| Platform {
+                    name: slug.to_uppercase(),
+                    slug: slug.to_string(),
+                    ..Platform::default()
+                })
+                .collect(),
+            ..IGDBGame::default()
+        }
+    }
+
+    fn matched_id(outcome: &RowOutcome) -> Option<i32> {
+        match outcome {
+            RowOutcome::Matched { game } => Some(game.igdb_id),
+            _ => None,
+        }
+    }
+
+    fn candidate_ids(outcome: &RowOutcome) -> Vec<i32> {
+        match outcome {
+            RowOutcome::Ambiguous { candidates } => candidates
+                .iter()
+                .map(|candidate| candidate.igdb_id)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn matches_titles_regardless_of_punctuation_and_case() {
+        let found = vec![
+            igdb_game(1, "Half-Life: Alyx", IN_2020, &["win"]),
+            igdb_game(2, "Half-Life 2", IN_2017, &["win"]),
+        ];
+
+        let outcome = match_title("half life alyx", None, None, &found);
+        assert_eq!(matched_id(&outcome), Some(1));
+    }
+
+    #[test]
+    fn year_and_platform_pick_between_same_titles() {
+        let found = vec![
+            igdb_game(1, "Prey", IN_2017, &["win", "ps4"]),
+            igdb_game(2, "Prey", IN_2020, &["ps4"]),
+        ];
+
+        assert_eq!(
+            candidate_ids(&match_title("Prey", None, None, &found)),
+            vec![1, 2]
+        );
+        assert_eq!(
+            matched_id(&match_title("Prey", Some(2017), None, &found)),
+            Some(1)
+        );
+        assert_eq!(
+            matched_id(&match_title("Prey", None, Some("win"), &found)),
+            Some(1)
+        );
+        assert_eq!(
+            matched_id(&match_title("Prey", Some(2020), Some("ps4"), &found)),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn offers_everything_when_year_or_platform_are_off() {
+        let found = vec![igdb_game(1, "Hades", IN_2020, &["win"])];
+
+        let outcome = match_title("Hades", Some(2017), None, &found);
+        assert_eq!(candidate_ids(&outcome), vec![1]);
+    }
+
+    #[test]
+    fn titles_without_an_exact_result_are_ambiguous() {
+        let found = vec![
+            igdb_game(1, "Hades II", IN_2020, &["win"]),
+            igdb_game(2, "Hades Star", IN_2017, &["win"]),
+        ];
+
+        let outcome = match_title("Hades", None, None, &found);
+        assert_eq!(candidate_ids(&outcome), vec![1, 2]);
+        assert!(matches!(
+            match_title("Hades", None, None, &[]),
+            RowOutcome::Failed { .. }
+        ));
+    }
+
+    #[test]
+    fn parses_csv_with_title_year_and_platform() {
+        let rows =
+            parse_csv("\u{feff}Name,Year,Platform\nHades , 2020,PC\n,,\nCeleste,,\n").unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert!(matches!(
+            &rows[0],
+            ImportRow::Title { title, year: Some(2020), platform: Some(platform) }
+                if title == "Hades" && platform == "pc"
+        ));
+        assert!(matches!(
+            &rows[1],
+            ImportRow::Title { title, year: None, platform: None } if title == "Celeste"
+        ));
+    }
+
+    #[test]
+    fn csv_rows_with_an_igdb_id_skip_the_search() {
+        let rows = parse_csv("title,igdb_id\nHades,113112\n").unwrap();
+
+        assert!(matches!(
+            &rows[0],
+            ImportRow::IgdbId { igdb_id: 113112, title: Some(title) } if title == "Hades"
+        ));
+    }
+
+    #[test]
+    fn rejects_broken_csv() {
+        assert_eq!(
+            parse_csv("game,year\nHades,2020\n").unwrap_err(),
+            "The CSV needs a title column"
+        );
+        assert_eq!(
+            parse_csv("title,year\nHades,soon\n").unwrap_err(),
+            "Line 2: year must be a number"
+        );
+        assert_eq!(
+            parse_csv("title,igdb_id\nHades,abc\n").unwrap_err(),
+            "Line 2: igdb_id must be a number"
+        );
+    }
+}
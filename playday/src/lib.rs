@@ -9,3 +9,17 @@ pub mod models;
 pub mod tasks;
 pub mod epicgames;
 pub mod dtos;
+pub mod webhooks;
+pub mod settings;
+pub mod mailer;
+pub mod digest;
+pub mod deals;
+pub mod api_tokens;
+pub mod accounts;
+pub mod wishlist;
+pub mod sharing;
+pub mod groups;
+pub mod export;
+pub mod imports;
+pub mod library;
+pub mod schedules;
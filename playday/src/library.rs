@@ -0,0 +1,49 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use uuid::Uuid;
+
+use crate::models::SyncRun;
+
+pub const STORE_EPICGAMES: &str = "epicgames";
+
+pub const STATUS_QUEUED: &str = "queued";
+pub const STATUS_RUNNING: &str = "running";
+pub const STATUS_SUCCEEDED: &str = "succeeded";
+pub const STATUS_FAILED: &str = "failed";
+
+/// How often the event stream of a run looks for progress.
+pub const PROGRESS_POLL_MS: u64 = 1000;
+/// The event stream sends a comment after this many polls without progress, so proxies
+/// keep the connection open and closed clients are noticed.
+pub const KEEP_ALIVE_POLLS: u32 = 15;
+/// A queued or running run without progress for this long was lost by the worker, it no
+/// longer blocks a new sync.
+const STALE_RUN_MINUTES: i64 = 30;
+pub const STALE_RUN_ERROR: &str = "The sync stopped responding";
+
+pub fn new_sync_run(usr_id: Uuid, store_name: &str) -> SyncRun {
+    let now_utc = Utc::now().naive_utc();
+
+    SyncRun {
+        id: Uuid::new_v4(),
+        user_id: usr_id,
+        store_name: store_name.to_string(),
+        status: STATUS_QUEUED.to_string(),
+        task_id: None,
+        items_total: None,
+        items_imported: 0,
+        error: None,
+        created_on: now_utc,
+        started_on: None,
+        finished_on: None,
+        updated_on: now_utc,
+    }
+}
+
+pub fn is_finished(run: &SyncRun) -> bool {
+    run.status == STATUS_SUCCEEDED || run.status == STATUS_FAILED
+}
+
+/// Whether the run is still expected to make progress.
+pub fn is_active(run: &SyncRun, now: NaiveDateTime) -> bool {
+    !is_finished(run) && run.updated_on > now - Duration::minutes(STALE_RUN_MINUTES)
+}
@@ -0,0 +1,62 @@
+use std::env;
+
+use anyhow::{Context, Result};
+use lettre::message::MultiPart;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use tera::Tera;
+
+pub struct Mailer {
+    transport: SmtpTransport,
+    from: String,
+}
+
+impl Mailer {
+    /// Builds the SMTP transport from `SMTP_*` environment variables. TLS is used unless
+    /// `SMTP_TLS=false`, which is handy with a local sink like MailHog.
+    pub fn new() -> Result<Mailer> {
+        let smtp_host = env::var("SMTP_HOST").context("SMTP_HOST must be set")?;
+        let smtp_from = env::var("SMTP_FROM").context("SMTP_FROM must be set")?;
+        let use_tls = env::var("SMTP_TLS").map(|tls| tls != "false").unwrap_or(true);
+
+        let mut builder = if use_tls {
+            SmtpTransport::relay(&smtp_host)?
+        } else {
+            SmtpTransport::builder_dangerous(&smtp_host)
+        };
+
+        if let Ok(port) = env::var("SMTP_PORT") {
+            builder = builder.port(port.parse()?);
+        }
+
+        if let (Ok(username), Ok(password)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Mailer {
+            transport: builder.build(),
+            from: smtp_from,
+        })
+    }
+
+    pub fn send(&self, to: &str, subject: &str, text_body: String, html_body: String) -> Result<()> {
+        let email = Message::builder()
+            .from(self.from.parse()?)
+            .to(to.parse()?)
+            .subject(subject)
+            .multipart(MultiPart::alternative_plain_html(text_body, html_body))?;
+
+        self.transport.send(&email)?;
+
+        Ok(())
+    }
+}
+
+/// Email templates live next to the web templates, `TEMPLATES_DIR` points the
+/// worker at them when it isn't started from the app directory.
+pub fn load_templates() -> Result<Tera> {
+    let templates_dir = env::var("TEMPLATES_DIR").unwrap_or_else(|_| "templates".to_string());
+    let tera = Tera::new(&format!("{}/emails/**/*", templates_dir))?;
+
+    Ok(tera)
+}
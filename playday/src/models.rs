@@ -1,4 +1,9 @@
-use super::schema::{game_stores, users, wished_games};
+use super::schema::{
+    api_tokens, credential_tokens, credentials, digests, failed_logins, game_list_entries,
+    game_lists, game_stores, gift_claims, group_invites, group_members, groups, imports,
+    invite_emails, release_date_changes, sessions, share_links, sync_runs, tags, user_identities,
+    user_settings, users, webhook_deliveries, webhooks, wished_game_tags, wished_games,
+};
 use diesel::{Insertable, Queryable};
 use serde::{Deserialize, Serialize};
 
@@ -24,6 +29,44 @@ pub struct User {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "user_identities"]
+pub struct UserIdentity {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_login: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+pub struct Session {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_seen: chrono::NaiveDateTime,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, AsChangeset)]
+#[table_name = "user_settings"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct UserSettings {
+    pub user_id: uuid::Uuid,
+    pub notification_channels: Vec<String>,
+    pub lead_times: Vec<i32>,
+    pub quiet_hours_start: Option<i16>,
+    pub quiet_hours_end: Option<i16>,
+    pub timezone: String,
+    pub delivery_mode: String,
+    pub updated_on: chrono::NaiveDateTime,
+    pub weekly_digest: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, AsChangeset)]
+#[changeset_options(treat_none_as_null = "true")]
 pub struct WishedGame {
     pub id: uuid::Uuid,
     pub title: String,
@@ -32,4 +75,234 @@ pub struct WishedGame {
     pub added_on: chrono::NaiveDateTime,
     pub user_id: uuid::Uuid,
     pub pc_release_date: i64,
+    pub total_rating: Option<f64>,
+    pub platform_slugs: Vec<String>,
+    pub status: String,
+    pub started_on: Option<chrono::NaiveDateTime>,
+    pub finished_on: Option<chrono::NaiveDateTime>,
+    pub priority: i16,
+    pub notes: String,
+    pub personal_rating: Option<i16>,
+    pub max_price_cents: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+pub struct Webhook {
+    pub id: uuid::Uuid,
+    pub url: String,
+    pub payload_format: String,
+    pub secret: String,
+    pub added_on: chrono::NaiveDateTime,
+    pub user_id: uuid::Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, AsChangeset)]
+#[table_name = "webhook_deliveries"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct WebhookDelivery {
+    pub id: uuid::Uuid,
+    pub webhook_id: uuid::Uuid,
+    pub event: String,
+    pub event_key: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub response_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_on: chrono::NaiveDateTime,
+    pub updated_on: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+pub struct ReleaseDateChange {
+    pub id: uuid::Uuid,
+    pub wished_game_id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub title: String,
+    pub old_release_date: i64,
+    pub new_release_date: i64,
+    pub changed_on: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+pub struct Digest {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub sent_on: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "api_tokens"]
+pub struct ApiToken {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub name: String,
+    pub token_hash: String,
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "credentials"]
+pub struct Credential {
+    pub user_id: uuid::Uuid,
+    pub email: String,
+    pub password_hash: String,
+    pub email_verified_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_on: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "credential_tokens"]
+pub struct CredentialToken {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub purpose: String,
+    pub token_hash: String,
+    pub expires_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "failed_logins"]
+pub struct FailedLogin {
+    pub id: uuid::Uuid,
+    pub email: String,
+    pub ip_address: String,
+    pub created_on: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "game_lists"]
+pub struct GameList {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub name: String,
+    pub created_on: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "game_list_entries"]
+pub struct GameListEntry {
+    pub list_id: uuid::Uuid,
+    pub wished_game_id: uuid::Uuid,
+    pub added_on: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "tags"]
+pub struct Tag {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub name: String,
+    pub created_on: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "wished_game_tags"]
+pub struct WishedGameTag {
+    pub tag_id: uuid::Uuid,
+    pub wished_game_id: uuid::Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "share_links"]
+pub struct ShareLink {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub token: String,
+    pub list_id: Option<uuid::Uuid>,
+    pub show_notes: bool,
+    pub show_prices: bool,
+    pub created_on: chrono::NaiveDateTime,
+}
+
+/// A visitor of a share page saying they will buy the game. Never shown to the owner.
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "gift_claims"]
+pub struct GiftClaim {
+    pub id: uuid::Uuid,
+    pub wished_game_id: uuid::Uuid,
+    pub claimer_name: Option<String>,
+    pub claimer_ip_hash: String,
+    pub created_on: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "groups"]
+pub struct Group {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub owner_id: uuid::Uuid,
+    pub notify_releases: bool,
+    pub created_on: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "group_members"]
+pub struct GroupMember {
+    pub group_id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub joined_on: chrono::NaiveDateTime,
+}
+
+/// Invites sent by email only work for that address and only once, link invites can be
+/// used by anyone until they expire.
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "group_invites"]
+pub struct GroupInvite {
+    pub id: uuid::Uuid,
+    pub group_id: uuid::Uuid,
+    pub email: Option<String>,
+    pub token_hash: String,
+    pub created_by: uuid::Uuid,
+    pub expires_at: chrono::NaiveDateTime,
+    pub created_on: chrono::NaiveDateTime,
+}
+
+/// A bulk import. `input_rows` holds the parsed rows, `report` how each of them resolved
+/// against IGDB once the worker got to it.
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "imports"]
+pub struct Import {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub source: String,
+    pub status: String,
+    pub input_rows: serde_json::Value,
+    pub report: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub created_on: chrono::NaiveDateTime,
+    pub updated_on: chrono::NaiveDateTime,
+}
+
+/// An invite sent by email, kept for a day to limit how many emails one user can make
+/// the server send.
+#[derive(Debug, Clone, Queryable, Insertable)]
+#[table_name = "invite_emails"]
+pub struct InviteEmail {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub created_on: chrono::NaiveDateTime,
+}
+
+/// One sync of a store library, queued by the web app and updated by the worker as it goes.
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "sync_runs"]
+pub struct SyncRun {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub store_name: String,
+    pub status: String,
+    pub task_id: Option<String>,
+    pub items_total: Option<i32>,
+    pub items_imported: i32,
+    pub error: Option<String>,
+    pub created_on: chrono::NaiveDateTime,
+    pub started_on: Option<chrono::NaiveDateTime>,
+    pub finished_on: Option<chrono::NaiveDateTime>,
+    pub updated_on: chrono::NaiveDateTime,
 }
@@ -0,0 +1,477 @@
+use anyhow::{bail, Result};
+use celery::beat::{DeltaSchedule, Schedule};
+use chrono::{DateTime, Datelike, Duration, LocalResult, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use std::collections::{BTreeSet, HashSet};
+use std::time::SystemTime;
+
+use crate::tasks;
+
+/// File read by beat when `BEAT_SCHEDULES` is not set.
+pub const DEFAULT_SCHEDULES_FILE: &str = "schedules.toml";
+
+/// How far ahead a cron expression is searched for its next run. Expressions that never
+/// match, like the 31st of February, are given up on after this.
+const MAX_SEARCH_DAYS: i64 = 5 * 366;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchedulesFile {
+    #[serde(default)]
+    pub schedule: Vec<ScheduleConfig>,
+}
+
+/// One `[[schedule]]` entry of the beat config.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    pub name: String,
+    pub task: String,
+    pub cron: Option<String>,
+    pub interval_seconds: Option<u64>,
+    #[serde(default = "empty_args")]
+    pub args: Value,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+    pub timezone: Option<String>,
+}
+
+fn empty_args() -> Value {
+    Value::Object(Map::new())
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+/// A validated schedule, ready to be handed to beat.
+#[derive(Debug)]
+pub struct BeatSchedule {
+    pub name: String,
+    pub task: String,
+    pub args: Value,
+    pub schedule: ConfiguredSchedule,
+}
+
+#[derive(Debug)]
+pub enum ConfiguredSchedule {
+    Cron(CronExpression, Tz),
+    Interval(std::time::Duration),
+}
+
+impl Schedule for ConfiguredSchedule {
+    fn next_call_at(&self, last_run_at: Option<SystemTime>) -> Option<SystemTime> {
+        match self {
+            ConfiguredSchedule::Cron(expression, tz) => expression
+                .next_after(&Utc::now().with_timezone(tz))
+                .map(|next_dt| SystemTime::from(next_dt.with_timezone(&Utc))),
+            ConfiguredSchedule::Interval(interval) => {
+                DeltaSchedule::new(*interval).next_call_at(last_run_at)
+            }
+        }
+    }
+}
+
+/// A five field cron expression: minute, hour, day of month, month and day of week.
+/// Fields take `*`, values, ranges, lists and `/` steps; Sunday is 0 or 7.
+#[derive(Debug)]
+pub struct CronExpression {
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    weekdays: BTreeSet<u32>,
+    any_day: bool,
+    any_weekday: bool,
+    every_hour: bool,
+}
+
+impl CronExpression {
+    pub fn parse(expression: &str) -> Result<CronExpression> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            bail!(
+                "Expected 5 fields in cron expression, found {}",
+                fields.len()
+            );
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7, "day of week")?;
+        if weekdays.remove(&7) {
+            weekdays.insert(0);
+        }
+
+        let hours = parse_field(fields[1], 0, 23, "hour")?;
+
+        Ok(CronExpression {
+            minutes: parse_field(fields[0], 0, 59, "minute")?,
+            every_hour: hours.len() == 24,
+            hours,
+            days: parse_field(fields[2], 1, 31, "day of month")?,
+            months: parse_field(fields[3], 1, 12, "month")?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    /// First matching time strictly after `after`, in the timezone of `after`. Local times
+    /// skipped by a daylight saving change are passed over. Local times that happen twice
+    /// when the clocks go back run once, unless the expression runs every hour.
+    pub fn next_after<T: TimeZone>(&self, after: &DateTime<T>) -> Option<DateTime<T>> {
+        let tz = after.timezone();
+        let mut date = after.naive_local().date();
+        let last_date = date + Duration::days(MAX_SEARCH_DAYS);
+
+        while date <= last_date {
+            if self.months.contains(&date.month())
+                && self.matches_day(date.day(), date.weekday().num_days_from_sunday())
+            {
+                // Local times of a day are only in order outside of an overlap, take the
+                // earliest instead of the first found
+                let mut next_dt: Option<DateTime<T>> = None;
+                for hour in &self.hours {
+                    for minute in &self.minutes {
+                        let candidate = date.and_hms(*hour, *minute, 0);
+                        let local_dt = match tz.from_local_datetime(&candidate) {
+                            LocalResult::None => continue,
+                            LocalResult::Single(local_dt) => local_dt,
+                            LocalResult::Ambiguous(first, _) if first > *after => first,
+                            LocalResult::Ambiguous(_, second) if self.every_hour => second,
+                            LocalResult::Ambiguous(..) => continue,
+                        };
+                        if local_dt > *after && next_dt.as_ref().map_or(true, |next_dt| local_dt < *next_dt) {
+                            next_dt = Some(local_dt);
+                        }
+                    }
+                }
+                if next_dt.is_some() {
+                    return next_dt;
+                }
+            }
+            date = date.succ();
+        }
+
+        None
+    }
+
+    // as in cron, a restricted day of month and day of week match when either does
+    fn matches_day(&self, day: u32, weekday: u32) -> bool {
+        let day_matches = self.days.contains(&day);
+        let weekday_matches = self.weekdays.contains(&weekday);
+
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day_matches || weekday_matches,
+            _ => day_matches && weekday_matches,
+        }
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32, label: &str) -> Result<BTreeSet<u32>> {
+    let mut values = BTreeSet::new();
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => bail!("Invalid step in {} field: {}", label, part),
+            },
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, label)?, parse_value(end, label)?)
+        } else {
+            let value = parse_value(range, label)?;
+            // `5/15` runs from 5 to the end of the field
+            (value, if step > 1 { max } else { value })
+        };
+
+        if start < min || end > max || start > end {
+            bail!("{} field must be within {}-{}: {}", label, min, max, part);
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+
+    Ok(values)
+}
+
+fn parse_value(value: &str, label: &str) -> Result<u32> {
+    match value.parse::<u32>() {
+        Ok(value) => Ok(value),
+        Err(_) => bail!("Invalid value in {} field: {}", label, value),
+    }
+}
+
+pub fn parse_schedules(content: &str) -> Result<SchedulesFile> {
+    let schedules_file = toml::from_str::<SchedulesFile>(content)?;
+    Ok(schedules_file)
+}
+
+/// Checks every enabled schedule against the task registry, returning all problems at
+/// once so a bad config is fixed in one go.
+pub fn validate_schedules(configs: Vec<ScheduleConfig>) -> Result<Vec<BeatSchedule>, Vec<String>> {
+    let mut schedules = Vec::new();
+    let mut errors = Vec::new();
+    let mut names = HashSet::new();
+
+    for config in configs {
+        if !names.insert(config.name.clone()) {
+            errors.push(format!("{}: duplicate schedule name", config.name));
+            continue;
+        }
+        if !config.enabled {
+            continue;
+        }
+
+        match validate_schedule(config) {
+            Ok(schedule) => schedules.push(schedule),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(schedules)
+}
+
+fn validate_schedule(config: ScheduleConfig) -> Result<BeatSchedule, String> {
+    let name = config.name;
+
+    if !tasks::TASK_NAMES.contains(&config.task.as_str()) {
+        return Err(format!("{}: unknown task {}", name, config.task));
+    }
+    if !config.args.is_object() {
+        return Err(format!("{}: args must be a table", name));
+    }
+
+    let schedule = match (config.cron, config.interval_seconds) {
+        (Some(cron), None) => {
+            let expression =
+                CronExpression::parse(&cron).map_err(|error| format!("{}: {}", name, error))?;
+            let tz = match config.timezone {
+                Some(timezone) => timezone
+                    .parse::<Tz>()
+                    .map_err(|_| format!("{}: unknown timezone {}", name, timezone))?,
+                None => Tz::UTC,
+            };
+            ConfiguredSchedule::Cron(expression, tz)
+        }
+        (None, Some(seconds)) => {
+            if seconds == 0 {
+                return Err(format!("{}: interval_seconds must be positive", name));
+            }
+            if config.timezone.is_some() {
+                return Err(format!("{}: timezone only applies to cron schedules", name));
+            }
+            ConfiguredSchedule::Interval(std::time::Duration::from_secs(seconds))
+        }
+        _ => {
+            return Err(format!(
+                "{}: set exactly one of cron or interval_seconds",
+                name
+            ))
+        }
+    };
+
+    Ok(BeatSchedule {
+        name,
+        task: config.task,
+        args: config.args,
+        schedule,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::New_York;
+
+    fn set(values: &[u32]) -> BTreeSet<u32> {
+        values.iter().copied().collect()
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.ymd(year, month, day).and_hms(hour, minute, 0)
+    }
+
+    fn next_utc(expression: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        CronExpression::parse(expression)
+            .unwrap()
+            .next_after(&after)
+    }
+
+    fn next_new_york(expression: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        CronExpression::parse(expression)
+            .unwrap()
+            .next_after(&after.with_timezone(&New_York))
+            .map(|next_dt| next_dt.with_timezone(&Utc))
+    }
+
+    #[test]
+    fn parses_steps_ranges_and_lists() {
+        let expression = CronExpression::parse("*/15 0-6/2 1,15 * 1-5").unwrap();
+
+        assert_eq!(expression.minutes, set(&[0, 15, 30, 45]));
+        assert_eq!(expression.hours, set(&[0, 2, 4, 6]));
+        assert_eq!(expression.days, set(&[1, 15]));
+        assert_eq!(expression.months, (1..=12).collect());
+        assert_eq!(expression.weekdays, set(&[1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn step_from_a_value_runs_to_the_end_of_the_field() {
+        let expression = CronExpression::parse("5/20 * * * *").unwrap();
+
+        assert_eq!(expression.minutes, set(&[5, 25, 45]));
+    }
+
+    #[test]
+    fn seven_is_sunday() {
+        assert_eq!(
+            CronExpression::parse("0 0 * * 7").unwrap().weekdays,
+            set(&[0])
+        );
+        assert_eq!(
+            CronExpression::parse("0 0 * * 5-7").unwrap().weekdays,
+            set(&[0, 5, 6])
+        );
+        // 2021-09-05 is a Sunday
+        assert_eq!(
+            next_utc("0 9 * * 7", utc(2021, 9, 1, 0, 0)),
+            Some(utc(2021, 9, 5, 9, 0))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in [
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+            "1- * * * *",
+        ]
+        .iter()
+        {
+            assert!(
+                CronExpression::parse(expression).is_err(),
+                "{} should be rejected",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn runs_strictly_after_the_given_time() {
+        assert_eq!(
+            next_utc("30 9 * * *", utc(2021, 9, 1, 9, 30)),
+            Some(utc(2021, 9, 2, 9, 30))
+        );
+        assert_eq!(
+            next_utc("30 9 * * *", utc(2021, 9, 1, 9, 29)),
+            Some(utc(2021, 9, 1, 9, 30))
+        );
+    }
+
+    #[test]
+    fn restricted_day_of_month_and_day_of_week_are_ored() {
+        // The 13th or any Friday. 2021-09-10 is a Friday, 2021-09-13 a Monday.
+        assert_eq!(
+            next_utc("0 0 13 * 5", utc(2021, 9, 4, 0, 0)),
+            Some(utc(2021, 9, 10, 0, 0))
+        );
+        assert_eq!(
+            next_utc("0 0 13 * 5", utc(2021, 9, 11, 0, 0)),
+            Some(utc(2021, 9, 13, 0, 0))
+        );
+    }
+
+    #[test]
+    fn wildcard_day_of_week_restricts_to_day_of_month() {
+        assert_eq!(
+            next_utc("0 0 13 * *", utc(2021, 9, 4, 0, 0)),
+            Some(utc(2021, 9, 13, 0, 0))
+        );
+        // And the other way around, 2021-09-06 is a Monday
+        assert_eq!(
+            next_utc("0 0 * * 1", utc(2021, 9, 4, 0, 0)),
+            Some(utc(2021, 9, 6, 0, 0))
+        );
+    }
+
+    #[test]
+    fn impossible_dates_never_run() {
+        assert_eq!(next_utc("0 0 31 2 *", utc(2021, 1, 1, 0, 0)), None);
+        assert_eq!(next_utc("0 0 31 4,6,9,11 *", utc(2021, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn rare_dates_are_found() {
+        assert_eq!(
+            next_utc("0 0 29 2 *", utc(2021, 3, 1, 0, 0)),
+            Some(utc(2024, 2, 29, 0, 0))
+        );
+    }
+
+    #[test]
+    fn skips_local_times_in_a_dst_gap() {
+        // 02:30 doesn't exist in New York on 2021-03-14, the clocks jump from 02:00 to 03:00
+        assert_eq!(
+            next_new_york("30 2 * * *", utc(2021, 3, 14, 5, 0)),
+            // 02:30 EDT on 2021-03-15
+            Some(utc(2021, 3, 15, 6, 30))
+        );
+        // Times after the gap on the same day still run, 03:30 EDT
+        assert_eq!(
+            next_new_york("30 3 * * *", utc(2021, 3, 14, 5, 0)),
+            Some(utc(2021, 3, 14, 7, 30))
+        );
+    }
+
+    #[test]
+    fn runs_once_in_a_dst_overlap() {
+        // 01:30 happens twice in New York on 2021-11-07, first in EDT then in EST
+        assert_eq!(
+            next_new_york("30 1 * * *", utc(2021, 11, 7, 4, 0)),
+            Some(utc(2021, 11, 7, 5, 30))
+        );
+        // Once it ran at 01:30 EDT, 01:10 EST is before the second 01:30 but it doesn't run again
+        assert_eq!(
+            next_new_york("30 1 * * *", utc(2021, 11, 7, 6, 10)),
+            // 01:30 EST on 2021-11-08
+            Some(utc(2021, 11, 8, 6, 30))
+        );
+    }
+
+    #[test]
+    fn hourly_expressions_keep_running_in_a_dst_overlap() {
+        // From 01:40 EDT the next run is 01:00 EST, twenty minutes later
+        assert_eq!(
+            next_new_york("*/30 * * * *", utc(2021, 11, 7, 5, 40)),
+            Some(utc(2021, 11, 7, 6, 0))
+        );
+        assert_eq!(
+            next_new_york("*/30 * * * *", utc(2021, 11, 7, 6, 0)),
+            Some(utc(2021, 11, 7, 6, 30))
+        );
+        // 01:30 EDT comes before the second 01:00
+        assert_eq!(
+            next_new_york("*/30 * * * *", utc(2021, 11, 7, 5, 10)),
+            Some(utc(2021, 11, 7, 5, 30))
+        );
+    }
+}
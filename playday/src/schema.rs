@@ -1,3 +1,72 @@
+table! {
+    api_tokens (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        token_hash -> Varchar,
+        token_prefix -> Varchar,
+        scopes -> Array<Varchar>,
+        created_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    credential_tokens (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        purpose -> Varchar,
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    credentials (user_id) {
+        user_id -> Uuid,
+        email -> Varchar,
+        password_hash -> Varchar,
+        email_verified_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_on -> Timestamp,
+    }
+}
+
+table! {
+    digests (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        sent_on -> Timestamp,
+    }
+}
+
+table! {
+    failed_logins (id) {
+        id -> Uuid,
+        email -> Varchar,
+        ip_address -> Varchar,
+        created_on -> Timestamp,
+    }
+}
+
+table! {
+    game_list_entries (list_id, wished_game_id) {
+        list_id -> Uuid,
+        wished_game_id -> Uuid,
+        added_on -> Timestamp,
+    }
+}
+
+table! {
+    game_lists (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        created_on -> Timestamp,
+    }
+}
+
 table! {
     game_stores (id) {
         id -> Uuid,
@@ -10,6 +79,155 @@ table! {
     }
 }
 
+table! {
+    gift_claims (id) {
+        id -> Uuid,
+        wished_game_id -> Uuid,
+        claimer_name -> Nullable<Varchar>,
+        claimer_ip_hash -> Varchar,
+        created_on -> Timestamp,
+    }
+}
+
+table! {
+    group_invites (id) {
+        id -> Uuid,
+        group_id -> Uuid,
+        email -> Nullable<Varchar>,
+        token_hash -> Varchar,
+        created_by -> Uuid,
+        expires_at -> Timestamp,
+        created_on -> Timestamp,
+    }
+}
+
+table! {
+    group_members (group_id, user_id) {
+        group_id -> Uuid,
+        user_id -> Uuid,
+        joined_on -> Timestamp,
+    }
+}
+
+table! {
+    groups (id) {
+        id -> Uuid,
+        name -> Varchar,
+        owner_id -> Uuid,
+        notify_releases -> Bool,
+        created_on -> Timestamp,
+    }
+}
+
+table! {
+    imports (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        source -> Varchar,
+        status -> Varchar,
+        input_rows -> Jsonb,
+        report -> Nullable<Jsonb>,
+        error -> Nullable<Varchar>,
+        created_on -> Timestamp,
+        updated_on -> Timestamp,
+    }
+}
+
+table! {
+    invite_emails (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        created_on -> Timestamp,
+    }
+}
+
+table! {
+    release_date_changes (id) {
+        id -> Uuid,
+        wished_game_id -> Uuid,
+        user_id -> Uuid,
+        title -> Varchar,
+        old_release_date -> Int8,
+        new_release_date -> Int8,
+        changed_on -> Timestamp,
+    }
+}
+
+table! {
+    sessions (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        created_at -> Timestamp,
+        last_seen -> Timestamp,
+        user_agent -> Nullable<Varchar>,
+        ip_address -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    share_links (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        token -> Varchar,
+        list_id -> Nullable<Uuid>,
+        show_notes -> Bool,
+        show_prices -> Bool,
+        created_on -> Timestamp,
+    }
+}
+
+table! {
+    sync_runs (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        store_name -> Varchar,
+        status -> Varchar,
+        task_id -> Nullable<Varchar>,
+        items_total -> Nullable<Int4>,
+        items_imported -> Int4,
+        error -> Nullable<Varchar>,
+        created_on -> Timestamp,
+        started_on -> Nullable<Timestamp>,
+        finished_on -> Nullable<Timestamp>,
+        updated_on -> Timestamp,
+    }
+}
+
+table! {
+    tags (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        created_on -> Timestamp,
+    }
+}
+
+table! {
+    user_identities (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        provider -> Varchar,
+        subject -> Varchar,
+        email -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        last_login -> Timestamp,
+    }
+}
+
+table! {
+    user_settings (user_id) {
+        user_id -> Uuid,
+        notification_channels -> Array<Varchar>,
+        lead_times -> Array<Int4>,
+        quiet_hours_start -> Nullable<Int2>,
+        quiet_hours_end -> Nullable<Int2>,
+        timezone -> Varchar,
+        delivery_mode -> Varchar,
+        updated_on -> Timestamp,
+        weekly_digest -> Bool,
+    }
+}
+
 table! {
     users (id) {
         id -> Uuid,
@@ -20,6 +238,40 @@ table! {
     }
 }
 
+table! {
+    webhook_deliveries (id) {
+        id -> Uuid,
+        webhook_id -> Uuid,
+        event -> Varchar,
+        event_key -> Varchar,
+        payload -> Jsonb,
+        status -> Varchar,
+        attempts -> Int4,
+        response_code -> Nullable<Int4>,
+        last_error -> Nullable<Varchar>,
+        created_on -> Timestamp,
+        updated_on -> Timestamp,
+    }
+}
+
+table! {
+    webhooks (id) {
+        id -> Uuid,
+        url -> Varchar,
+        payload_format -> Varchar,
+        secret -> Varchar,
+        added_on -> Timestamp,
+        user_id -> Uuid,
+    }
+}
+
+table! {
+    wished_game_tags (tag_id, wished_game_id) {
+        tag_id -> Uuid,
+        wished_game_id -> Uuid,
+    }
+}
+
 table! {
     wished_games (id) {
         id -> Uuid,
@@ -29,11 +281,43 @@ table! {
         added_on -> Timestamp,
         user_id -> Uuid,
         pc_release_date -> Int8,
+        total_rating -> Nullable<Float8>,
+        platform_slugs -> Array<Varchar>,
+        status -> Varchar,
+        started_on -> Nullable<Timestamp>,
+        finished_on -> Nullable<Timestamp>,
+        priority -> Int2,
+        notes -> Text,
+        personal_rating -> Nullable<Int2>,
+        max_price_cents -> Nullable<Int4>,
     }
 }
 
 allow_tables_to_appear_in_same_query!(
+    api_tokens,
+    credential_tokens,
+    credentials,
+    digests,
+    failed_logins,
+    game_list_entries,
+    game_lists,
     game_stores,
+    gift_claims,
+    group_invites,
+    group_members,
+    groups,
+    imports,
+    invite_emails,
+    release_date_changes,
+    sessions,
+    share_links,
+    sync_runs,
+    tags,
+    user_identities,
+    user_settings,
     users,
+    webhook_deliveries,
+    webhooks,
+    wished_game_tags,
     wished_games,
 );
@@ -0,0 +1,315 @@
+use chrono::{DateTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::models::UserSettings;
+
+pub const CHANNEL_WEBHOOK: &str = "webhook";
+pub const CHANNEL_EMAIL: &str = "email";
+pub const CHANNELS: [&str; 2] = [CHANNEL_WEBHOOK, CHANNEL_EMAIL];
+
+pub const MODE_IMMEDIATE: &str = "immediate";
+pub const MODE_DIGEST: &str = "digest";
+
+const MAX_LEAD_TIME_DAYS: i32 = 365;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SettingsUpdate {
+    pub notification_channels: Vec<String>,
+    pub lead_times: Vec<i32>,
+    pub quiet_hours_start: Option<i16>,
+    pub quiet_hours_end: Option<i16>,
+    pub timezone: String,
+    pub delivery_mode: String,
+    /// Off unless asked for, older clients leave it out.
+    #[serde(default)]
+    pub weekly_digest: bool,
+}
+
+/// Settings used for users who never saved any, matching the column defaults.
+pub fn default_settings(usr_id: Uuid) -> UserSettings {
+    UserSettings {
+        user_id: usr_id,
+        notification_channels: vec![CHANNEL_WEBHOOK.to_string()],
+        lead_times: vec![3],
+        quiet_hours_start: None,
+        quiet_hours_end: None,
+        timezone: "UTC".to_string(),
+        delivery_mode: MODE_IMMEDIATE.to_string(),
+        updated_on: Utc::now().naive_utc(),
+        weekly_digest: false,
+    }
+}
+
+/// Validates an update and turns it into settings for the user. Lead times are
+/// de-duplicated and kept in descending order.
+pub fn apply_update(usr_id: Uuid, update: SettingsUpdate) -> Result<UserSettings, String> {
+    for channel in update.notification_channels.iter() {
+        if !CHANNELS.contains(&channel.as_str()) {
+            return Err(format!("Unknown notification channel: {}", channel));
+        }
+    }
+
+    if update.timezone.parse::<Tz>().is_err() {
+        return Err(format!("Unknown timezone: {}", update.timezone));
+    }
+
+    if update.delivery_mode != MODE_IMMEDIATE && update.delivery_mode != MODE_DIGEST {
+        return Err(format!("Unknown delivery mode: {}", update.delivery_mode));
+    }
+
+    // Reminders go out by webhook, without it an immediate user would hear nothing
+    if update.delivery_mode == MODE_IMMEDIATE
+        && !update.notification_channels.iter().any(|c| c == CHANNEL_WEBHOOK)
+    {
+        return Err("The immediate delivery mode needs the webhook channel".to_string());
+    }
+
+    // The digest goes out by email
+    if update.weekly_digest && !update.notification_channels.iter().any(|c| c == CHANNEL_EMAIL) {
+        return Err("The weekly digest needs the email channel".to_string());
+    }
+
+    // Digest mode holds the reminders back for the digest, without it the user would hear nothing
+    if update.delivery_mode == MODE_DIGEST && !update.weekly_digest {
+        return Err("The digest delivery mode needs the weekly digest".to_string());
+    }
+
+    match (update.quiet_hours_start, update.quiet_hours_end) {
+        (None, None) => {}
+        (Some(start), Some(end)) if (0..24).contains(&start) && (0..24).contains(&end) => {}
+        _ => {
+            return Err(
+                "Quiet hours need both a start and an end hour between 0 and 23".to_string(),
+            )
+        }
+    }
+
+    let mut lead_times = update.lead_times;
+    if lead_times
+        .iter()
+        .any(|days| *days < 0 || *days > MAX_LEAD_TIME_DAYS)
+    {
+        return Err(format!(
+            "Lead times must be between 0 and {} days",
+            MAX_LEAD_TIME_DAYS
+        ));
+    }
+    lead_times.sort_unstable_by(|a, b| b.cmp(a));
+    lead_times.dedup();
+
+    let mut channels = update.notification_channels;
+    channels.sort();
+    channels.dedup();
+
+    Ok(UserSettings {
+        user_id: usr_id,
+        notification_channels: channels,
+        lead_times,
+        quiet_hours_start: update.quiet_hours_start,
+        quiet_hours_end: update.quiet_hours_end,
+        timezone: update.timezone,
+        delivery_mode: update.delivery_mode,
+        updated_on: Utc::now().naive_utc(),
+        weekly_digest: update.weekly_digest,
+    })
+}
+
+impl UserSettings {
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse::<Tz>().unwrap_or(Tz::UTC)
+    }
+
+    pub fn has_channel(&self, channel: &str) -> bool {
+        self.notification_channels.iter().any(|c| c == channel)
+    }
+
+    pub fn is_digest(&self) -> bool {
+        self.delivery_mode == MODE_DIGEST
+    }
+
+    /// Whether `now` falls in the user's quiet hours. The window may wrap around midnight,
+    /// e.g. 22 to 7.
+    pub fn is_quiet_time(&self, now: DateTime<Utc>) -> bool {
+        let (start, end) = match (self.quiet_hours_start, self.quiet_hours_end) {
+            (Some(start), Some(end)) => (start as u32, end as u32),
+            _ => return false,
+        };
+        let hour = now.with_timezone(&self.tz()).hour();
+
+        if start <= end {
+            hour >= start && hour < end
+        } else {
+            hour >= start || hour < end
+        }
+    }
+
+    /// Days until `release_date` counted in calendar days of the user's timezone,
+    /// 0 being the release day.
+    pub fn days_until(&self, now: DateTime<Utc>, release_date: i64) -> i64 {
+        let tz = self.tz();
+        let today = now.with_timezone(&tz).date();
+        let release_day = Utc.timestamp(release_date, 0).with_timezone(&tz).date();
+
+        release_day.signed_duration_since(today).num_days()
+    }
+
+    /// The lead time whose reminder is due for a game `days_left` days away, which is
+    /// the smallest configured lead time not below it.
+    pub fn due_lead_time(&self, days_left: i64) -> Option<i32> {
+        if days_left < 0 {
+            return None;
+        }
+
+        self.lead_times
+            .iter()
+            .filter(|days| **days as i64 >= days_left)
+            .min()
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings_with(
+        lead_times: Vec<i32>,
+        quiet_hours: Option<(i16, i16)>,
+        timezone: &str,
+    ) -> UserSettings {
+        UserSettings {
+            lead_times,
+            quiet_hours_start: quiet_hours.map(|(start, _)| start),
+            quiet_hours_end: quiet_hours.map(|(_, end)| end),
+            timezone: timezone.to_string(),
+            ..default_settings(Uuid::nil())
+        }
+    }
+
+    fn update_with(channels: &[&str], delivery_mode: &str) -> SettingsUpdate {
+        SettingsUpdate {
+            notification_channels: channels.iter().map(|c| c.to_string()).collect(),
+            lead_times: vec![1, 7, 0, 7],
+            quiet_hours_start: None,
+            quiet_hours_end: None,
+            timezone: "Europe/Berlin".to_string(),
+            delivery_mode: delivery_mode.to_string(),
+            weekly_digest: delivery_mode == MODE_DIGEST,
+        }
+    }
+
+    #[test]
+    fn due_lead_time_is_the_smallest_lead_time_not_below_days_left() {
+        let usr_settings = settings_with(vec![7, 1, 0], None, "UTC");
+
+        assert_eq!(usr_settings.due_lead_time(0), Some(0));
+        assert_eq!(usr_settings.due_lead_time(1), Some(1));
+        assert_eq!(usr_settings.due_lead_time(3), Some(7));
+        assert_eq!(usr_settings.due_lead_time(7), Some(7));
+        assert_eq!(usr_settings.due_lead_time(8), None);
+        assert_eq!(usr_settings.due_lead_time(-1), None);
+    }
+
+    #[test]
+    fn release_day_counts_in_the_users_timezone() {
+        // Released at midnight UTC on 2021-10-05
+        let release_date = Utc.ymd(2021, 10, 5).and_hms(0, 0, 0).timestamp();
+        let now = Utc.ymd(2021, 10, 5).and_hms(9, 0, 0);
+
+        // The release day goes on after the release timestamp
+        assert_eq!(settings_with(vec![0], None, "UTC").days_until(now, release_date), 0);
+        let berlin = settings_with(vec![0], None, "Europe/Berlin");
+        assert_eq!(berlin.days_until(now, release_date), 0);
+        // It came out in the evening of 2021-10-04 in New York
+        let new_york = settings_with(vec![0], None, "America/New_York");
+        assert_eq!(new_york.days_until(now, release_date), -1);
+        assert_eq!(new_york.days_until(now - chrono::Duration::hours(12), release_date), 0);
+    }
+
+    #[test]
+    fn quiet_hours_within_a_day() {
+        let usr_settings = settings_with(vec![0], Some((9, 17)), "UTC");
+
+        assert!(!usr_settings.is_quiet_time(Utc.ymd(2021, 10, 5).and_hms(8, 59, 0)));
+        assert!(usr_settings.is_quiet_time(Utc.ymd(2021, 10, 5).and_hms(9, 0, 0)));
+        assert!(usr_settings.is_quiet_time(Utc.ymd(2021, 10, 5).and_hms(16, 59, 0)));
+        assert!(!usr_settings.is_quiet_time(Utc.ymd(2021, 10, 5).and_hms(17, 0, 0)));
+    }
+
+    #[test]
+    fn quiet_hours_wrap_around_midnight() {
+        let usr_settings = settings_with(vec![0], Some((22, 7)), "UTC");
+
+        assert!(usr_settings.is_quiet_time(Utc.ymd(2021, 10, 5).and_hms(23, 0, 0)));
+        assert!(usr_settings.is_quiet_time(Utc.ymd(2021, 10, 5).and_hms(3, 0, 0)));
+        assert!(!usr_settings.is_quiet_time(Utc.ymd(2021, 10, 5).and_hms(7, 0, 0)));
+        assert!(!usr_settings.is_quiet_time(Utc.ymd(2021, 10, 5).and_hms(21, 59, 0)));
+    }
+
+    #[test]
+    fn quiet_hours_use_the_users_timezone() {
+        // 22:00 to 07:00 in Berlin, which is UTC+2 in October
+        let usr_settings = settings_with(vec![0], Some((22, 7)), "Europe/Berlin");
+
+        assert!(usr_settings.is_quiet_time(Utc.ymd(2021, 10, 5).and_hms(20, 30, 0)));
+        assert!(!usr_settings.is_quiet_time(Utc.ymd(2021, 10, 5).and_hms(5, 30, 0)));
+    }
+
+    #[test]
+    fn no_quiet_hours_is_never_quiet() {
+        let usr_settings = settings_with(vec![0], None, "UTC");
+
+        assert!(!usr_settings.is_quiet_time(Utc.ymd(2021, 10, 5).and_hms(3, 0, 0)));
+    }
+
+    #[test]
+    fn update_sorts_and_dedups_lead_times() {
+        let usr_settings =
+            apply_update(Uuid::nil(), update_with(&[CHANNEL_WEBHOOK], MODE_IMMEDIATE)).unwrap();
+
+        assert_eq!(usr_settings.lead_times, vec![7, 1, 0]);
+    }
+
+    #[test]
+    fn update_rejects_modes_without_their_channel() {
+        assert!(
+            apply_update(Uuid::nil(), update_with(&[CHANNEL_EMAIL], MODE_IMMEDIATE)).is_err()
+        );
+        assert!(apply_update(Uuid::nil(), update_with(&[CHANNEL_WEBHOOK], MODE_DIGEST)).is_err());
+        assert!(apply_update(Uuid::nil(), update_with(&[CHANNEL_EMAIL], MODE_DIGEST)).is_ok());
+    }
+
+    #[test]
+    fn weekly_digest_is_independent_of_the_delivery_mode() {
+        let mut update = update_with(&[CHANNEL_WEBHOOK, CHANNEL_EMAIL], MODE_IMMEDIATE);
+        update.weekly_digest = true;
+        assert!(apply_update(Uuid::nil(), update).unwrap().weekly_digest);
+
+        let mut update = update_with(&[CHANNEL_WEBHOOK], MODE_IMMEDIATE);
+        update.weekly_digest = true;
+        assert!(apply_update(Uuid::nil(), update).is_err());
+
+        let mut update = update_with(&[CHANNEL_EMAIL], MODE_DIGEST);
+        update.weekly_digest = false;
+        assert!(apply_update(Uuid::nil(), update).is_err());
+    }
+
+    #[test]
+    fn update_rejects_invalid_values() {
+        let mut update = update_with(&[CHANNEL_WEBHOOK], MODE_IMMEDIATE);
+        update.lead_times = vec![-1];
+        assert!(apply_update(Uuid::nil(), update).is_err());
+
+        let mut update = update_with(&[CHANNEL_WEBHOOK], MODE_IMMEDIATE);
+        update.quiet_hours_start = Some(22);
+        assert!(apply_update(Uuid::nil(), update).is_err());
+
+        let mut update = update_with(&[CHANNEL_WEBHOOK], MODE_IMMEDIATE);
+        update.timezone = "Mars/Olympus_Mons".to_string();
+        assert!(apply_update(Uuid::nil(), update).is_err());
+
+        assert!(apply_update(Uuid::nil(), update_with(&["sms"], MODE_IMMEDIATE)).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use std::net::IpAddr;
+
+use chrono::{Duration, NaiveDateTime, TimeZone, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::models::{GiftClaim, ShareLink, WishedGame};
+
+/// How many games one address can claim in a day, across all share pages.
+pub const MAX_CLAIMS_PER_DAY: i64 = 10;
+const MAX_CLAIMER_NAME_LENGTH: usize = 50;
+
+/// What the owner picks when publishing their wishlist, or one of their lists.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewShareLink {
+    pub list_id: Option<Uuid>,
+    #[serde(default)]
+    pub show_notes: bool,
+    #[serde(default)]
+    pub show_prices: bool,
+}
+
+/// A game as shown on the public page, notes and prices are only there when the owner
+/// asked for them.
+#[derive(Debug, Serialize)]
+pub struct SharedGame {
+    pub id: Uuid,
+    pub title: String,
+    pub cover_url: Option<String>,
+    pub release_date: String,
+    pub platforms: Vec<String>,
+    pub notes: Option<String>,
+    pub max_price: Option<String>,
+    pub claim: Option<SharedClaim>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SharedClaim {
+    pub claimer_name: Option<String>,
+    /// The visitor made this claim and may release it.
+    pub is_own: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClaimForm {
+    pub claimer_name: Option<String>,
+}
+
+pub fn new_share_link(usr_id: Uuid, new_link: NewShareLink) -> ShareLink {
+    ShareLink {
+        id: Uuid::new_v4(),
+        user_id: usr_id,
+        // The token is all that protects the page, it has to be unguessable
+        token: format!(
+            "{}{}",
+            Uuid::new_v4().to_simple(),
+            Uuid::new_v4().to_simple()
+        ),
+        list_id: new_link.list_id,
+        show_notes: new_link.show_notes,
+        show_prices: new_link.show_prices,
+        created_on: Utc::now().naive_utc(),
+    }
+}
+
+fn format_date(epoch: i64) -> String {
+    if epoch == 0 {
+        return "TBD".to_string();
+    }
+
+    Utc.timestamp(epoch, 0).format("%d %b %Y").to_string()
+}
+
+fn format_price(cents: i32) -> String {
+    format!("{}.{:02}", cents / 100, cents % 100)
+}
+
+/// `claim` is left out for the owner, who should not find out what they are getting.
+pub fn to_shared_game(
+    share_link: &ShareLink,
+    game: &WishedGame,
+    claim: Option<&GiftClaim>,
+    own_claims: &[Uuid],
+) -> SharedGame {
+    let cover_url = game.igdb_info["cover"]["image_id"]
+        .as_str()
+        .map(|image_id| {
+            format!(
+                "https://images.igdb.com/igdb/image/upload/t_cover_big/{}.jpg",
+                image_id
+            )
+        });
+    let platforms = game.igdb_info["platforms"]
+        .as_array()
+        .map(|platforms| {
+            platforms
+                .iter()
+                .filter_map(|platform| platform["name"].as_str())
+                .map(|name| name.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    SharedGame {
+        id: game.id,
+        title: game.title.to_owned(),
+        cover_url,
+        release_date: format_date(game.pc_release_date),
+        platforms,
+        notes: if share_link.show_notes && !game.notes.is_empty() {
+            Some(game.notes.to_owned())
+        } else {
+            None
+        },
+        max_price: game
+            .max_price_cents
+            .filter(|_| share_link.show_prices)
+            .map(format_price),
+        claim: claim.map(|claim| SharedClaim {
+            claimer_name: claim.claimer_name.to_owned(),
+            is_own: own_claims.contains(&claim.id),
+        }),
+    }
+}
+
+/// Claims are rate limited per address. Only a keyed hash of it is kept, a plain hash of
+/// an IPv4 address is reversed by hashing all of them.
+pub fn hash_claimer_ip(key: &[u8], ip: IpAddr) -> String {
+    let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC can take key of any size");
+    mac.update(ip.to_string().as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub fn claims_window_start(now: NaiveDateTime) -> NaiveDateTime {
+    now - Duration::days(1)
+}
+
+pub fn new_gift_claim(
+    game_id: Uuid,
+    claimer_name: Option<String>,
+    claimer_ip_hash: String,
+) -> Result<GiftClaim, String> {
+    let claimer_name = claimer_name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    if let Some(name) = &claimer_name {
+        if name.chars().count() > MAX_CLAIMER_NAME_LENGTH {
+            return Err(format!(
+                "Names can be at most {} characters long",
+                MAX_CLAIMER_NAME_LENGTH
+            ));
+        }
+    }
+
+    Ok(GiftClaim {
+        id: Uuid::new_v4(),
+        wished_game_id: game_id,
+        claimer_name,
+        claimer_ip_hash,
+        created_on: Utc::now().naive_utc(),
+    })
+}
@@ -1,25 +1,218 @@
-use celery::task::TaskResult;
-use chrono::{Duration, TimeZone, Utc};
-use anyhow::Result;
-use celery::broker::AMQPBroker;
+use celery::error::TaskError;
+use celery::task::{AsyncResult, Signature, Task, TaskResult};
+use chrono::{DateTime, Duration, Utc};
+use anyhow::{anyhow, bail, Result};
+use celery::beat::{Beat, LocalSchedulerBackend, Schedule};
+use celery::broker::{AMQPBroker, Broker};
+use diesel::prelude::PgConnection;
+use serde::Serialize;
+use serde_json::json;
 use uuid::Uuid;
 
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration as StdDuration;
 
 use crate::db;
+use crate::deals::Deals;
+use crate::digest;
+use crate::epicgames::{EpicGames, EpicGamesToken, LibraryItem};
+use crate::igdb::{IGDBGame, IGDB};
+use crate::imports::{self, ImportRow, RowOutcome};
+use crate::library;
+use crate::mailer::{self, Mailer};
+use crate::models::{Digest, ReleaseDateChange, SyncRun, UserSettings, WishedGame};
+use crate::settings;
+use crate::webhooks;
+use crate::wishlist::{self, GameStatus};
 
-const QUEUE_NAME: &str = "playday_celery";
+/// Queue every task is routed to and the worker consumes from.
+pub const QUEUE_NAME: &str = "playday_celery";
 
-pub async fn get_celery_app() -> Result<Arc<celery::Celery<celery::broker::AMQPBroker>>> {
-    let my_app = celery::app!(
-        broker = AMQPBroker { std::env::var("AMQP_ADDR").unwrap() },
-        tasks = [whats_for_tomorrow, sync_epicgames_library],
-        task_routes = [
-            "*" => QUEUE_NAME,
-        ],
-    ).await?;
+/// Declares the task registry. The web producer, the worker and beat all build their
+/// Celery apps from here, so a task listed once is known to every binary.
+macro_rules! task_registry {
+    ($($task:ident),* $(,)?) => {
+        /// Names of every registered task.
+        pub const TASK_NAMES: &[&str] = &[$(<$task as Task>::NAME),*];
 
-    Ok(my_app)
+        pub async fn get_celery_app() -> Result<Arc<celery::Celery<AMQPBroker>>> {
+            let broker_url = std::env::var("AMQP_ADDR")?;
+            let my_app = celery::app!(
+                broker = AMQPBroker { broker_url },
+                tasks = [$($task),*],
+                task_routes = [
+                    "*" => QUEUE_NAME,
+                ],
+            ).await?;
+
+            Ok(my_app)
+        }
+
+        /// Schedules the registered task called `task_name` on beat, with its arguments
+        /// given as JSON.
+        pub fn schedule_task<S: Schedule + 'static>(
+            beat: &mut Beat<AMQPBroker, LocalSchedulerBackend>,
+            schedule_name: &str,
+            task_name: &str,
+            args: serde_json::Value,
+            schedule: S,
+        ) -> Result<()> {
+            $(
+                if task_name == <$task as Task>::NAME {
+                    let params = serde_json::from_value(args)
+                        .map_err(|error| anyhow!("Invalid args for {}: {}", task_name, error))?;
+                    beat.schedule_named_task(
+                        schedule_name.to_string(),
+                        Signature::<$task>::new(params),
+                        schedule,
+                    );
+                    return Ok(());
+                }
+            )*
+
+            bail!("Unknown task {}", task_name)
+        }
+    };
+}
+
+task_registry![
+    whats_for_tomorrow,
+    sync_epicgames_library,
+    deliver_webhook,
+    refresh_release_dates,
+    send_weekly_digests,
+    resolve_import,
+];
+
+/// A beat without schedules, sending to the same queue as the producer.
+pub async fn get_celery_beat() -> Result<Beat<AMQPBroker, LocalSchedulerBackend>> {
+    let broker_url = std::env::var("AMQP_ADDR")?;
+    let beat = Beat::<AMQPBroker, LocalSchedulerBackend>::default_builder("beat", &broker_url)
+        .task_route("*", QUEUE_NAME)
+        .build()
+        .await?;
+
+    Ok(beat)
+}
+
+/// Connection state of the producer, as shown by the readiness endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProducerStatus {
+    pub connected: bool,
+    pub last_error: Option<String>,
+}
+
+/// Seconds the producer waits for the broker when checking or connecting.
+const BROKER_CONNECTION_TIMEOUT_SECS: u32 = 2;
+
+/// The web app's producer, shared by every request. It connects on first use and again
+/// once the broker dropped the connection, so a broker restart needs no web app restart.
+#[derive(Default)]
+pub struct Producer {
+    app: Mutex<Option<Arc<celery::Celery<AMQPBroker>>>>,
+    last_error: Mutex<Option<String>>,
+    /// Held while connecting, so the requests that come in during a broker outage wait
+    /// for one connection attempt instead of each making their own.
+    connecting: futures::lock::Mutex<()>,
+}
+
+impl Producer {
+    pub fn new() -> Producer {
+        Producer::default()
+    }
+
+    fn current(&self) -> Option<Arc<celery::Celery<AMQPBroker>>> {
+        self.app.lock().unwrap().clone()
+    }
+
+    async fn connection(&self) -> Result<Arc<celery::Celery<AMQPBroker>>> {
+        if let Some(app) = self.current() {
+            return Ok(app);
+        }
+
+        let _connecting = self.connecting.lock().await;
+        // Somebody else may have connected while we waited
+        if let Some(app) = self.current() {
+            return Ok(app);
+        }
+
+        match get_celery_app().await {
+            Ok(app) => {
+                *self.app.lock().unwrap() = Some(app.clone());
+                *self.last_error.lock().unwrap() = None;
+                Ok(app)
+            }
+            Err(error) => {
+                log::error!("Could not connect to the broker! {:#}", error);
+                *self.last_error.lock().unwrap() = Some(error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    /// Drops a broken connection so the next use connects again. A connection that
+    /// already replaced it is kept.
+    fn disconnect(&self, app: &Arc<celery::Celery<AMQPBroker>>, error: String) {
+        *self.last_error.lock().unwrap() = Some(error);
+
+        let mut current = self.app.lock().unwrap();
+        if current.as_ref().map_or(false, |current| Arc::ptr_eq(current, app)) {
+            *current = None;
+        }
+    }
+
+    /// Connects when there is no connection yet.
+    pub async fn connect(&self) -> Result<()> {
+        self.connection().await.map(|_| ())
+    }
+
+    /// `signature` builds the task to send, it is called again when the first attempt
+    /// failed and the task is retried on a new connection.
+    pub async fn send_task<T, F>(&self, signature: F) -> Result<AsyncResult>
+    where
+        T: Task,
+        F: Fn() -> Signature<T>,
+    {
+        let app = self.connection().await?;
+        match app.send_task(signature()).await {
+            Ok(async_result) => return Ok(async_result),
+            Err(error) => {
+                log::warn!("Could not send task, connecting again! {}", error);
+                self.disconnect(&app, error.to_string());
+            }
+        }
+
+        let app = self.connection().await?;
+        app.send_task(signature()).await.map_err(|error| {
+            self.disconnect(&app, error.to_string());
+            error.into()
+        })
+    }
+
+    /// Checks the live broker connection, which the AMQP heartbeat keeps up to date. A
+    /// dropped connection is reconnected on the spot and only reported when that fails.
+    pub async fn status(&self) -> ProducerStatus {
+        let connected = match self.connection().await {
+            Ok(app) => match app.broker.reconnect(BROKER_CONNECTION_TIMEOUT_SECS).await {
+                Ok(()) => {
+                    *self.last_error.lock().unwrap() = None;
+                    true
+                }
+                Err(error) => {
+                    self.disconnect(&app, error.to_string());
+                    false
+                }
+            },
+            Err(_) => false,
+        };
+
+        ProducerStatus {
+            connected,
+            last_error: self.last_error.lock().unwrap().clone(),
+        }
+    }
 }
 
 #[celery::task]
@@ -28,38 +221,596 @@ pub fn add(x: i32, y: i32) -> TaskResult<i32> {
     Ok(x + y)
 }
 
-#[celery::task]
-pub fn whats_for_tomorrow() -> TaskResult<bool> {
+fn map_unexpected<E: std::fmt::Display>(error: E) -> TaskError {
+    TaskError::UnexpectedError(error.to_string())
+}
+
+fn cached_settings<'a>(
+    db_conn: &PgConnection,
+    user_settings: &'a mut HashMap<Uuid, UserSettings>,
+    usr_id: Uuid,
+) -> Result<&'a UserSettings> {
+    if !user_settings.contains_key(&usr_id) {
+        let usr_settings = db::get_user_settings(db_conn, usr_id)?
+            .unwrap_or_else(|| settings::default_settings(usr_id));
+        user_settings.insert(usr_id, usr_settings);
+    }
+
+    Ok(&user_settings[&usr_id])
+}
+
+/// Days until the release, if one of the user's reminders is due now. Digest users hear
+/// about it in the digest, quiet hours are picked up on a later run.
+fn due_reminder(
+    usr_settings: &UserSettings,
+    now_dt: DateTime<Utc>,
+    release_date: i64,
+) -> Option<(i64, i32)> {
+    if usr_settings.is_digest()
+        || !usr_settings.has_channel(settings::CHANNEL_WEBHOOK)
+        || usr_settings.is_quiet_time(now_dt)
+    {
+        return None;
+    }
+
+    let days_left = usr_settings.days_until(now_dt, release_date);
+    usr_settings
+        .due_lead_time(days_left)
+        .map(|lead_time| (days_left, lead_time))
+}
+
+fn release_message(title: &str, days_left: i64) -> String {
+    match days_left {
+        0 => format!("{} releases today!", title),
+        1 => format!("{} releases tomorrow!", title),
+        days => format!("{} releases in {} days!", title, days),
+    }
+}
+
+/// Returns the ids of the deliveries that were not queued before under `event_key`.
+fn queue_deliveries(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    event: &str,
+    event_key: &str,
+    payload: serde_json::Value,
+) -> Result<Vec<Uuid>> {
+    let mut delivery_ids: Vec<Uuid> = Vec::new();
+
+    for webhook in db::get_webhooks_with_secrets(db_conn, usr_id)?.iter() {
+        let delivery = webhooks::new_delivery(webhook, event, event_key, payload.clone());
+
+        if db::create_webhook_delivery(db_conn, &delivery)? {
+            delivery_ids.push(delivery.id);
+        }
+    }
+
+    Ok(delivery_ids)
+}
+
+/// Creates a webhook delivery for every game whose reminder is due according to the
+/// owner's settings. Each delivery is keyed on the game, its release date and the lead
+/// time, so re-runs skip reminders that were already sent and a changed release date
+/// gets fresh ones.
+fn queue_release_reminders() -> Result<Vec<Uuid>> {
     let db_conn = db::establish_connection();
 
     // Get all games in wishlist
-    let results = db::get_future_wishlist_games(&db_conn).unwrap(); // TODO: Handle error in better way
+    let results = db::get_future_wishlist_games(&db_conn)?;
 
     let now_dt = Utc::now();
-    let release_dt_threshold = now_dt + Duration::days(3); // 3 days from now
-
-    let release_dt_epoch = release_dt_threshold.timestamp();
+    let mut user_settings: HashMap<Uuid, UserSettings> = HashMap::new();
+    let mut delivery_ids: Vec<Uuid> = Vec::new();
 
     // For each game
     for game in results.iter() {
-        let release_dt = Utc.timestamp(game.pc_release_date, 0);
+        let usr_settings = cached_settings(&db_conn, &mut user_settings, game.user_id)?;
+        let due = due_reminder(usr_settings, now_dt, game.pc_release_date);
+        let (days_left, lead_time) = match due {
+            Some(due) => due,
+            None => continue,
+        };
+        log::info!("{} releasing in {} days", game.title, days_left);
 
-        let duration = release_dt.signed_duration_since(now_dt);
+        let payload = webhooks::build_payload(
+            webhooks::EVENT_RELEASE_REMINDER,
+            &release_message(&game.title, days_left),
+            json!({
+                "wished_game_id": game.id,
+                "igdb_id": game.igdb_id,
+                "title": game.title,
+                "pc_release_date": game.pc_release_date,
+                "days_left": days_left,
+            }),
+        );
+        let event_key = format!(
+            "{}:{}:{}:{}",
+            webhooks::EVENT_RELEASE_REMINDER,
+            game.id,
+            game.pc_release_date,
+            lead_time
+        );
 
-        println!("{} releasing in {} days", game.title, duration.num_days())
-        // if game.pc_release_date < release_dt_epoch {
-        // }
+        delivery_ids.extend(queue_deliveries(
+            &db_conn,
+            game.user_id,
+            webhooks::EVENT_RELEASE_REMINDER,
+            &event_key,
+            payload,
+        )?);
     }
-    // is it releasing tomorrow?
-    // Send email
 
-    // Get latest info from igdb incase release date is updated
-    // update entry in db
+    delivery_ids.extend(queue_group_release_reminders(
+        &db_conn,
+        &results,
+        &mut user_settings,
+        now_dt,
+    )?);
+
+    Ok(delivery_ids)
+}
+
+/// For groups that turned on release notifications, reminds every member about games at
+/// least two of them want, on the member's own schedule. Keyed per group, so a member
+/// in two groups hears about it from both.
+fn queue_group_release_reminders(
+    db_conn: &PgConnection,
+    games: &[WishedGame],
+    user_settings: &mut HashMap<Uuid, UserSettings>,
+    now_dt: DateTime<Utc>,
+) -> Result<Vec<Uuid>> {
+    let notifying_groups = db::get_notifying_groups(db_conn)?;
+    let group_ids: Vec<Uuid> = notifying_groups.iter().map(|group| group.id).collect();
+    let members = db::get_members_of_groups(db_conn, &group_ids)?;
+    let mut delivery_ids: Vec<Uuid> = Vec::new();
+
+    for group in notifying_groups.iter() {
+        let member_ids: Vec<Uuid> = members
+            .iter()
+            .filter(|member| member.group_id == group.id)
+            .map(|member| member.user_id)
+            .collect();
+
+        let mut wanted: HashMap<i32, Vec<&WishedGame>> = HashMap::new();
+        for game in games
+            .iter()
+            .filter(|game| member_ids.contains(&game.user_id))
+        {
+            wanted.entry(game.igdb_id).or_default().push(game);
+        }
+
+        for wishers in wanted.values().filter(|wishers| wishers.len() >= 2) {
+            let game = wishers[0];
+
+            for member_id in member_ids.iter() {
+                let usr_settings = cached_settings(db_conn, user_settings, *member_id)?;
+                let due = due_reminder(usr_settings, now_dt, game.pc_release_date);
+                let (days_left, lead_time) = match due {
+                    Some(due) => due,
+                    None => continue,
+                };
+
+                let message = format!(
+                    "{} {} people in {} want it.",
+                    release_message(&game.title, days_left),
+                    wishers.len(),
+                    group.name
+                );
+                let payload = webhooks::build_payload(
+                    webhooks::EVENT_GROUP_RELEASE_REMINDER,
+                    &message,
+                    json!({
+                        "group_id": group.id,
+                        "group_name": group.name,
+                        "igdb_id": game.igdb_id,
+                        "title": game.title,
+                        "pc_release_date": game.pc_release_date,
+                        "days_left": days_left,
+                        "member_count": wishers.len(),
+                    }),
+                );
+                let event_key = format!(
+                    "{}:{}:{}:{}:{}",
+                    webhooks::EVENT_GROUP_RELEASE_REMINDER,
+                    group.id,
+                    game.igdb_id,
+                    game.pc_release_date,
+                    lead_time
+                );
+
+                delivery_ids.extend(queue_deliveries(
+                    db_conn,
+                    *member_id,
+                    webhooks::EVENT_GROUP_RELEASE_REMINDER,
+                    &event_key,
+                    payload,
+                )?);
+            }
+        }
+    }
+
+    Ok(delivery_ids)
+}
+
+#[celery::task]
+pub async fn whats_for_tomorrow() -> TaskResult<bool> {
+    let delivery_ids = queue_release_reminders().map_err(map_unexpected)?;
+    if delivery_ids.is_empty() {
+        return Ok(true);
+    }
+
+    let celery_app = get_celery_app().await.map_err(map_unexpected)?;
+    for delivery_id in delivery_ids {
+        celery_app
+            .send_task(deliver_webhook::new(delivery_id))
+            .await
+            .map_err(map_unexpected)?;
+    }
+    celery_app.close().await.map_err(map_unexpected)?;
 
     Ok(true)
 }
 
+/// Failed attempts are retried by celery with an exponential backoff between
+/// `min_retry_delay` and `max_retry_delay` seconds.
+#[celery::task(max_retries = 5, min_retry_delay = 30, max_retry_delay = 3600)]
+pub fn deliver_webhook(delivery_id: Uuid) -> TaskResult<bool> {
+    let db_conn = db::establish_connection();
+
+    let delivery = match db::get_webhook_delivery(&db_conn, delivery_id).map_err(map_unexpected)? {
+        Some(delivery) => delivery,
+        // Webhook was removed since the delivery was queued
+        None => return Ok(false),
+    };
+    if delivery.status == webhooks::STATUS_DELIVERED {
+        return Ok(true);
+    }
+
+    let webhook = match db::get_webhook(&db_conn, delivery.webhook_id).map_err(map_unexpected)? {
+        Some(webhook) => webhook,
+        None => return Ok(false),
+    };
+
+    // The user may have turned webhooks off since the delivery was queued. Tests were asked
+    // for explicitly and go out anyway.
+    let usr_settings = db::get_user_settings(&db_conn, webhook.user_id)
+        .map_err(map_unexpected)?
+        .unwrap_or_else(|| settings::default_settings(webhook.user_id));
+    if delivery.event != webhooks::EVENT_TEST && !usr_settings.has_channel(settings::CHANNEL_WEBHOOK)
+    {
+        return Ok(false);
+    }
+
+    let delivery = webhooks::attempt_delivery(&db_conn, &webhook, &delivery).map_err(map_unexpected)?;
+    if delivery.status != webhooks::STATUS_DELIVERED {
+        return Err(TaskError::ExpectedError(
+            delivery.last_error.unwrap_or_default(),
+        ));
+    }
+
+    Ok(true)
+}
+
+/// Pulls the latest info from IGDB for unreleased games, in case a release date moved.
+/// Every change is recorded so the weekly digest can report it.
+#[celery::task]
+pub fn refresh_release_dates() -> TaskResult<bool> {
+    let db_conn = db::establish_connection();
+    let igdb_client = IGDB::new().map_err(map_unexpected)?;
+
+    let wished_games = db::get_unreleased_wishlist_games(&db_conn).map_err(map_unexpected)?;
+
+    let mut igdb_ids: Vec<i32> = wished_games.iter().map(|game| game.igdb_id).collect();
+    igdb_ids.sort_unstable();
+    igdb_ids.dedup();
+
+    let igdb_games: HashMap<i32, IGDBGame> = igdb_client
+        .get_games_by_ids(&igdb_ids)
+        .map_err(map_unexpected)?
+        .into_iter()
+        .map(|game| (game.id, game))
+        .collect();
+
+    for game in wished_games.iter() {
+        let igdb_game = match igdb_games.get(&game.igdb_id) {
+            Some(igdb_game) => igdb_game,
+            None => continue,
+        };
+
+        let new_release_date = igdb_game.get_pc_release_date();
+        let platforms = igdb_game.get_platform_slugs();
+        let release_changed = new_release_date != game.pc_release_date;
+        if !release_changed
+            && igdb_game.total_rating == game.total_rating
+            && platforms == game.platform_slugs
+        {
+            continue;
+        }
+
+        let igdb_info = serde_json::to_value(igdb_game).map_err(map_unexpected)?;
+        db::update_wished_game_release(
+            &db_conn,
+            game.id,
+            &igdb_info,
+            new_release_date,
+            igdb_game.total_rating,
+            &platforms,
+        )
+        .map_err(map_unexpected)?;
+        if !release_changed {
+            continue;
+        }
+
+        log::info!(
+            "{} moved from {} to {}",
+            game.title,
+            game.pc_release_date,
+            new_release_date
+        );
+        db::add_release_date_change(
+            &db_conn,
+            &ReleaseDateChange {
+                id: Uuid::new_v4(),
+                wished_game_id: game.id,
+                user_id: game.user_id,
+                title: game.title.to_owned(),
+                old_release_date: game.pc_release_date,
+                new_release_date,
+                changed_on: Utc::now().naive_utc(),
+            },
+        )
+        .map_err(map_unexpected)?;
+    }
+
+    Ok(true)
+}
+
+/// Emails every user who turned on the weekly digest a summary of their wishlist since
+/// their last digest. Users with nothing to report are skipped.
+#[celery::task]
+pub fn send_weekly_digests() -> TaskResult<bool> {
+    let db_conn = db::establish_connection();
+    let mailer = Mailer::new().map_err(map_unexpected)?;
+    let templates = mailer::load_templates().map_err(map_unexpected)?;
+
+    let now_dt = Utc::now();
+    let users = db::get_all_users(&db_conn).map_err(map_unexpected)?;
+    // The digest still goes out when the deals can't be looked up, just without them
+    let mut deals = match Deals::new() {
+        Ok(deals) => Some(deals),
+        Err(error) => {
+            log::warn!("Sending digests without deals! {}", error);
+            None
+        }
+    };
+
+    for user in users.iter() {
+        let usr_settings = db::get_user_settings(&db_conn, user.id)
+            .map_err(map_unexpected)?
+            .unwrap_or_else(|| settings::default_settings(user.id));
+        if !usr_settings.weekly_digest || !usr_settings.has_channel(settings::CHANNEL_EMAIL) {
+            continue;
+        }
+
+        let since = db::get_last_digest_time(&db_conn, user.id)
+            .map_err(map_unexpected)?
+            .unwrap_or_else(|| (now_dt - Duration::days(7)).naive_utc());
+
+        let sent = digest::build_digest(&db_conn, user, &usr_settings, deals.as_mut(), since, now_dt)
+            .and_then(|weekly_digest| {
+                if weekly_digest.is_empty() {
+                    return Ok(false);
+                }
+
+                let (text_body, html_body) = digest::render_digest(&templates, &weekly_digest)?;
+                mailer.send(&user.email, "Your week in Playday", text_body, html_body)?;
+
+                Ok(true)
+            });
+
+        match sent {
+            Ok(true) => {
+                db::add_digest(
+                    &db_conn,
+                    &Digest {
+                        id: Uuid::new_v4(),
+                        user_id: user.id,
+                        sent_on: now_dt.naive_utc(),
+                    },
+                )
+                .map_err(map_unexpected)?;
+            }
+            Ok(false) => log::info!("Nothing to send in digest for {}", user.id),
+            // One bad address shouldn't stop everybody else's digest
+            Err(error) => log::error!("Error sending digest to {}! {}", user.id, error),
+        }
+    }
+
+    Ok(true)
+}
+
+/// Looks the rows of an import up on IGDB and leaves the report for the user to review,
+/// nothing is added to the wishlist here.
+#[celery::task]
+pub fn resolve_import(import_id: Uuid) -> TaskResult<bool> {
+    let db_conn = db::establish_connection();
+
+    let import = match db::get_import_by_id(&db_conn, import_id).map_err(map_unexpected)? {
+        Some(import) => import,
+        None => return Ok(false),
+    };
+    // Redelivered messages find the import already picked up
+    if !db::change_import_status(
+        &db_conn,
+        import_id,
+        imports::STATUS_PENDING,
+        imports::STATUS_RESOLVING,
+    )
+    .map_err(map_unexpected)?
+    {
+        return Ok(false);
+    }
+
+    let resolved = serde_json::from_value::<Vec<ImportRow>>(import.input_rows)
+        .map_err(anyhow::Error::from)
+        .and_then(|rows| {
+            let igdb_client = IGDB::new()?;
+            imports::resolve_rows(&igdb_client, &rows)
+        })
+        .and_then(|results| Ok(serde_json::to_value(&results)?));
+
+    match resolved {
+        Ok(report) => {
+            db::save_import_report(&db_conn, import_id, imports::STATUS_REVIEW, &report)
+                .map_err(map_unexpected)?;
+            Ok(true)
+        }
+        Err(error) => {
+            log::error!("Error resolving import {}! {}", import_id, error);
+            db::fail_import(
+                &db_conn,
+                import_id,
+                imports::STATUS_FAILED,
+                "The games could not be looked up on IGDB, try again later",
+            )
+            .map_err(map_unexpected)?;
+            Ok(false)
+        }
+    }
+}
+
+/// Runs a library sync queued by the web app, keeping its `sync_runs` row up to date so
+/// the user can follow along.
 #[celery::task]
-pub async fn sync_epicgames_library(usr_id: Uuid) -> TaskResult<bool> {
+pub fn sync_epicgames_library(run_id: Uuid) -> TaskResult<bool> {
+    let db_conn = db::establish_connection();
+
+    let run = match db::get_sync_run_by_id(&db_conn, run_id).map_err(map_unexpected)? {
+        Some(run) => run,
+        None => return Ok(false),
+    };
+    // Redelivered messages find the run already picked up
+    if !db::start_sync_run(
+        &db_conn,
+        run_id,
+        library::STATUS_QUEUED,
+        library::STATUS_RUNNING,
+    )
+    .map_err(map_unexpected)?
+    {
+        return Ok(false);
+    }
+
+    let store_token = db::get_game_store_token(&db_conn, run.user_id, library::STORE_EPICGAMES)
+        .map_err(map_unexpected)?;
+    let synced = match store_token {
+        Some(store_token) => sync_epicgames_games(&db_conn, &run, store_token),
+        None => {
+            db::finish_sync_run(
+                &db_conn,
+                run_id,
+                library::STATUS_FAILED,
+                Some("The Epic Games account is not connected"),
+            )
+            .map_err(map_unexpected)?;
+            return Ok(false);
+        }
+    };
+
+    match synced {
+        Ok(()) => {
+            db::finish_sync_run(&db_conn, run_id, library::STATUS_SUCCEEDED, None)
+                .map_err(map_unexpected)?;
+            Ok(true)
+        }
+        Err(error) => {
+            log::error!("Error syncing library for run {}! {}", run_id, error);
+            db::finish_sync_run(
+                &db_conn,
+                run_id,
+                library::STATUS_FAILED,
+                Some("The Epic Games library could not be synced, try again later"),
+            )
+            .map_err(map_unexpected)?;
+            Ok(false)
+        }
+    }
+}
+
+/// Adds the games of the Epic Games library that IGDB knows by their exact title to the
+/// wishlist as owned, in the backlog. Games already on the wishlist are left as they are.
+/// The run is updated after every item so the user can follow along.
+fn sync_epicgames_games(
+    db_conn: &PgConnection,
+    run: &SyncRun,
+    store_token: serde_json::Value,
+) -> Result<()> {
+    let epic_games = EpicGames::new()?;
+    let mut token: EpicGamesToken = serde_json::from_value(store_token)?;
+    if token.is_expired(Utc::now()) {
+        token = epic_games.refresh_login_tokens(&token.refresh_token)?;
+        db::update_game_store_token(
+            db_conn,
+            run.user_id,
+            library::STORE_EPICGAMES,
+            &serde_json::to_value(&token)?,
+        )?;
+    }
+
+    let items = epic_games.get_library(&token)?;
+    let items_total = items.len() as i32;
+    let mut items_imported = 0;
+    db::update_sync_run_progress(db_conn, run.id, Some(items_total), items_imported)?;
+
+    let igdb_client = IGDB::new()?;
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            thread::sleep(StdDuration::from_millis(imports::SEARCH_INTERVAL_MS));
+        }
+
+        // One item IGDB or the catalog trips over shouldn't end the whole sync
+        match sync_library_item(db_conn, &epic_games, &igdb_client, &token, run.user_id, item) {
+            Ok(true) => items_imported += 1,
+            Ok(false) => {}
+            Err(error) => log::warn!("Could not sync {} for run {}: {}", item.app_name, run.id, error),
+        }
+
+        db::update_sync_run_progress(db_conn, run.id, Some(items_total), items_imported)?;
+    }
+
+    Ok(())
+}
+
+/// Returns whether the item was added to the wishlist.
+fn sync_library_item(
+    db_conn: &PgConnection,
+    epic_games: &EpicGames,
+    igdb_client: &IGDB,
+    token: &EpicGamesToken,
+    usr_id: Uuid,
+    item: &LibraryItem,
+) -> Result<bool> {
+    let title = match epic_games.get_item_title(token, item)? {
+        Some(title) => title,
+        None => return Ok(false),
+    };
+
+    let found = igdb_client.search_games(&title)?;
+    let igdb_game = match imports::match_title(&title, None, None, &found) {
+        RowOutcome::Matched { game } => match found.iter().find(|found_game| found_game.id == game.igdb_id) {
+            Some(igdb_game) => igdb_game,
+            None => return Ok(false),
+        },
+        // Only certain matches are added, there is nobody to pick from the candidates
+        RowOutcome::Ambiguous { .. } | RowOutcome::Failed { .. } => return Ok(false),
+    };
+    if !db::get_wished_igdb_ids(db_conn, usr_id, &[igdb_game.id])?.is_empty() {
+        return Ok(false);
+    }
+
+    let mut game = wishlist::new_wished_game(usr_id, igdb_game)?;
+    game.status = GameStatus::Backlog.as_str().to_string();
+    db::add_games_to_wishlist(db_conn, &vec![game])?;
+
     Ok(true)
 }
\ No newline at end of file
@@ -0,0 +1,314 @@
+use std::net::{IpAddr, SocketAddr};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use diesel::prelude::PgConnection;
+use hmac::{Hmac, Mac, NewMac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::db;
+use crate::models::{Webhook, WebhookDelivery};
+
+pub const EVENT_RELEASE_REMINDER: &str = "release_reminder";
+pub const EVENT_GROUP_RELEASE_REMINDER: &str = "group_release_reminder";
+pub const EVENT_TEST: &str = "test";
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_DELIVERED: &str = "delivered";
+pub const STATUS_FAILED: &str = "failed";
+
+const SIGNATURE_HEADER: &str = "X-Playday-Signature";
+const EVENT_HEADER: &str = "X-Playday-Event";
+const DELIVERY_HEADER: &str = "X-Playday-Delivery";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat {
+    Generic,
+    Discord,
+    Slack,
+}
+
+impl PayloadFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayloadFormat::Generic => "generic",
+            PayloadFormat::Discord => "discord",
+            PayloadFormat::Slack => "slack",
+        }
+    }
+
+    pub fn parse(format: &str) -> Option<PayloadFormat> {
+        match format {
+            "generic" => Some(PayloadFormat::Generic),
+            "discord" => Some(PayloadFormat::Discord),
+            "slack" => Some(PayloadFormat::Slack),
+            _ => None,
+        }
+    }
+}
+
+pub fn generate_secret() -> String {
+    format!(
+        "{}{}",
+        Uuid::new_v4().to_simple(),
+        Uuid::new_v4().to_simple()
+    )
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || octets[0] == 0
+                // Carrier grade NAT, 100.64.0.0/10
+                || (octets[0] == 100 && octets[1] & 0xc0 == 64)
+                // IETF protocol assignments, 192.0.0.0/24
+                || (octets[0] == 192 && octets[1] == 0 && octets[2] == 0)
+                // Reserved, 240.0.0.0/4
+                || octets[0] >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ipv4) = ip.to_ipv4() {
+                return is_public_ip(IpAddr::V4(ipv4));
+            }
+            let first_segment = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, fc00::/7
+                || first_segment & 0xfe00 == 0xfc00
+                // Link local, fe80::/10
+                || first_segment & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+/// Parses the URL and resolves its host. Webhooks are sent from inside our network, every
+/// address the host resolves to has to be public so they can't reach it.
+pub fn resolve_url(hook_url: &str) -> Result<(url::Url, Vec<SocketAddr>)> {
+    let parsed = url::Url::parse(hook_url)?;
+    if parsed.scheme() != "https" && parsed.scheme() != "http" {
+        bail!("Webhook URL must be an http(s) URL");
+    }
+
+    let addrs = parsed.socket_addrs(|| None)?;
+    if addrs.is_empty() {
+        bail!("Webhook host does not resolve to any address");
+    }
+    if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+        bail!("Webhook host must resolve to public addresses only");
+    }
+
+    Ok((parsed, addrs))
+}
+
+/// Builds the payload we keep in `webhook_deliveries`. It is format agnostic,
+/// the Discord/Slack shape is derived from it when the delivery is sent.
+pub fn build_payload(event: &str, message: &str, data: serde_json::Value) -> serde_json::Value {
+    json!({
+        "event": event,
+        "message": message,
+        "data": data,
+    })
+}
+
+fn format_payload(format: PayloadFormat, payload: &serde_json::Value) -> serde_json::Value {
+    let message = payload["message"].as_str().unwrap_or_default();
+
+    match format {
+        PayloadFormat::Generic => payload.clone(),
+        PayloadFormat::Discord => json!({ "content": message }),
+        PayloadFormat::Slack => json!({ "text": message }),
+    }
+}
+
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(body);
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub fn new_delivery(
+    webhook: &Webhook,
+    event: &str,
+    event_key: &str,
+    payload: serde_json::Value,
+) -> WebhookDelivery {
+    let now_utc = Utc::now().naive_utc();
+
+    WebhookDelivery {
+        id: Uuid::new_v4(),
+        webhook_id: webhook.id,
+        event: event.to_string(),
+        event_key: event_key.to_string(),
+        payload,
+        status: STATUS_PENDING.to_string(),
+        attempts: 0,
+        response_code: None,
+        last_error: None,
+        created_on: now_utc,
+        updated_on: now_utc,
+    }
+}
+
+fn send(webhook: &Webhook, delivery: &WebhookDelivery) -> Result<u16> {
+    let format = match PayloadFormat::parse(&webhook.payload_format) {
+        Some(format) => format,
+        None => bail!("Unknown payload format: {}", webhook.payload_format),
+    };
+    let body = serde_json::to_vec(&format_payload(format, &delivery.payload))?;
+    let signature = sign_payload(&webhook.secret, &body);
+
+    // Checked again on every delivery, the host may resolve elsewhere since it was saved
+    let (mut hook_url, addrs) = resolve_url(&webhook.url)?;
+    let host = match (hook_url.host_str(), hook_url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => bail!("Webhook URL has no host"),
+    };
+    // Connect to the checked address so a second lookup can't answer differently. That
+    // doesn't work for https, there the certificate check fails if the name moved.
+    if hook_url.scheme() == "http" {
+        hook_url
+            .set_ip_host(addrs[0].ip())
+            .map_err(|_| anyhow!("Could not connect to {}", addrs[0]))?;
+    }
+
+    let resp = reqwest::blocking::Client::builder()
+        // A redirect could point anywhere, it counts as a failed delivery
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?
+        .post(hook_url)
+        .header(http::header::HOST, host)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+        .header(EVENT_HEADER, delivery.event.as_str())
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .timeout(std::time::Duration::from_secs(10))
+        .body(body)
+        .send()?;
+
+    Ok(resp.status().as_u16())
+}
+
+/// Sends a delivery once and records the outcome. Returns the updated delivery,
+/// callers decide whether a failed attempt should be retried.
+pub fn attempt_delivery(
+    db_conn: &PgConnection,
+    webhook: &Webhook,
+    delivery: &WebhookDelivery,
+) -> Result<WebhookDelivery> {
+    let mut delivery = delivery.clone();
+    delivery.attempts += 1;
+    delivery.updated_on = Utc::now().naive_utc();
+
+    match send(webhook, &delivery) {
+        Ok(status) if (200..300).contains(&status) => {
+            delivery.status = STATUS_DELIVERED.to_string();
+            delivery.response_code = Some(status as i32);
+            delivery.last_error = None;
+        }
+        Ok(status) => {
+            log::error!(
+                "Webhook delivery {} failed with status {}!",
+                delivery.id,
+                status
+            );
+            delivery.status = STATUS_FAILED.to_string();
+            delivery.response_code = Some(status as i32);
+            delivery.last_error = Some(format!("Received error response status: {}", status));
+        }
+        Err(error) => {
+            log::error!("Webhook delivery {} failed! {}", delivery.id, error);
+            delivery.status = STATUS_FAILED.to_string();
+            delivery.response_code = None;
+            delivery.last_error = Some(error.to_string());
+        }
+    }
+
+    db::update_webhook_delivery(db_conn, &delivery)?;
+
+    Ok(delivery)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_public(ip: &str) -> bool {
+        is_public_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn public_addresses_are_allowed() {
+        assert!(is_public("8.8.8.8"));
+        assert!(is_public("100.128.0.1"));
+        assert!(is_public("192.0.1.1"));
+        assert!(is_public("2606:4700:4700::1111"));
+    }
+
+    #[test]
+    fn private_and_loopback_addresses_are_rejected() {
+        assert!(!is_public("10.0.0.1"));
+        assert!(!is_public("172.16.5.4"));
+        assert!(!is_public("192.168.1.1"));
+        assert!(!is_public("127.0.0.1"));
+        assert!(!is_public("0.0.0.0"));
+        assert!(!is_public("169.254.169.254"));
+        assert!(!is_public("255.255.255.255"));
+        assert!(!is_public("224.0.0.1"));
+        assert!(!is_public("240.0.0.1"));
+    }
+
+    #[test]
+    fn carrier_grade_nat_is_rejected() {
+        assert!(!is_public("100.64.0.1"));
+        assert!(!is_public("100.127.255.254"));
+    }
+
+    #[test]
+    fn ietf_protocol_assignments_are_rejected() {
+        assert!(!is_public("192.0.0.1"));
+        assert!(!is_public("192.0.0.170"));
+    }
+
+    #[test]
+    fn non_public_ipv6_addresses_are_rejected() {
+        assert!(!is_public("::1"));
+        assert!(!is_public("::"));
+        assert!(!is_public("fc00::1"));
+        assert!(!is_public("fd12:3456:789a::1"));
+        assert!(!is_public("fe80::1"));
+        assert!(!is_public("febf::1"));
+        assert!(!is_public("ff02::1"));
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_are_checked_as_ipv4() {
+        assert!(!is_public("::ffff:127.0.0.1"));
+        assert!(!is_public("::ffff:10.0.0.1"));
+        assert!(!is_public("::ffff:169.254.169.254"));
+        assert!(is_public("::ffff:8.8.8.8"));
+    }
+
+    #[test]
+    fn urls_must_be_http_and_resolve_to_public_addresses() {
+        assert!(resolve_url("ftp://8.8.8.8/hook").is_err());
+        assert!(resolve_url("not a url").is_err());
+        assert!(resolve_url("http://127.0.0.1:8080/hook").is_err());
+        assert!(resolve_url("http://[::1]/hook").is_err());
+        assert!(resolve_url("https://8.8.8.8/hook").is_ok());
+    }
+}
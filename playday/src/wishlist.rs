@@ -0,0 +1,744 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use uuid::Uuid;
+
+use crate::igdb::IGDBGame;
+use crate::models::WishedGame;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 100;
+/// Games per `POST /api/wishlist`, they are all looked up on IGDB while the client waits.
+pub const MAX_NEW_GAMES: usize = 100;
+
+/// Games without a rating sort below every rated game.
+pub const MISSING_RATING: f64 = -1.0;
+/// Same for the personal rating, which goes from 1 to 10.
+pub const MISSING_PERSONAL_RATING: i16 = 0;
+/// And games without a max price sort below the free ones.
+pub const MISSING_MAX_PRICE: i32 = -1;
+
+pub const MIN_PRIORITY: i16 = 1;
+pub const MAX_PRIORITY: i16 = 5;
+pub const DEFAULT_PRIORITY: i16 = 3;
+const MAX_NOTES_LENGTH: usize = 10_000;
+const MAX_NAME_LENGTH: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WishlistSort {
+    ReleaseDate,
+    AddedOn,
+    Title,
+    Rating,
+    Priority,
+    PersonalRating,
+    MaxPrice,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Where a game is in its life, from wished for to played. Only games still on the
+/// wishlist get release notifications, every other status means the game is owned.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameStatus {
+    Wishlist,
+    Backlog,
+    Playing,
+    Completed,
+    Dropped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseStatus {
+    Released,
+    Unreleased,
+    Tbd,
+}
+
+/// Query parameters of `GET /api/wishlist`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WishlistParams {
+    pub sort: Option<WishlistSort>,
+    pub order: Option<SortOrder>,
+    pub status: Option<GameStatus>,
+    pub release: Option<ReleaseStatus>,
+    pub owned: Option<bool>,
+    pub platform: Option<String>,
+    pub list: Option<Uuid>,
+    pub tag: Option<Uuid>,
+    pub min_priority: Option<i16>,
+    pub min_personal_rating: Option<i16>,
+    pub q: Option<String>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Value of the sort column for the last game of a page.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SortKey {
+    ReleaseDate(i64),
+    AddedOn(NaiveDateTime),
+    Title(String),
+    Rating(f64),
+    Priority(i16),
+    PersonalRating(i16),
+    MaxPrice(i32),
+}
+
+/// Where the next page starts. Ties on the sort key are broken by id, so the position
+/// stays stable while games are added or removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WishlistCursor {
+    pub key: SortKey,
+    pub id: Uuid,
+}
+
+#[derive(Debug, Clone)]
+pub struct WishlistQuery {
+    pub sort: WishlistSort,
+    pub descending: bool,
+    pub status: Option<GameStatus>,
+    pub release: Option<ReleaseStatus>,
+    pub owned: Option<bool>,
+    pub platform: Option<String>,
+    pub list: Option<Uuid>,
+    pub tag: Option<Uuid>,
+    pub min_priority: Option<i16>,
+    pub min_personal_rating: Option<i16>,
+    pub search: Option<String>,
+    pub after: Option<WishlistCursor>,
+    pub limit: i64,
+}
+
+/// A game of the wishlist along with the lists and tags it is in.
+#[derive(Debug, Serialize)]
+pub struct WishlistEntry {
+    #[serde(flatten)]
+    pub game: WishedGame,
+    pub list_ids: Vec<Uuid>,
+    pub tag_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WishlistPage {
+    pub games: Vec<WishlistEntry>,
+    pub next_cursor: Option<String>,
+}
+
+/// Body of `PATCH /api/wishlist/{game_id}`, fields left out stay as they are and the
+/// nullable ones are cleared with an explicit `null`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WishedGameChanges {
+    pub status: Option<GameStatus>,
+    pub priority: Option<i16>,
+    pub notes: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub personal_rating: Option<Option<i16>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub max_price_cents: Option<Option<i32>>,
+}
+
+/// One game of `POST /api/wishlist`. Everything about the game comes from IGDB, the client
+/// only picks it by id and may set the personal fields.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewWishedGame {
+    pub igdb_id: i32,
+    pub status: Option<GameStatus>,
+    pub priority: Option<i16>,
+    pub notes: Option<String>,
+    pub personal_rating: Option<i16>,
+    pub max_price_cents: Option<i32>,
+}
+
+/// What became of each game of `POST /api/wishlist`, in the order they were sent.
+#[derive(Debug, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum AddedGame {
+    Added(WishedGame),
+    AlreadyWished { igdb_id: i32 },
+    UnknownGame { igdb_id: i32 },
+    Invalid { igdb_id: i32, message: String },
+}
+
+/// Tells a missing field (`None`) apart from a `null` one (`Some(None)`).
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl GameStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameStatus::Wishlist => "wishlist",
+            GameStatus::Backlog => "backlog",
+            GameStatus::Playing => "playing",
+            GameStatus::Completed => "completed",
+            GameStatus::Dropped => "dropped",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<GameStatus> {
+        match status {
+            "wishlist" => Some(GameStatus::Wishlist),
+            "backlog" => Some(GameStatus::Backlog),
+            "playing" => Some(GameStatus::Playing),
+            "completed" => Some(GameStatus::Completed),
+            "dropped" => Some(GameStatus::Dropped),
+            _ => None,
+        }
+    }
+
+    /// Bought games go to the backlog or straight to playing, finished ones can be
+    /// replayed and dropped ones picked up again.
+    pub fn can_change_to(&self, next: GameStatus) -> bool {
+        use GameStatus::*;
+
+        matches!(
+            (self, next),
+            (Wishlist, Backlog)
+                | (Wishlist, Playing)
+                | (Wishlist, Dropped)
+                | (Backlog, Wishlist)
+                | (Backlog, Playing)
+                | (Backlog, Dropped)
+                | (Playing, Backlog)
+                | (Playing, Completed)
+                | (Playing, Dropped)
+                | (Completed, Playing)
+                | (Dropped, Backlog)
+                | (Dropped, Playing)
+        )
+    }
+}
+
+impl WishlistSort {
+    /// Earliest release and A to Z first, for everything else the most or best first.
+    fn default_descending(&self) -> bool {
+        match self {
+            WishlistSort::ReleaseDate | WishlistSort::Title => false,
+            WishlistSort::AddedOn
+            | WishlistSort::Rating
+            | WishlistSort::Priority
+            | WishlistSort::PersonalRating
+            | WishlistSort::MaxPrice => true,
+        }
+    }
+
+    pub fn key(&self, game: &WishedGame) -> SortKey {
+        match self {
+            WishlistSort::ReleaseDate => SortKey::ReleaseDate(game.pc_release_date),
+            WishlistSort::AddedOn => SortKey::AddedOn(game.added_on),
+            WishlistSort::Title => SortKey::Title(game.title.to_lowercase()),
+            WishlistSort::Rating => SortKey::Rating(game.total_rating.unwrap_or(MISSING_RATING)),
+            WishlistSort::Priority => SortKey::Priority(game.priority),
+            WishlistSort::PersonalRating => {
+                SortKey::PersonalRating(game.personal_rating.unwrap_or(MISSING_PERSONAL_RATING))
+            }
+            WishlistSort::MaxPrice => {
+                SortKey::MaxPrice(game.max_price_cents.unwrap_or(MISSING_MAX_PRICE))
+            }
+        }
+    }
+
+    fn matches(&self, key: &SortKey) -> bool {
+        matches!(
+            (self, key),
+            (WishlistSort::ReleaseDate, SortKey::ReleaseDate(_))
+                | (WishlistSort::AddedOn, SortKey::AddedOn(_))
+                | (WishlistSort::Title, SortKey::Title(_))
+                | (WishlistSort::Rating, SortKey::Rating(_))
+                | (WishlistSort::Priority, SortKey::Priority(_))
+                | (WishlistSort::PersonalRating, SortKey::PersonalRating(_))
+                | (WishlistSort::MaxPrice, SortKey::MaxPrice(_))
+        )
+    }
+}
+
+impl WishlistCursor {
+    /// Cursors are opaque to clients, base64 of the JSON keeps them URL safe.
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("Cursor is always serializable");
+        base64::encode_config(json, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(cursor: &str) -> Option<WishlistCursor> {
+        let json = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+/// Validates the query parameters and fills in the defaults.
+pub fn parse_params(params: WishlistParams) -> Result<WishlistQuery, String> {
+    let sort = params.sort.unwrap_or(WishlistSort::AddedOn);
+    let descending = match params.order {
+        Some(order) => order == SortOrder::Desc,
+        None => sort.default_descending(),
+    };
+
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(format!("limit must be between 1 and {}", MAX_PAGE_SIZE));
+    }
+
+    if let Some(min_priority) = params.min_priority {
+        validate_priority(min_priority)?;
+    }
+    if let Some(min_personal_rating) = params.min_personal_rating {
+        validate_personal_rating(min_personal_rating)?;
+    }
+
+    let after = match params.cursor {
+        Some(cursor) => {
+            let cursor = WishlistCursor::decode(&cursor).ok_or("Invalid cursor")?;
+            // A cursor from one sort means nothing in another
+            if !sort.matches(&cursor.key) {
+                return Err("The cursor belongs to a different sort".to_string());
+            }
+            Some(cursor)
+        }
+        None => None,
+    };
+
+    let non_empty = |value: Option<String>| {
+        value
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    Ok(WishlistQuery {
+        sort,
+        descending,
+        status: params.status,
+        release: params.release,
+        owned: params.owned,
+        platform: non_empty(params.platform),
+        list: params.list,
+        tag: params.tag,
+        min_priority: params.min_priority,
+        min_personal_rating: params.min_personal_rating,
+        search: non_empty(params.q),
+        after,
+        limit,
+    })
+}
+
+/// Builds the page from up to `limit + 1` games, the extra one only tells us there is more.
+/// `game_lists` and `game_tags` are `(wished_game_id, list_id/tag_id)` pairs.
+pub fn into_page(
+    query: &WishlistQuery,
+    mut games: Vec<WishedGame>,
+    game_lists: &[(Uuid, Uuid)],
+    game_tags: &[(Uuid, Uuid)],
+) -> WishlistPage {
+    let has_more = games.len() as i64 > query.limit;
+    games.truncate(query.limit as usize);
+
+    let next_cursor = match games.last() {
+        Some(last) if has_more => Some(
+            WishlistCursor {
+                key: query.sort.key(last),
+                id: last.id,
+            }
+            .encode(),
+        ),
+        _ => None,
+    };
+
+    let ids_of = |game_id: Uuid, pairs: &[(Uuid, Uuid)]| -> Vec<Uuid> {
+        pairs
+            .iter()
+            .filter(|(wished_game_id, _)| *wished_game_id == game_id)
+            .map(|(_, id)| *id)
+            .collect()
+    };
+    let games = games
+        .into_iter()
+        .map(|game| WishlistEntry {
+            list_ids: ids_of(game.id, game_lists),
+            tag_ids: ids_of(game.id, game_tags),
+            game,
+        })
+        .collect();
+
+    WishlistPage { games, next_cursor }
+}
+
+/// List and tag names are trimmed and must not be empty.
+pub fn normalize_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("name can not be empty".to_string());
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "name can be at most {} characters long",
+            MAX_NAME_LENGTH
+        ));
+    }
+
+    Ok(name.to_string())
+}
+
+/// A new wishlist entry with the defaults, from what IGDB has on the game.
+pub fn new_wished_game(usr_id: Uuid, game: &IGDBGame) -> Result<WishedGame, serde_json::Error> {
+    Ok(WishedGame {
+        id: Uuid::new_v4(),
+        user_id: usr_id,
+        title: game.name.to_owned(),
+        igdb_id: game.id,
+        added_on: Utc::now().naive_utc(),
+        igdb_info: serde_json::to_value(game)?,
+        pc_release_date: game.get_pc_release_date(),
+        total_rating: game.total_rating,
+        platform_slugs: game.get_platform_slugs(),
+        status: GameStatus::Wishlist.as_str().to_string(),
+        started_on: None,
+        finished_on: None,
+        priority: DEFAULT_PRIORITY,
+        notes: String::new(),
+        personal_rating: None,
+        max_price_cents: None,
+    })
+}
+
+/// Builds the entries of `POST /api/wishlist`. `igdb_games` are the requested games IGDB
+/// knows, `wished_ids` the IGDB ids already on the wishlist.
+pub fn new_wished_games(
+    usr_id: Uuid,
+    new_games: Vec<NewWishedGame>,
+    igdb_games: &HashMap<i32, IGDBGame>,
+    wished_ids: &HashSet<i32>,
+) -> Vec<AddedGame> {
+    let now = Utc::now().naive_utc();
+    let mut seen: HashSet<i32> = HashSet::new();
+
+    new_games
+        .into_iter()
+        .map(|new_game| {
+            let igdb_id = new_game.igdb_id;
+            if wished_ids.contains(&igdb_id) || !seen.insert(igdb_id) {
+                return AddedGame::AlreadyWished { igdb_id };
+            }
+            let igdb_game = match igdb_games.get(&igdb_id) {
+                Some(igdb_game) => igdb_game,
+                None => return AddedGame::UnknownGame { igdb_id },
+            };
+
+            let changes = WishedGameChanges {
+                status: new_game.status,
+                priority: new_game.priority,
+                notes: new_game.notes,
+                personal_rating: Some(new_game.personal_rating),
+                max_price_cents: Some(new_game.max_price_cents),
+            };
+            let game = new_wished_game(usr_id, igdb_game)
+                .map_err(|error| error.to_string())
+                .and_then(|mut game| {
+                    apply_changes(&mut game, changes, now)?;
+                    Ok(game)
+                });
+
+            match game {
+                Ok(game) => AddedGame::Added(game),
+                Err(message) => AddedGame::Invalid { igdb_id, message },
+            }
+        })
+        .collect()
+}
+
+/// Moves the game to `next`, keeping `started_on` and `finished_on` in line with it.
+/// Setting the status it already has changes nothing.
+pub fn change_status(
+    game: &mut WishedGame,
+    next: GameStatus,
+    now: NaiveDateTime,
+) -> Result<(), String> {
+    let current = GameStatus::parse(&game.status).unwrap_or(GameStatus::Wishlist);
+    if current == next {
+        return Ok(());
+    }
+    if !current.can_change_to(next) {
+        return Err(format!(
+            "A game can not go from {} to {}",
+            current.as_str(),
+            next.as_str()
+        ));
+    }
+
+    match next {
+        GameStatus::Wishlist | GameStatus::Backlog => {
+            game.started_on = None;
+            game.finished_on = None;
+        }
+        GameStatus::Playing => {
+            game.started_on = Some(now);
+            game.finished_on = None;
+        }
+        GameStatus::Completed | GameStatus::Dropped => game.finished_on = Some(now),
+    }
+    game.status = next.as_str().to_string();
+
+    Ok(())
+}
+
+fn validate_priority(priority: i16) -> Result<(), String> {
+    if !(MIN_PRIORITY..=MAX_PRIORITY).contains(&priority) {
+        return Err(format!(
+            "priority must be between {} and {}",
+            MIN_PRIORITY, MAX_PRIORITY
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_personal_rating(rating: i16) -> Result<(), String> {
+    if !(1..=10).contains(&rating) {
+        return Err("personal_rating must be between 1 and 10".to_string());
+    }
+
+    Ok(())
+}
+
+/// Applies the changes of a `PATCH`, checking each of them.
+pub fn apply_changes(
+    game: &mut WishedGame,
+    changes: WishedGameChanges,
+    now: NaiveDateTime,
+) -> Result<(), String> {
+    if let Some(status) = changes.status {
+        change_status(game, status, now)?;
+    }
+    if let Some(priority) = changes.priority {
+        validate_priority(priority)?;
+        game.priority = priority;
+    }
+    if let Some(notes) = changes.notes {
+        if notes.chars().count() > MAX_NOTES_LENGTH {
+            return Err(format!(
+                "notes can be at most {} characters long",
+                MAX_NOTES_LENGTH
+            ));
+        }
+        game.notes = notes;
+    }
+    if let Some(personal_rating) = changes.personal_rating {
+        if let Some(rating) = personal_rating {
+            validate_personal_rating(rating)?;
+        }
+        game.personal_rating = personal_rating;
+    }
+    if let Some(max_price_cents) = changes.max_price_cents {
+        if max_price_cents.map_or(false, |price| price < 0) {
+            return Err("max_price_cents can not be negative".to_string());
+        }
+        game.max_price_cents = max_price_cents;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn params(sort: Option<WishlistSort>, cursor: Option<String>) -> WishlistParams {
+        WishlistParams {
+            sort,
+            order: None,
+            status: None,
+            release: None,
+            owned: None,
+            platform: None,
+            list: None,
+            tag: None,
+            min_priority: None,
+            min_personal_rating: None,
+            q: None,
+            cursor,
+            limit: None,
+        }
+    }
+
+    fn game(title: &str) -> WishedGame {
+        WishedGame {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            igdb_id: 1,
+            igdb_info: serde_json::Value::Null,
+            added_on: NaiveDate::from_ymd(2022, 1, 1).and_hms(12, 0, 0),
+            user_id: Uuid::new_v4(),
+            pc_release_date: 0,
+            total_rating: None,
+            platform_slugs: Vec::new(),
+            status: GameStatus::Wishlist.as_str().to_string(),
+            started_on: None,
+            finished_on: None,
+            priority: DEFAULT_PRIORITY,
+            notes: String::new(),
+            personal_rating: None,
+            max_price_cents: None,
+        }
+    }
+
+    #[test]
+    fn cursors_survive_the_round_trip() {
+        let keys = vec![
+            SortKey::ReleaseDate(1_640_995_200),
+            SortKey::AddedOn(NaiveDate::from_ymd(2022, 1, 1).and_hms(12, 30, 0)),
+            SortKey::Title("Hades: Battle out of Hell?".to_string()),
+            SortKey::Rating(MISSING_RATING),
+            SortKey::Priority(MAX_PRIORITY),
+            SortKey::PersonalRating(MISSING_PERSONAL_RATING),
+            SortKey::MaxPrice(1999),
+        ];
+
+        for key in keys {
+            let cursor = WishlistCursor {
+                key: key.clone(),
+                id: Uuid::new_v4(),
+            };
+            let encoded = cursor.encode();
+            assert!(encoded
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+
+            let decoded = WishlistCursor::decode(&encoded).unwrap();
+            assert_eq!(decoded.key, key);
+            assert_eq!(decoded.id, cursor.id);
+        }
+    }
+
+    #[test]
+    fn rejects_made_up_cursors() {
+        assert!(WishlistCursor::decode("").is_none());
+        assert!(WishlistCursor::decode("not a cursor!").is_none());
+        let empty_json = base64::encode_config("{}", base64::URL_SAFE_NO_PAD);
+        assert!(WishlistCursor::decode(&empty_json).is_none());
+
+        let error = parse_params(params(None, Some("not a cursor!".to_string()))).unwrap_err();
+        assert_eq!(error, "Invalid cursor");
+    }
+
+    #[test]
+    fn cursors_only_work_with_their_sort() {
+        let cursor = WishlistCursor {
+            key: SortKey::Title("Hades".to_string()),
+            id: Uuid::new_v4(),
+        }
+        .encode();
+
+        assert!(parse_params(params(Some(WishlistSort::Title), Some(cursor.clone()))).is_ok());
+        assert_eq!(
+            parse_params(params(Some(WishlistSort::Rating), Some(cursor))).unwrap_err(),
+            "The cursor belongs to a different sort"
+        );
+    }
+
+    #[test]
+    fn pages_point_at_their_last_game() {
+        let query = parse_params(WishlistParams {
+            limit: Some(2),
+            ..params(Some(WishlistSort::Title), None)
+        })
+        .unwrap();
+        let games = vec![game("Celeste"), game("Hades"), game("Outer Wilds")];
+        let last_id = games[1].id;
+
+        let page = into_page(&query, games.clone(), &[], &[]);
+        assert_eq!(page.games.len(), 2);
+        let cursor = WishlistCursor::decode(&page.next_cursor.unwrap()).unwrap();
+        // Titles sort without case
+        assert_eq!(cursor.key, SortKey::Title("hades".to_string()));
+        assert_eq!(cursor.id, last_id);
+
+        // Without the extra game there is no next page
+        let page = into_page(&query, games[..2].to_vec(), &[], &[]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    fn at(hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2022, 1, 10).and_hms(hour, 0, 0)
+    }
+
+    fn with_status(status: GameStatus) -> WishedGame {
+        WishedGame {
+            status: status.as_str().to_string(),
+            ..game("Hades")
+        }
+    }
+
+    #[test]
+    fn playing_sets_the_start_and_finishing_the_end() {
+        let mut game = with_status(GameStatus::Wishlist);
+
+        change_status(&mut game, GameStatus::Playing, at(9)).unwrap();
+        assert_eq!(game.status, "playing");
+        assert_eq!(game.started_on, Some(at(9)));
+        assert_eq!(game.finished_on, None);
+
+        change_status(&mut game, GameStatus::Completed, at(18)).unwrap();
+        assert_eq!(game.status, "completed");
+        assert_eq!(game.started_on, Some(at(9)));
+        assert_eq!(game.finished_on, Some(at(18)));
+    }
+
+    #[test]
+    fn replaying_starts_over() {
+        let mut game = with_status(GameStatus::Completed);
+        game.started_on = Some(at(1));
+        game.finished_on = Some(at(2));
+
+        change_status(&mut game, GameStatus::Playing, at(9)).unwrap();
+        assert_eq!(game.started_on, Some(at(9)));
+        assert_eq!(game.finished_on, None);
+    }
+
+    #[test]
+    fn back_to_the_wishlist_clears_the_dates() {
+        let mut game = with_status(GameStatus::Playing);
+        game.started_on = Some(at(1));
+
+        change_status(&mut game, GameStatus::Backlog, at(9)).unwrap();
+        change_status(&mut game, GameStatus::Wishlist, at(10)).unwrap();
+        assert_eq!(game.status, "wishlist");
+        assert_eq!(game.started_on, None);
+        assert_eq!(game.finished_on, None);
+    }
+
+    #[test]
+    fn same_status_changes_nothing() {
+        let mut game = with_status(GameStatus::Playing);
+        game.started_on = Some(at(1));
+
+        change_status(&mut game, GameStatus::Playing, at(9)).unwrap();
+        assert_eq!(game.started_on, Some(at(1)));
+    }
+
+    #[test]
+    fn rejects_skipped_steps() {
+        let mut game = with_status(GameStatus::Wishlist);
+        assert_eq!(
+            change_status(&mut game, GameStatus::Completed, at(9)).unwrap_err(),
+            "A game can not go from wishlist to completed"
+        );
+        assert_eq!(game.status, "wishlist");
+        assert_eq!(game.finished_on, None);
+
+        let mut game = with_status(GameStatus::Completed);
+        assert!(change_status(&mut game, GameStatus::Wishlist, at(9)).is_err());
+    }
+}
@@ -1,31 +1,49 @@
-use anyhow::Result;
-use celery::beat::CronSchedule;
-use celery::broker::AMQPBroker;
+use anyhow::{bail, Context, Result};
 use dotenv::dotenv;
+use log::{info, warn};
 
-use playday::tasks::whats_for_tomorrow;
-
-const QUEUE_NAME: &str = "playday_celery";
+use playday::schedules;
+use playday::tasks;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
     env_logger::init();
 
-    // Build a `Beat` with a default scheduler backend.
-    let mut beat = celery::beat!(
-        broker = AMQPBroker { std::env::var("AMQP_ADDR").unwrap() },
-        tasks = [
-            "long_running" => {
-                whats_for_tomorrow,
-                schedule = CronSchedule::from_string("*/2 * * * *")?,
-                args = (),
-            }
-        ],
-        task_routes = [
-            "*" => QUEUE_NAME,
-        ],
-    ).await?;
+    let schedules_path = std::env::var("BEAT_SCHEDULES")
+        .unwrap_or_else(|_| schedules::DEFAULT_SCHEDULES_FILE.to_string());
+    let content = std::fs::read_to_string(&schedules_path)
+        .with_context(|| format!("Failed to read schedules from {}", schedules_path))?;
+    let schedules_file = schedules::parse_schedules(&content)
+        .with_context(|| format!("Failed to parse schedules in {}", schedules_path))?;
+
+    let beat_schedules = match schedules::validate_schedules(schedules_file.schedule) {
+        Ok(beat_schedules) => beat_schedules,
+        Err(errors) => bail!(
+            "Invalid schedules in {}:\n{}",
+            schedules_path,
+            errors.join("\n")
+        ),
+    };
+    if beat_schedules.is_empty() {
+        warn!("No enabled schedules in {}", schedules_path);
+    }
+
+    let mut beat = tasks::get_celery_beat().await?;
+
+    for beat_schedule in beat_schedules {
+        info!(
+            "Scheduling {} to run {}",
+            beat_schedule.name, beat_schedule.task
+        );
+        tasks::schedule_task(
+            &mut beat,
+            &beat_schedule.name,
+            &beat_schedule.task,
+            beat_schedule.args,
+            beat_schedule.schedule,
+        )?;
+    }
 
     beat.start().await?;
 
@@ -1,26 +1,17 @@
 use anyhow::Result;
-use celery::broker::AMQPBroker;
 use dotenv::dotenv;
 
-use playday::tasks::whats_for_tomorrow;
-
-const QUEUE_NAME: &str = "playday_celery";
+use playday::tasks;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
     env_logger::init();
 
-    let my_app = celery::app!(
-        broker = AMQPBroker { std::env::var("AMQP_ADDR").unwrap() },
-        tasks = [whats_for_tomorrow],
-        task_routes = [
-            "*" => QUEUE_NAME,
-        ],
-    ).await?;
+    let my_app = tasks::get_celery_app().await?;
 
     my_app.display_pretty().await;
-    my_app.consume_from(&[QUEUE_NAME]).await?;
+    my_app.consume_from(&[tasks::QUEUE_NAME]).await?;
 
     Ok(())
 }
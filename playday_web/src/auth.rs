@@ -0,0 +1,409 @@
+use std::net::IpAddr;
+use std::ops::Deref;
+use std::sync::Once;
+
+use actix_identity::{CookieIdentityPolicy, Identity, IdentityPolicy};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{AUTHORIZATION, USER_AGENT};
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest};
+use chrono::{Duration, NaiveDateTime, Utc};
+use futures::future::{ready, FutureExt, LocalBoxFuture, Ready};
+use uuid::Uuid;
+
+use crate::api_tokens;
+use crate::config::{ClientConfig, CookieConfig};
+use crate::db;
+use crate::errors::{ApiError, ErrorCode};
+use crate::models;
+use crate::types;
+
+/// Don't write `last_seen` or `last_used_at` on every request, once every few minutes is plenty.
+const LAST_SEEN_INTERVAL_MINUTES: i64 = 5;
+/// Sessions end after this long without a request.
+const SESSION_IDLE_DAYS: i64 = 14;
+/// And after this long in any case, so a stolen cookie doesn't work forever.
+const SESSION_MAX_AGE_DAYS: i64 = 90;
+
+/// How the request was authenticated.
+pub enum AuthMethod {
+    /// Browser session, the id of the row in `sessions`. Allowed to do everything.
+    Session(Uuid),
+    /// Personal API token, limited to its scopes.
+    ApiToken { scopes: Vec<String> },
+}
+
+/// The logged in user, reloaded from `users` on every request so deleted accounts
+/// are caught. Either the identity cookie carries the id of a row in `sessions`, or an
+/// `Authorization: Bearer` header carries a personal API token. Requests with neither
+/// are rejected with `401`.
+pub struct CurrentUser {
+    pub user: models::User,
+    pub auth: AuthMethod,
+}
+
+impl CurrentUser {
+    pub fn session_id(&self) -> Option<Uuid> {
+        match self.auth {
+            AuthMethod::Session(session_id) => Some(session_id),
+            AuthMethod::ApiToken { .. } => None,
+        }
+    }
+
+    /// Sessions pass every scope check, API tokens only the scopes they were created with.
+    pub fn require_scope(&self, scope: &str) -> Result<(), ApiError> {
+        match &self.auth {
+            AuthMethod::Session(_) => Ok(()),
+            AuthMethod::ApiToken { scopes } if scopes.iter().any(|s| s == scope) => Ok(()),
+            AuthMethod::ApiToken { .. } => Err(ApiError::new(
+                ErrorCode::InsufficientScope,
+                format!("This API token needs the {} scope", scope),
+            )),
+        }
+    }
+
+    /// For account management that API tokens must never reach, like creating more tokens.
+    pub fn require_session(&self) -> Result<Uuid, ApiError> {
+        self.session_id().ok_or_else(|| {
+            ApiError::new(
+                ErrorCode::InsufficientScope,
+                "This can only be done from a browser session",
+            )
+        })
+    }
+}
+
+impl Deref for CurrentUser {
+    type Target = models::User;
+
+    fn deref(&self) -> &models::User {
+        &self.user
+    }
+}
+
+fn invalid_session(id: &Identity) -> ApiError {
+    // Drop the cookie, otherwise every following request fails the same way
+    id.forget();
+    ApiError::unauthorized()
+}
+
+/// Sessions last seen before the first or created before the second have expired.
+pub fn session_cutoffs(now: NaiveDateTime) -> (NaiveDateTime, NaiveDateTime) {
+    (
+        now - Duration::days(SESSION_IDLE_DAYS),
+        now - Duration::days(SESSION_MAX_AGE_DAYS),
+    )
+}
+
+/// Builds a session row for the user logging in with this request. Callers save it and
+/// then store its id with `Identity::remember`.
+pub fn new_session(req: &HttpRequest, usr_id: Uuid) -> models::Session {
+    let now_utc = Utc::now().naive_utc();
+
+    models::Session {
+        id: Uuid::new_v4(),
+        user_id: usr_id,
+        created_at: now_utc,
+        last_seen: now_utc,
+        user_agent: req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|agent| agent.to_str().ok())
+            .map(|agent| agent.to_string()),
+        ip_address: client_ip(req).map(|ip| ip.to_string()),
+    }
+}
+
+static MISSING_PROXIES_WARNING: Once = Once::new();
+
+/// The address of the client. Anybody can send `X-Forwarded-For`, so it is only followed
+/// while the hop that added an entry is one of the trusted proxies.
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let mut ip = req.peer_addr()?.ip();
+    let trusted_proxies = match req.app_data::<web::Data<ClientConfig>>() {
+        Some(client_config) => &client_config.trusted_proxies,
+        None => return Some(ip),
+    };
+
+    let forwarded_for: Vec<&str> = req
+        .headers()
+        .get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    // Otherwise every client looks like the proxy and they all share its rate limits
+    if trusted_proxies.is_empty() && !forwarded_for.is_empty() {
+        MISSING_PROXIES_WARNING.call_once(|| {
+            log::warn!(
+                "Requests come through a proxy at {} but TRUSTED_PROXIES is not set, X-Forwarded-For is ignored",
+                ip
+            );
+        });
+    }
+
+    for entry in forwarded_for.iter().rev() {
+        if !trusted_proxies.iter().any(|proxy| proxy.contains(ip)) {
+            break;
+        }
+        match entry.trim().parse::<IpAddr>() {
+            Ok(forwarded_ip) => ip = forwarded_ip,
+            Err(_) => break,
+        }
+    }
+
+    Some(ip)
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<Result<String, ApiError>> {
+    let header = req.headers().get(AUTHORIZATION)?;
+    let token = header
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty());
+
+    Some(token.ok_or_else(invalid_token))
+}
+
+fn invalid_token() -> ApiError {
+    ApiError::new(
+        ErrorCode::InvalidToken,
+        "The API token is invalid or was revoked",
+    )
+}
+
+fn from_api_token(
+    pool: web::Data<types::DBPool>,
+    token: String,
+) -> LocalBoxFuture<'static, Result<CurrentUser, ApiError>> {
+    async move {
+        // use web::block to offload blocking Diesel code without blocking server thread
+        let user = web::block(move || -> Result<_, ApiError> {
+            let conn = pool.get()?;
+            let api_token = match db::get_api_token_by_hash(&conn, &api_tokens::hash_token(&token))?
+            {
+                Some(api_token) => api_token,
+                None => return Ok(None),
+            };
+
+            let last_used_threshold =
+                Utc::now().naive_utc() - Duration::minutes(LAST_SEEN_INTERVAL_MINUTES);
+            if api_token
+                .last_used_at
+                .map_or(true, |last_used| last_used < last_used_threshold)
+            {
+                db::update_api_token_last_used(&conn, api_token.id)?;
+            }
+
+            let user = db::get_user_by_id(&conn, api_token.user_id)?;
+            Ok(user.map(|user| (user, api_token)))
+        })
+        .await?;
+
+        match user {
+            Some((user, api_token)) => Ok(CurrentUser {
+                user,
+                auth: AuthMethod::ApiToken {
+                    scopes: api_token.scopes,
+                },
+            }),
+            None => Err(invalid_token()),
+        }
+    }
+    .boxed_local()
+}
+
+impl FromRequest for CurrentUser {
+    type Error = ApiError;
+    type Future = LocalBoxFuture<'static, Result<CurrentUser, ApiError>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let pool = match req.app_data::<web::Data<types::DBPool>>().cloned() {
+            Some(pool) => pool,
+            None => {
+                return ready(Err(ApiError::internal("DB pool is not configured"))).boxed_local()
+            }
+        };
+
+        // A bearer token wins over the cookie, scripts shouldn't pick up a browser session
+        match bearer_token(req) {
+            Some(Ok(token)) => return from_api_token(pool, token),
+            Some(Err(error)) => return ready(Err(error)).boxed_local(),
+            None => {}
+        }
+
+        let identity = Identity::from_request(req, payload);
+
+        async move {
+            let id = identity.await.map_err(ApiError::internal)?;
+
+            let session_info = id.identity().ok_or_else(ApiError::unauthorized)?;
+            // Cookies from before server side sessions carry the whole user, those fail here
+            let session_id = match Uuid::parse_str(&session_info) {
+                Ok(session_id) => session_id,
+                Err(error) => {
+                    log::warn!("Dropping malformed session cookie! {}", error);
+                    return Err(invalid_session(&id));
+                }
+            };
+
+            // use web::block to offload blocking Diesel code without blocking server thread
+            let user = web::block(move || -> Result<_, ApiError> {
+                let conn = pool.get()?;
+                let session = match db::get_session(&conn, session_id)? {
+                    Some(session) => session,
+                    None => return Ok(None),
+                };
+
+                let now_utc = Utc::now().naive_utc();
+                let (idle_cutoff, max_age_cutoff) = session_cutoffs(now_utc);
+                if session.last_seen < idle_cutoff || session.created_at < max_age_cutoff {
+                    db::remove_session(&conn, session.user_id, session.id)?;
+                    return Ok(None);
+                }
+
+                let last_seen_threshold = now_utc - Duration::minutes(LAST_SEEN_INTERVAL_MINUTES);
+                if session.last_seen < last_seen_threshold {
+                    db::update_session_last_seen(&conn, session.id)?;
+                }
+
+                Ok(db::get_user_by_id(&conn, session.user_id)?)
+            })
+            .await?;
+
+            match user {
+                Some(user) => Ok(CurrentUser {
+                    user,
+                    auth: AuthMethod::Session(session_id),
+                }),
+                None => Err(invalid_session(&id)),
+            }
+        }
+        .boxed_local()
+    }
+}
+
+/// Marks requests whose identity cookie was signed with a previous key.
+struct SignedWithPreviousKey;
+
+/// Identity cookie policy that signs with the current key but also accepts cookies
+/// signed with previous keys. Those get re-signed with the current key on the way out.
+pub struct RotatingCookieIdentityPolicy {
+    current: CookieIdentityPolicy,
+    previous: Vec<CookieIdentityPolicy>,
+}
+
+impl RotatingCookieIdentityPolicy {
+    pub fn new(config: &CookieConfig) -> RotatingCookieIdentityPolicy {
+        let policy = |key: &[u8]| {
+            CookieIdentityPolicy::new(key)
+                .name("auth-cookie")
+                .secure(config.secure)
+                .same_site(config.same_site)
+        };
+
+        RotatingCookieIdentityPolicy {
+            current: policy(&config.key),
+            previous: config.previous_keys.iter().map(|key| policy(key)).collect(),
+        }
+    }
+}
+
+impl IdentityPolicy for RotatingCookieIdentityPolicy {
+    type Future = Ready<Result<Option<String>, Error>>;
+    type ResponseFuture = Ready<Result<(), Error>>;
+
+    fn from_request(&self, request: &mut ServiceRequest) -> Self::Future {
+        match self.current.from_request(request).into_inner() {
+            Ok(None) => {}
+            result => return ready(result),
+        }
+
+        for policy in self.previous.iter() {
+            match policy.from_request(request).into_inner() {
+                Ok(None) => {}
+                Ok(identity) => {
+                    request.extensions_mut().insert(SignedWithPreviousKey);
+                    return ready(Ok(identity));
+                }
+                Err(error) => return ready(Err(error)),
+            }
+        }
+
+        ready(Ok(None))
+    }
+
+    fn to_response<B>(
+        &self,
+        identity: Option<String>,
+        changed: bool,
+        response: &mut ServiceResponse<B>,
+    ) -> Self::ResponseFuture {
+        let resign = response
+            .request()
+            .extensions()
+            .get::<SignedWithPreviousKey>()
+            .is_some();
+
+        self.current
+            .to_response(identity, changed || resign, response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn request(peer: &str, forwarded_for: Option<&str>, trusted_proxies: &[&str]) -> HttpRequest {
+        let mut req = TestRequest::default()
+            .peer_addr(format!("{}:40000", peer).parse().unwrap())
+            .data(ClientConfig {
+                trusted_proxies: trusted_proxies
+                    .iter()
+                    .map(|proxy| proxy.parse().unwrap())
+                    .collect(),
+                ip_hash_key: vec![0; 32],
+            });
+        if let Some(forwarded_for) = forwarded_for {
+            req = req.header("x-forwarded-for", forwarded_for);
+        }
+
+        req.to_http_request()
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn ignores_forwarded_for_from_untrusted_peers() {
+        let req = request("203.0.113.9", Some("198.51.100.1"), &["10.0.0.1"]);
+        assert_eq!(client_ip(&req), ip("203.0.113.9"));
+
+        let req = request("203.0.113.9", Some("198.51.100.1"), &[]);
+        assert_eq!(client_ip(&req), ip("203.0.113.9"));
+    }
+
+    #[test]
+    fn follows_forwarded_for_through_trusted_proxies() {
+        let req = request("172.18.0.5", Some("198.51.100.1"), &["172.18.0.0/16"]);
+        assert_eq!(client_ip(&req), ip("198.51.100.1"));
+
+        let req = request(
+            "10.0.0.1",
+            Some("198.51.100.1, 172.18.0.5"),
+            &["10.0.0.1", "172.18.0.0/16"],
+        );
+        assert_eq!(client_ip(&req), ip("198.51.100.1"));
+    }
+
+    #[test]
+    fn stops_at_the_first_untrusted_hop() {
+        // The client made up the first entry, the trusted proxy saw 203.0.113.9
+        let req = request("10.0.0.1", Some("198.51.100.1, 203.0.113.9"), &["10.0.0.1"]);
+        assert_eq!(client_ip(&req), ip("203.0.113.9"));
+    }
+}
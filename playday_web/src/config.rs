@@ -0,0 +1,386 @@
+use std::env;
+use std::fs;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use actix_web::cookie::SameSite;
+use anyhow::{bail, Context, Result};
+use url::Url;
+
+/// Cookie signing keys must be at least this long.
+const MIN_KEY_LENGTH: usize = 32;
+const DEFAULT_KEY: [u8; 32] = [0; 32];
+const DEVELOPMENT_PUBLIC_URL: &str = "http://localhost:8000";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Profile {
+    Development,
+    Production,
+}
+
+impl Profile {
+    /// Without `APP_ENV` the app runs as in production, the insecure development key and
+    /// cookies over plain http have to be asked for with `APP_ENV=development`.
+    fn from_env() -> Result<Profile> {
+        match env::var("APP_ENV").as_deref() {
+            Ok("production") | Err(_) => Ok(Profile::Production),
+            Ok("development") => Ok(Profile::Development),
+            Ok(other) => bail!("Unknown APP_ENV: {}", other),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CookieConfig {
+    /// Signs new cookies.
+    pub key: Vec<u8>,
+    /// Still accepted when verifying, so rotating the key doesn't log everybody out.
+    pub previous_keys: Vec<Vec<u8>>,
+    pub secure: bool,
+    pub same_site: SameSite,
+}
+
+#[derive(Clone)]
+pub struct OidcProviderConfig {
+    /// Used in the login URLs and stored with every identity, don't rename it once in use.
+    pub name: String,
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct AuthConfig {
+    /// Empty when OpenID Connect logins are switched off.
+    pub oidc_providers: Vec<OidcProviderConfig>,
+    /// Email and password accounts, with verification and resets over SMTP.
+    pub local_accounts: bool,
+}
+
+/// An address, or a network in CIDR notation like `172.18.0.0/16`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u32,
+}
+
+impl IpNetwork {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(addr), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len).unwrap_or(0);
+                u32::from(addr) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(addr), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len).unwrap_or(0);
+                u128::from(addr) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = anyhow::Error;
+
+    fn from_str(network: &str) -> Result<IpNetwork> {
+        let (addr, prefix_len) = match network.split_once('/') {
+            Some((addr, prefix_len)) => (addr.parse::<IpAddr>()?, Some(prefix_len.parse::<u32>()?)),
+            None => (network.parse::<IpAddr>()?, None),
+        };
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = prefix_len.unwrap_or(max_prefix_len);
+        if prefix_len > max_prefix_len {
+            bail!("Prefix length {} is too long", prefix_len);
+        }
+
+        Ok(IpNetwork { addr, prefix_len })
+    }
+}
+
+#[derive(Clone)]
+pub struct ClientConfig {
+    /// Reverse proxies whose `X-Forwarded-For` header is believed, it is ignored from anybody else.
+    pub trusted_proxies: Vec<IpNetwork>,
+    /// Keys the hashes of client addresses kept for rate limiting.
+    pub ip_hash_key: Vec<u8>,
+}
+
+/// The address people reach the app at. Links that go out by email are built from it,
+/// never from the request, whose `Host` header the client picks.
+#[derive(Clone)]
+pub struct PublicUrl(String);
+
+impl PublicUrl {
+    /// `path` starts with a slash.
+    pub fn join(&self, path: &str) -> String {
+        format!("{}{}", self.0, path)
+    }
+}
+
+#[derive(Clone)]
+pub struct AppConfig {
+    pub cookie: CookieConfig,
+    pub auth: AuthConfig,
+    pub client: ClientConfig,
+    pub public_url: PublicUrl,
+}
+
+fn decode_key(encoded: &str) -> Result<Vec<u8>> {
+    let key = base64::decode(encoded.trim()).context("Cookie keys must be base64 encoded")?;
+    if key.len() < MIN_KEY_LENGTH {
+        bail!("Cookie keys must be at least {} bytes long", MIN_KEY_LENGTH);
+    }
+
+    Ok(key)
+}
+
+/// Reads the keys, current one first. `COOKIE_KEY_FILE` wins over `COOKIE_KEY`, the file
+/// holds one base64 key per line with the previous keys after the current one.
+fn load_keys() -> Result<Vec<Vec<u8>>> {
+    let mut encoded_keys: Vec<String> = Vec::new();
+
+    if let Ok(key_file) = env::var("COOKIE_KEY_FILE") {
+        let contents = fs::read_to_string(&key_file)
+            .with_context(|| format!("Could not read cookie key file {}", key_file))?;
+        encoded_keys.extend(
+            contents
+                .lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| line.to_string()),
+        );
+    } else if let Ok(key) = env::var("COOKIE_KEY") {
+        encoded_keys.push(key);
+    }
+
+    if let Ok(previous_keys) = env::var("COOKIE_PREVIOUS_KEYS") {
+        encoded_keys.extend(
+            previous_keys
+                .split(',')
+                .filter(|key| !key.trim().is_empty())
+                .map(|key| key.to_string()),
+        );
+    }
+
+    encoded_keys.iter().map(|key| decode_key(key)).collect()
+}
+
+fn required_var(name: &str) -> Result<String> {
+    env::var(name).with_context(|| format!("{} must be set", name))
+}
+
+fn load_oidc_provider(name: &str) -> Result<OidcProviderConfig> {
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        bail!(
+            "Invalid OIDC provider name {}, use lowercase letters, digits and -",
+            name
+        );
+    }
+
+    let prefix = format!("OIDC_{}", name.to_uppercase().replace('-', "_"));
+    let scopes = env::var(format!("{}_SCOPES", prefix))
+        .unwrap_or_else(|_| "openid,profile,email".to_string());
+
+    Ok(OidcProviderConfig {
+        name: name.to_string(),
+        issuer_url: required_var(&format!("{}_ISSUER_URL", prefix))?,
+        client_id: required_var(&format!("{}_CLIENT_ID", prefix))?,
+        client_secret: required_var(&format!("{}_CLIENT_SECRET", prefix))?,
+        scopes: scopes
+            .split(',')
+            .map(|scope| scope.trim())
+            .filter(|scope| !scope.is_empty())
+            .map(|scope| scope.to_string())
+            .collect(),
+    })
+}
+
+/// Providers are listed in `OIDC_PROVIDERS` and configured with `OIDC_<NAME>_*`. Without it
+/// the old `AUTH0_*` variables are used as a provider called `auth0`.
+fn load_oidc_providers() -> Result<Vec<OidcProviderConfig>> {
+    if let Ok(names) = env::var("OIDC_PROVIDERS") {
+        let providers = names
+            .split(',')
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .map(load_oidc_provider)
+            .collect::<Result<Vec<_>>>()?;
+
+        if providers.is_empty() {
+            bail!("OIDC_PROVIDERS must list at least one provider");
+        }
+
+        return Ok(providers);
+    }
+
+    let auth0_base_url = required_var("AUTH0_BASE_URL")
+        .context("Either OIDC_PROVIDERS or AUTH0_BASE_URL must be set")?;
+
+    Ok(vec![OidcProviderConfig {
+        name: "auth0".to_string(),
+        // Auth0 issuers end with a slash and the issuer has to match exactly
+        issuer_url: format!("{}/", auth0_base_url.trim_end_matches('/')),
+        client_id: required_var("AUTH0_CLIENT_ID")?,
+        client_secret: required_var("AUTH0_CLIENT_SECRET")?,
+        scopes: vec![
+            "openid".to_string(),
+            "profile".to_string(),
+            "email".to_string(),
+        ],
+    }])
+}
+
+/// `AUTH_METHODS` lists the enabled login methods, `oidc` and `local`. Defaults to `oidc`.
+fn load_auth() -> Result<AuthConfig> {
+    let methods = env::var("AUTH_METHODS").unwrap_or_else(|_| "oidc".to_string());
+    let mut oidc = false;
+    let mut local = false;
+
+    for method in methods.split(',').map(|method| method.trim()) {
+        match method {
+            "oidc" => oidc = true,
+            "local" => local = true,
+            "" => {}
+            other => bail!("Unknown auth method in AUTH_METHODS: {}", other),
+        }
+    }
+
+    if !oidc && !local {
+        bail!("AUTH_METHODS must enable at least one of oidc, local");
+    }
+
+    Ok(AuthConfig {
+        oidc_providers: if oidc {
+            load_oidc_providers()?
+        } else {
+            Vec::new()
+        },
+        local_accounts: local,
+    })
+}
+
+/// `TRUSTED_PROXIES` lists the addresses of the reverse proxies in front of the app. Networks
+/// can be given in CIDR notation, proxies in containers don't keep their address.
+fn load_trusted_proxies() -> Result<Vec<IpNetwork>> {
+    let proxies = match env::var("TRUSTED_PROXIES") {
+        Ok(proxies) => proxies,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    proxies
+        .split(',')
+        .map(|proxy| proxy.trim())
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| {
+            proxy
+                .parse::<IpNetwork>()
+                .with_context(|| format!("Invalid address in TRUSTED_PROXIES: {}", proxy))
+        })
+        .collect()
+}
+
+/// `PUBLIC_URL` like `https://playday.example.com`, only optional in development.
+fn load_public_url(profile: Profile) -> Result<PublicUrl> {
+    let public_url = match env::var("PUBLIC_URL") {
+        Ok(public_url) => public_url,
+        Err(_) if profile == Profile::Development => DEVELOPMENT_PUBLIC_URL.to_string(),
+        Err(_) => bail!("PUBLIC_URL must be set in production"),
+    };
+
+    let parsed = Url::parse(public_url.trim()).context("PUBLIC_URL must be a URL")?;
+    if !matches!(parsed.scheme(), "http" | "https")
+        || parsed.host().is_none()
+        || parsed.query().is_some()
+        || parsed.fragment().is_some()
+    {
+        bail!("PUBLIC_URL must be an http(s) URL without query or fragment");
+    }
+
+    Ok(PublicUrl(parsed.as_str().trim_end_matches('/').to_string()))
+}
+
+impl AppConfig {
+    /// Loads the configuration from the environment. Refuses to run in production
+    /// without a real cookie key.
+    pub fn from_env() -> Result<AppConfig> {
+        let profile = Profile::from_env()?;
+        let mut keys = load_keys()?;
+
+        if keys.is_empty() || keys[0] == DEFAULT_KEY {
+            if profile == Profile::Production {
+                bail!("COOKIE_KEY or COOKIE_KEY_FILE must be set to a random key in production");
+            }
+
+            log::warn!("No cookie key configured, using the insecure development key");
+            if keys.is_empty() {
+                keys.push(DEFAULT_KEY.to_vec());
+            }
+        }
+
+        let key = keys.remove(0);
+        let auth = load_auth()?;
+        let public_url = load_public_url(profile)?;
+        let client = ClientConfig {
+            trusted_proxies: load_trusted_proxies()?,
+            ip_hash_key: key.clone(),
+        };
+
+        Ok(AppConfig {
+            cookie: CookieConfig {
+                key,
+                previous_keys: keys,
+                // Secure cookies need https, which we only have in production
+                secure: profile == Profile::Production,
+                // Strict would drop the cookie on the redirect back from the identity provider
+                same_site: SameSite::Lax,
+            },
+            auth,
+            client,
+            public_url,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network(network: &str) -> IpNetwork {
+        network.parse().unwrap()
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn single_addresses_only_contain_themselves() {
+        assert!(network("10.0.0.1").contains(ip("10.0.0.1")));
+        assert!(!network("10.0.0.1").contains(ip("10.0.0.2")));
+        assert!(network("::1").contains(ip("::1")));
+        assert!(!network("::1").contains(ip("::2")));
+    }
+
+    #[test]
+    fn networks_contain_their_addresses() {
+        assert!(network("172.18.0.0/16").contains(ip("172.18.5.3")));
+        assert!(!network("172.18.0.0/16").contains(ip("172.19.0.1")));
+        assert!(network("fd00::/8").contains(ip("fd12:3456::1")));
+        assert!(!network("fd00::/8").contains(ip("fe80::1")));
+        assert!(network("0.0.0.0/0").contains(ip("203.0.113.9")));
+        assert!(!network("0.0.0.0/0").contains(ip("::1")));
+    }
+
+    #[test]
+    fn rejects_invalid_networks() {
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("::/129".parse::<IpNetwork>().is_err());
+        assert!("10.0.0.0/".parse::<IpNetwork>().is_err());
+        assert!("proxy".parse::<IpNetwork>().is_err());
+    }
+}
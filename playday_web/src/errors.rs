@@ -0,0 +1,208 @@
+use std::fmt;
+
+use actix_web::error::BlockingError;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use diesel::result::DatabaseErrorKind;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::request_id::{self, REQUEST_ID_HEADER};
+
+/// Machine readable error codes returned by the API. The string values are part of the
+/// API contract, only ever add new ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+    Unauthorized,
+    InvalidToken,
+    InsufficientScope,
+    Forbidden,
+    BadRequest,
+    NotFound,
+    AlreadyExists,
+    InvalidSettings,
+    InvalidWebhookUrl,
+    InvalidApiToken,
+    InvalidGameChanges,
+    InvalidImport,
+    ImportNotReady,
+    TooManyInvites,
+    UnknownStore,
+    StoreLoginFailed,
+    IgdbUnavailable,
+    TaskQueueUnavailable,
+    DatabaseError,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::InvalidToken => "invalid_token",
+            ErrorCode::InsufficientScope => "insufficient_scope",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::AlreadyExists => "already_exists",
+            ErrorCode::InvalidSettings => "invalid_settings",
+            ErrorCode::InvalidWebhookUrl => "invalid_webhook_url",
+            ErrorCode::InvalidApiToken => "invalid_api_token",
+            ErrorCode::InvalidGameChanges => "invalid_game_changes",
+            ErrorCode::InvalidImport => "invalid_import",
+            ErrorCode::ImportNotReady => "import_not_ready",
+            ErrorCode::TooManyInvites => "too_many_invites",
+            ErrorCode::UnknownStore => "unknown_store",
+            ErrorCode::StoreLoginFailed => "store_login_failed",
+            ErrorCode::IgdbUnavailable => "igdb_unavailable",
+            ErrorCode::TaskQueueUnavailable => "task_queue_unavailable",
+            ErrorCode::DatabaseError => "database_error",
+            ErrorCode::InternalError => "internal_error",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::Unauthorized | ErrorCode::InvalidToken => StatusCode::UNAUTHORIZED,
+            ErrorCode::InsufficientScope | ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::BadRequest
+            | ErrorCode::InvalidSettings
+            | ErrorCode::InvalidWebhookUrl
+            | ErrorCode::InvalidApiToken
+            | ErrorCode::InvalidGameChanges
+            | ErrorCode::InvalidImport
+            | ErrorCode::UnknownStore => StatusCode::BAD_REQUEST,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::AlreadyExists | ErrorCode::ImportNotReady => StatusCode::CONFLICT,
+            ErrorCode::TooManyInvites => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::StoreLoginFailed | ErrorCode::IgdbUnavailable => StatusCode::BAD_GATEWAY,
+            ErrorCode::TaskQueueUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::DatabaseError | ErrorCode::InternalError => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ApiError {
+    code: ErrorCode,
+    message: String,
+    cause: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail<'a> {
+    code: &'a str,
+    message: &'a str,
+    request_id: String,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: ErrorDetail<'a>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> ApiError {
+        ApiError {
+            code,
+            message: message.into(),
+            cause: None,
+        }
+    }
+
+    pub fn unauthorized() -> ApiError {
+        ApiError::new(ErrorCode::Unauthorized, "You need to be logged in")
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> ApiError {
+        ApiError::new(ErrorCode::BadRequest, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> ApiError {
+        ApiError::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn internal(cause: impl fmt::Display) -> ApiError {
+        ApiError::new(ErrorCode::InternalError, "Something went wrong").with_cause(cause)
+    }
+
+    /// Attaches the underlying error. It only goes to the logs, never to the client.
+    pub fn with_cause(mut self, cause: impl fmt::Display) -> ApiError {
+        self.cause = Some(cause.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code.as_str(), self.message)?;
+        if let Some(cause) = &self.cause {
+            write!(f, " ({})", cause)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.code.status()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // Only missing when the error is rendered outside of the request id middleware
+        let request_id = request_id::current().unwrap_or_else(|| Uuid::new_v4().to_string());
+        if self.status_code().is_server_error() {
+            log::error!("[{}] {}", request_id, self);
+        } else {
+            log::info!("[{}] {}", request_id, self);
+        }
+
+        HttpResponse::build(self.status_code())
+            .header(REQUEST_ID_HEADER, request_id.as_str())
+            .json(ErrorBody {
+                error: ErrorDetail {
+                    code: self.code.as_str(),
+                    message: &self.message,
+                    request_id,
+                },
+            })
+    }
+}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(error: diesel::result::Error) -> ApiError {
+        match error {
+            diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                ApiError::new(ErrorCode::AlreadyExists, "That already exists").with_cause(error)
+            }
+            _ => ApiError::new(ErrorCode::DatabaseError, "Something went wrong").with_cause(error),
+        }
+    }
+}
+
+impl From<r2d2::Error> for ApiError {
+    fn from(error: r2d2::Error) -> ApiError {
+        ApiError::new(ErrorCode::DatabaseError, "Something went wrong").with_cause(error)
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> ApiError {
+        ApiError::internal(error)
+    }
+}
+
+impl<E> From<BlockingError<E>> for ApiError
+where
+    E: Into<ApiError> + fmt::Debug,
+{
+    fn from(error: BlockingError<E>) -> ApiError {
+        match error {
+            BlockingError::Error(error) => error.into(),
+            BlockingError::Canceled => ApiError::internal("Blocking operation was canceled"),
+        }
+    }
+}
@@ -4,37 +4,27 @@ extern crate diesel_migrations;
 use std::env;
 
 use actix_files::Files;
-use actix_identity::{CookieIdentityPolicy, IdentityService};
+use actix_identity::IdentityService;
 use actix_session::{CookieSession};
 use actix_web::middleware::Logger;
 use actix_web::{web, App, HttpServer};
 
 use dotenv::dotenv;
-use oauth2::basic::BasicClient;
-use oauth2::{AuthUrl, ClientId, ClientSecret, TokenUrl};
 use tera::Tera;
 
-use playday::{db, epicgames, igdb, models, types, tasks};
+use playday::{
+    accounts, api_tokens, db, dtos, epicgames, export, groups, igdb, imports, library, models,
+    settings, sharing, tasks, types, webhooks, wishlist,
+};
+mod auth;
+mod config;
+mod errors;
+mod oidc;
+mod request_id;
 mod routes;
 
 diesel_migrations::embed_migrations!();
 
-fn get_oauth_client() -> types::OAuthClient {
-    let client_id =
-        ClientId::new(env::var("AUTH0_CLIENT_ID").expect("AUTH0_CLIENT_ID must be set"));
-    let client_secret = ClientSecret::new(
-        env::var("AUTH0_CLIENT_SECRET").expect("AUTH0_CLIENT_SECRET must be set"),
-    );
-    let auth0_base_url = env::var("AUTH0_BASE_URL").expect("AUTH0_BASE_URL must be set");
-
-    let auth_url = AuthUrl::new(format!("{}/authorize", auth0_base_url))
-        .expect("Invalid authorization endpoint URL");
-    let token_url = TokenUrl::new(format!("{}/oauth/token", auth0_base_url))
-        .expect("Invalid token endpoint URL");
-
-    BasicClient::new(client_id, Some(client_secret), auth_url, Some(token_url))
-}
-
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
@@ -50,54 +40,239 @@ async fn main() -> std::io::Result<()> {
         ..Default::default()
     }));
 
+    let app_config = match config::AppConfig::from_env() {
+        Ok(app_config) => app_config,
+        Err(error) => {
+            log::error!("Invalid configuration! {:#}", error);
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, error.to_string()));
+        }
+    };
+
+    let oidc_providers = match oidc::OidcProviders::discover(&app_config.auth.oidc_providers) {
+        Ok(oidc_providers) => web::Data::new(oidc_providers),
+        Err(error) => {
+            log::error!("Could not set up login providers! {:#}", error);
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, error.to_string()));
+        }
+    };
+
+    let account_mailer = if app_config.auth.local_accounts {
+        match accounts::AccountMailer::new() {
+            Ok(account_mailer) => Some(web::Data::new(account_mailer)),
+            Err(error) => {
+                log::error!("Could not set up email for local accounts! {:#}", error);
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, error.to_string()));
+            }
+        }
+    } else {
+        None
+    };
+
+    // Email invites to groups need SMTP, invite links work without it
+    let invite_mailer = if env::var("SMTP_HOST").is_ok() {
+        match groups::InviteMailer::new() {
+            Ok(invite_mailer) => Some(web::Data::new(invite_mailer)),
+            Err(error) => {
+                log::error!("Could not set up email for group invites! {:#}", error);
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, error.to_string()));
+            }
+        }
+    } else {
+        None
+    };
+
     let pool: types::DBPool = db::establish_pool_connection();
 
     // let _ = embedded_migrations::run_with_output(&pool.get().unwrap(), &mut std::io::stdout());
     db::run_migrations(&pool.get().unwrap());
 
+    // One producer for all requests. The web app starts without a broker too, the producer
+    // connects once it is back.
+    let celery_app = web::Data::new(types::CeleryApp::new());
+    if celery_app.connect().await.is_err() {
+        log::warn!("Starting without a broker connection, tasks can't be queued yet");
+    }
+
     HttpServer::new(move || {
         let tera = Tera::new("templates/**/*").unwrap();
         let igdb_client = web::Data::new(igdb::IGDB::new().unwrap());
         App::new()
+            .wrap(request_id::RequestId)
             .wrap(sentry_actix::Sentry::new())
-            .wrap(IdentityService::new(
-                CookieIdentityPolicy::new(&[0; 32])
-                    .name("auth-cookie")
-                    .secure(false),
-            ))
-            .wrap(CookieSession::signed(&[0; 32]).secure(false))
+            .wrap(IdentityService::new(auth::RotatingCookieIdentityPolicy::new(
+                &app_config.cookie,
+            )))
+            .wrap(
+                CookieSession::signed(&app_config.cookie.key)
+                    .secure(app_config.cookie.secure)
+                    .same_site(app_config.cookie.same_site),
+            )
             .data(pool.clone())
             .data(tera)
-            .data(get_oauth_client())
+            .app_data(oidc_providers.clone())
+            .data(app_config.auth.clone())
+            .data(app_config.client.clone())
+            .data(app_config.public_url.clone())
             .app_data(igdb_client.clone())
+            .app_data(celery_app.clone())
             .route("/", web::get().to(routes::home))
+            .route("/ready", web::get().to(routes::readiness))
             .route("/login", web::get().to(routes::login))
+            .route("/logout", web::post().to(routes::logout))
             .service(
                 web::scope("/api")
+                    .app_data(web::JsonConfig::default().error_handler(routes::extractor_error))
+                    .app_data(web::PathConfig::default().error_handler(routes::extractor_error))
+                    .app_data(web::QueryConfig::default().error_handler(routes::extractor_error))
                     .route("/search", web::get().to(routes::search_igdb_games))
                     .route("/wishlist", web::get().to(routes::get_games_in_wishlist))
                     .route("/wishlist", web::post().to(routes::add_games_to_wishlist))
+                    .route("/wishlist/{game_id}", web::patch().to(routes::update_wished_game))
                     .route(
                         "/wishlist/{game_id}",
                         web::delete().to(routes::remove_game_from_wishlist),
                     )
+                    .route("/export", web::get().to(routes::export_wishlist))
+                    .service(
+                        web::resource("/imports")
+                            .app_data(
+                                web::JsonConfig::default()
+                                    .limit(imports::MAX_UPLOAD_BYTES)
+                                    .error_handler(routes::extractor_error),
+                            )
+                            .route(web::post().to(routes::add_import)),
+                    )
+                    .route("/imports/{import_id}", web::get().to(routes::get_import))
+                    .route(
+                        "/imports/{import_id}/commit",
+                        web::post().to(routes::commit_import),
+                    )
+                    .route("/lists", web::get().to(routes::get_game_lists))
+                    .route("/lists", web::post().to(routes::add_game_list))
+                    .route("/lists/{list_id}", web::patch().to(routes::rename_game_list))
+                    .route("/lists/{list_id}", web::delete().to(routes::remove_game_list))
+                    .route(
+                        "/lists/{list_id}/games/{game_id}",
+                        web::put().to(routes::add_game_to_list),
+                    )
+                    .route(
+                        "/lists/{list_id}/games/{game_id}",
+                        web::delete().to(routes::remove_game_from_list),
+                    )
+                    .route("/tags", web::get().to(routes::get_tags))
+                    .route("/tags", web::post().to(routes::add_tag))
+                    .route("/tags/{tag_id}", web::patch().to(routes::rename_tag))
+                    .route("/tags/{tag_id}", web::delete().to(routes::remove_tag))
+                    .route("/tags/{tag_id}/games/{game_id}", web::put().to(routes::tag_game))
+                    .route("/tags/{tag_id}/games/{game_id}", web::delete().to(routes::untag_game))
                     .route("/library/{store_name}/settings", web::get().to(routes::get_library_settings))
                     .route("/library/{store_name}/settings", web::delete().to(routes::disconnect_library))
                     .route("/library/{store_name}/sync", web::post().to(routes::sync_game_library))
+                    .route("/library/{store_name}/sync/{run_id}", web::get().to(routes::get_sync_run))
+                    .route(
+                        "/library/{store_name}/sync/{run_id}/events",
+                        web::get().to(routes::sync_run_events),
+                    )
+                    .route("/webhooks", web::get().to(routes::get_webhooks))
+                    .route("/webhooks", web::post().to(routes::add_webhook))
+                    .route("/webhooks/{webhook_id}", web::delete().to(routes::remove_webhook))
+                    .route(
+                        "/webhooks/{webhook_id}/deliveries",
+                        web::get().to(routes::get_webhook_deliveries),
+                    )
+                    .route("/webhooks/{webhook_id}/test", web::post().to(routes::test_webhook))
+                    .route("/settings", web::get().to(routes::get_settings))
+                    .route("/settings", web::put().to(routes::update_settings))
+                    .route("/sessions", web::get().to(routes::get_sessions))
+                    .route("/sessions", web::delete().to(routes::remove_all_sessions))
+                    .route("/sessions/{session_id}", web::delete().to(routes::remove_session))
+                    .route("/tokens", web::get().to(routes::get_api_tokens))
+                    .route("/tokens", web::post().to(routes::add_api_token))
+                    .route("/tokens/{token_id}", web::delete().to(routes::remove_api_token))
+                    .route("/shares", web::get().to(routes::get_share_links))
+                    .route("/shares", web::post().to(routes::add_share_link))
+                    .route("/shares/{share_id}", web::delete().to(routes::remove_share_link))
+                    .route("/groups", web::get().to(routes::get_groups))
+                    .route("/groups", web::post().to(routes::add_group))
+                    .route("/groups/{group_id}", web::patch().to(routes::update_group))
+                    .route("/groups/{group_id}", web::delete().to(routes::remove_group))
+                    .route("/groups/{group_id}/members", web::get().to(routes::get_group_members))
+                    .route(
+                        "/groups/{group_id}/members/{user_id}",
+                        web::delete().to(routes::remove_group_member),
+                    )
+                    .route("/groups/{group_id}/invites", web::get().to(routes::get_group_invites))
+                    .route("/groups/{group_id}/invites", web::post().to(routes::add_group_invite))
+                    .route(
+                        "/groups/{group_id}/invites/{invite_id}",
+                        web::delete().to(routes::remove_group_invite),
+                    )
+                    .route("/groups/{group_id}/games", web::get().to(routes::get_group_games))
 
             )
             .service(Files::new("/static", "./static"))
+            .route("/login/{provider}", web::get().to(routes::login_with_provider))
+            .configure(|cfg| {
+                // Local account pages only exist when local accounts are enabled
+                if let Some(account_mailer) = &account_mailer {
+                    cfg.app_data(account_mailer.clone())
+                        .route("/account/login", web::post().to(routes::login_local))
+                        .route("/account/register", web::get().to(routes::register_form))
+                        .route("/account/register", web::post().to(routes::register))
+                        .service(
+                            web::resource("/account/verify")
+                                .name("verify_email")
+                                .route(web::get().to(routes::verify_email)),
+                        )
+                        .service(
+                            web::resource("/account/forgot-password")
+                                .name("forgot_password")
+                                .route(web::get().to(routes::forgot_password_form))
+                                .route(web::post().to(routes::forgot_password)),
+                        )
+                        .service(
+                            web::resource("/account/reset-password")
+                                .name("reset_password")
+                                .route(web::get().to(routes::reset_password_form))
+                                .route(web::post().to(routes::reset_password)),
+                        );
+                }
+            })
+            .configure(|cfg| {
+                if let Some(invite_mailer) = &invite_mailer {
+                    cfg.app_data(invite_mailer.clone());
+                }
+            })
+            .service(
+                web::resource("/groups/join")
+                    .name("join_group")
+                    .route(web::get().to(routes::join_group_form))
+                    .route(web::post().to(routes::join_group)),
+            )
             .service(
-                web::resource("/login/callback")
+                web::resource("/share/{token}")
+                    .name("share_page")
+                    .route(web::get().to(routes::share_page)),
+            )
+            .route("/share/{token}/claims/{game_id}", web::post().to(routes::claim_gift))
+            .route(
+                "/share/{token}/claims/{game_id}/release",
+                web::post().to(routes::release_gift_claim),
+            )
+            .service(
+                web::resource("/login/{provider}/callback")
                     .name("login_callback")
                     .route(web::get().to(routes::login_callback)),
             )
             .service(
                 web::scope("/connect")
+                    .app_data(web::JsonConfig::default().error_handler(routes::extractor_error))
                     .route("/epicgames/login", web::post().to(routes::login_via_epicgames))
             )
 
-            .wrap(Logger::default())
+            .wrap(Logger::new(
+                "%a \"%r\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %{X-Request-Id}o %T",
+            ))
     })
     .workers(4) // <- Start 4 workers
     .bind("0.0.0.0:8000")
@@ -0,0 +1,171 @@
+use anyhow::{anyhow, Context, Result};
+use openidconnect::core::{CoreClient, CoreProviderMetadata, CoreResponseType};
+use openidconnect::reqwest::http_client;
+use openidconnect::{
+    AuthenticationFlow, AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce,
+    OAuth2TokenResponse, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope, TokenResponse,
+};
+
+use crate::config::OidcProviderConfig;
+
+/// An identity provider set up from its discovery document. The signing keys (JWKS) are
+/// fetched along with it, so rotating them at the provider needs a restart here.
+pub struct OidcProvider {
+    pub name: String,
+    client: CoreClient,
+    scopes: Vec<String>,
+}
+
+/// Everything the callback needs to finish a login, kept in the session in between.
+pub struct LoginRequest {
+    pub url: url::Url,
+    pub state: String,
+    pub nonce: String,
+    pub pkce_verifier: String,
+}
+
+/// The user as vouched for by a validated ID token.
+#[derive(Debug)]
+pub struct OidcIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+impl OidcProvider {
+    /// Fetches `.well-known/openid-configuration` and the JWKS of the issuer. Blocking.
+    pub fn discover(config: &OidcProviderConfig) -> Result<OidcProvider> {
+        let issuer_url = IssuerUrl::new(config.issuer_url.to_owned())
+            .with_context(|| format!("Invalid issuer URL for {}", config.name))?;
+        let metadata = CoreProviderMetadata::discover(&issuer_url, http_client)
+            .with_context(|| format!("OIDC discovery failed for {}", config.name))?;
+
+        let client = CoreClient::from_provider_metadata(
+            metadata,
+            ClientId::new(config.client_id.to_owned()),
+            Some(ClientSecret::new(config.client_secret.to_owned())),
+        );
+
+        Ok(OidcProvider {
+            name: config.name.to_owned(),
+            client,
+            scopes: config.scopes.to_owned(),
+        })
+    }
+
+    fn client(&self, redirect_url: &str) -> Result<CoreClient> {
+        let redirect_url = RedirectUrl::new(redirect_url.to_string())?;
+        Ok(self.client.clone().set_redirect_uri(redirect_url))
+    }
+
+    pub fn start_login(&self, redirect_url: &str) -> Result<LoginRequest> {
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+        let client = self.client(redirect_url)?;
+        let mut request = client
+            .authorize_url(
+                AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
+                CsrfToken::new_random,
+                Nonce::new_random,
+            )
+            .set_pkce_challenge(pkce_challenge);
+        for scope in self.scopes.iter().filter(|scope| *scope != "openid") {
+            request = request.add_scope(Scope::new(scope.to_owned()));
+        }
+        let (url, csrf_token, nonce) = request.url();
+
+        Ok(LoginRequest {
+            url,
+            state: csrf_token.secret().to_owned(),
+            nonce: nonce.secret().to_owned(),
+            pkce_verifier: pkce_verifier.secret().to_owned(),
+        })
+    }
+
+    /// Exchanges the code and validates the ID token: signature against the JWKS, issuer,
+    /// audience, expiry and nonce. Blocking.
+    pub fn finish_login(
+        &self,
+        redirect_url: &str,
+        code: String,
+        pkce_verifier: String,
+        nonce: String,
+    ) -> Result<OidcIdentity> {
+        let client = self.client(redirect_url)?;
+        let token = client
+            .exchange_code(AuthorizationCode::new(code))
+            .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier))
+            .request(http_client)
+            .context("Exchanging the code failed")?;
+
+        let id_token = token
+            .id_token()
+            .ok_or_else(|| anyhow!("Provider did not return an ID token"))?;
+        let claims = id_token
+            .claims(&client.id_token_verifier(), &Nonce::new(nonce))
+            .context("Invalid ID token")?;
+
+        let mut identity = OidcIdentity {
+            provider: self.name.to_owned(),
+            subject: claims.subject().to_string(),
+            email: claims.email().map(|email| email.to_string()),
+            email_verified: claims.email_verified().unwrap_or(false),
+            name: claims
+                .name()
+                .and_then(|name| name.get(None))
+                .map(|name| name.to_string()),
+        };
+
+        // Some providers keep the profile out of the ID token, ask the userinfo endpoint
+        if identity.email.is_none() || identity.name.is_none() {
+            let userinfo: openidconnect::core::CoreUserInfoClaims = client
+                .user_info(
+                    token.access_token().to_owned(),
+                    Some(claims.subject().to_owned()),
+                )?
+                .request(http_client)
+                .context("Fetching userinfo failed")?;
+
+            if identity.email.is_none() {
+                identity.email = userinfo.email().map(|email| email.to_string());
+                identity.email_verified = userinfo.email_verified().unwrap_or(false);
+            }
+            if identity.name.is_none() {
+                identity.name = userinfo
+                    .name()
+                    .and_then(|name| name.get(None))
+                    .map(|name| name.to_string());
+            }
+        }
+
+        Ok(identity)
+    }
+}
+
+pub struct OidcProviders {
+    providers: Vec<OidcProvider>,
+}
+
+impl OidcProviders {
+    pub fn discover(configs: &[OidcProviderConfig]) -> Result<OidcProviders> {
+        let providers = configs
+            .iter()
+            .map(OidcProvider::discover)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(OidcProviders { providers })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OidcProvider> {
+        self.providers.iter().find(|provider| provider.name == name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.providers
+            .iter()
+            .map(|provider| provider.name.as_str())
+            .collect()
+    }
+}
@@ -1,45 +1,47 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
 use actix_identity::Identity;
-use actix_web::{web, Error, HttpRequest, HttpResponse, Responder};
-use anyhow::bail;
-use chrono::Utc;
-use diesel::prelude::PgConnection;
-use http::header::{HeaderValue, ACCEPT, AUTHORIZATION};
-use http::method::Method;
-use oauth2::reqwest::http_client;
-use oauth2::{AccessToken, AuthorizationCode, CsrfToken, RedirectUrl, Scope, TokenResponse};
+use actix_session::Session;
+use actix_web::http::StatusCode;
+use actix_web::{rt, web, Error, HttpRequest, HttpResponse, Responder};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::{Connection, PgConnection};
+use futures::stream;
 use qstring::QString;
-use serde::Deserialize;
-use std::borrow::Cow;
-use std::env;
+use serde::{Deserialize, Serialize};
 use tera::{Context, Tera};
-use url::Url;
 use uuid::Uuid;
 
+use crate::accounts::{self, AccountMailer};
+use crate::api_tokens;
+use crate::auth::{client_ip, new_session, session_cutoffs, CurrentUser};
+use crate::config::{AuthConfig, ClientConfig, PublicUrl};
 use crate::db;
+use crate::dtos;
 use crate::epicgames::EpicGames;
+use crate::export;
+use crate::groups;
+use crate::errors::{ApiError, ErrorCode};
 use crate::igdb::{IGDBGame, IGDB};
+use crate::imports;
+use crate::library;
 use crate::models;
+use crate::oidc;
+use crate::settings;
+use crate::sharing;
 use crate::types;
 use crate::tasks;
+use crate::webhooks::{self, PayloadFormat};
+use crate::wishlist;
 
-pub const MIME_TYPE_JSON: &str = "application/json";
-
-fn is_logged_in(id: Identity) -> Option<models::User> {
-    if let Some(user_info) = id.identity() {
-        let user: models::User = serde_json::from_str(&user_info).unwrap();
-
-        return Some(user);
-    } else {
-        return None;
-    }
-}
-
-pub async fn home(tera: web::Data<Tera>, id: Identity) -> impl Responder {
+pub async fn home(tera: web::Data<Tera>, user: Option<CurrentUser>) -> impl Responder {
     let mut tera_data = Context::new();
 
-    if let Some(user) = is_logged_in(id) {
-        tera_data.insert("user", &user);
-        tera_data.insert("str_user", &serde_json::to_string(&user).unwrap());
+    if let Some(user) = user {
+        tera_data.insert("user", &*user);
+        tera_data.insert("str_user", &serde_json::to_string(&*user).unwrap());
     } else {
         return HttpResponse::TemporaryRedirect()
             .header("location", "/login")
@@ -54,406 +56,3083 @@ pub async fn home(tera: web::Data<Tera>, id: Identity) -> impl Responder {
         .body(rendered)
 }
 
+const LOGIN_STATE_KEY: &str = "oidc_login";
+
+/// Kept in the signed session between the redirect to the provider and the callback.
+#[derive(Serialize, Deserialize)]
+struct LoginState {
+    provider: String,
+    state: String,
+    nonce: String,
+    pkce_verifier: String,
+}
+
+fn page_context() -> Context {
+    let mut tera_data = Context::new();
+    tera_data.insert("title", "Playday");
+    tera_data
+}
+
+fn render_page(
+    tera: &Tera,
+    template: &str,
+    tera_data: &Context,
+    status: StatusCode,
+) -> HttpResponse {
+    match tera.render(template, tera_data) {
+        Ok(rendered) => HttpResponse::build(status)
+            .content_type("text/html; charset=UTF-8")
+            .body(rendered),
+        Err(error) => {
+            log::error!("Error rendering {}! {}", template, error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+fn render_login_error(tera: &Tera, message: &str) -> HttpResponse {
+    let mut tera_data = page_context();
+    tera_data.insert("message", message);
+
+    render_page(
+        tera,
+        "login_error.html",
+        &tera_data,
+        StatusCode::BAD_REQUEST,
+    )
+}
+
+fn render_message(tera: &Tera, heading: &str, message: &str) -> HttpResponse {
+    let mut tera_data = page_context();
+    tera_data.insert("heading", heading);
+    tera_data.insert("message", message);
+
+    render_page(tera, "account_message.html", &tera_data, StatusCode::OK)
+}
+
+fn render_login_page(
+    tera: &Tera,
+    providers: &oidc::OidcProviders,
+    auth_config: &AuthConfig,
+    error: Option<&str>,
+) -> HttpResponse {
+    let mut tera_data = page_context();
+    tera_data.insert("providers", &providers.names());
+    tera_data.insert("local_accounts", &auth_config.local_accounts);
+    tera_data.insert("error", &error);
+
+    let status = match error {
+        Some(_) => StatusCode::BAD_REQUEST,
+        None => StatusCode::OK,
+    };
+    render_page(tera, "login.html", &tera_data, status)
+}
+
+// GET /login
 pub async fn login(
-    req: HttpRequest,
-    oauth: web::Data<types::OAuthClient>,
+    tera: web::Data<Tera>,
+    providers: web::Data<oidc::OidcProviders>,
+    auth_config: web::Data<AuthConfig>,
     id: Identity,
 ) -> impl Responder {
-    let redirect_url = req
-        .url_for("login_callback", &[""])
-        .expect("Redirect URL not found");
-
     if let Some(_id) = id.identity() {
         return HttpResponse::TemporaryRedirect()
             .header("location", "/")
             .finish();
     }
 
-    let (auth_url, _csrf_token) = &oauth
-        .authorize_url(CsrfToken::new_random)
-        .set_redirect_uri(Cow::Owned(
-            RedirectUrl::new(redirect_url.to_string()).unwrap(),
-        ))
-        .add_scope(Scope::new("openid".to_string()))
-        .add_scope(Scope::new("profile".to_string()))
-        .add_scope(Scope::new("email".to_string()))
-        .url();
+    // Nothing to choose from, go straight to the provider
+    let names = providers.names();
+    if names.len() == 1 && !auth_config.local_accounts {
+        return HttpResponse::TemporaryRedirect()
+            .header("location", format!("/login/{}", names[0]))
+            .finish();
+    }
 
-    HttpResponse::TemporaryRedirect()
-        .header("location", auth_url.to_string())
-        .finish()
+    render_login_page(&tera, &providers, &auth_config, None)
 }
 
-fn get_user(access_token: &AccessToken) -> types::Auth0User {
-    let auth0_base_url = env::var("AUTH0_BASE_URL").expect("AUTH0_BASE_URL must be set");
-    let req_url = Url::parse(format!("{}/userinfo", auth0_base_url).as_str()).unwrap();
-
-    let userinfo_request = oauth2::HttpRequest {
-        url: req_url,
-        method: Method::GET,
-        headers: vec![
-            (ACCEPT, HeaderValue::from_static(MIME_TYPE_JSON)),
-            (
-                AUTHORIZATION,
-                HeaderValue::from_str(&format!("{} {}", "Bearer", access_token.secret()))
-                    .expect("Invalid access token"),
-            ),
-        ]
-        .into_iter()
-        .collect(),
-        body: Vec::new(),
+// GET /login/{provider}
+pub async fn login_with_provider(
+    req: HttpRequest,
+    tera: web::Data<Tera>,
+    providers: web::Data<oidc::OidcProviders>,
+    provider_name: web::Path<String>,
+    id: Identity,
+    session: Session,
+) -> impl Responder {
+    if let Some(_id) = id.identity() {
+        return HttpResponse::TemporaryRedirect()
+            .header("location", "/")
+            .finish();
+    }
+
+    let provider = match providers.get(&provider_name) {
+        Some(provider) => provider,
+        None => return render_login_error(&tera, "This login method is not available."),
+    };
+    let redirect_url = req
+        .url_for("login_callback", &[provider.name.as_str()])
+        .expect("Redirect URL not found");
+
+    let login_request = match provider.start_login(redirect_url.as_str()) {
+        Ok(login_request) => login_request,
+        Err(error) => {
+            log::error!("Error starting login with {}! {:#}", provider.name, error);
+            return render_login_error(&tera, "We could not start the login, please try again.");
+        }
     };
 
-    let resp = http_client(userinfo_request).expect("Request failed");
-    serde_json::from_slice(&resp.body).unwrap()
+    // Checked and consumed in login_callback
+    let login_state = LoginState {
+        provider: provider.name.to_owned(),
+        state: login_request.state,
+        nonce: login_request.nonce,
+        pkce_verifier: login_request.pkce_verifier,
+    };
+    if let Err(error) = session.set(LOGIN_STATE_KEY, login_state) {
+        log::error!("Error saving login state to session! {}", error);
+        return render_login_error(&tera, "We could not start the login, please try again.");
+    }
+
+    HttpResponse::TemporaryRedirect()
+        .header("location", login_request.url.to_string())
+        .finish()
 }
 
+/// Finds the user behind an identity by provider and subject. Accounts from before
+/// identities existed are linked once, by verified email. Everybody else gets a new user.
 fn login_user(
     db_conn: &PgConnection,
-    auth0_user: &types::Auth0User,
-) -> Result<models::User, diesel::result::Error> {
-    let user = db::get_user_by_email(db_conn, &auth0_user.email);
-
-    match user {
-        Ok(user) => {
-            let now_utc = Utc::now().naive_utc();
-            if let Some(app_user) = user {
-                let _update = db::update_user_login_time(db_conn, app_user.id);
-                return Ok(app_user);
-            } else {
-                let app_user = models::User {
+    identity: &oidc::OidcIdentity,
+) -> Result<models::User, ApiError> {
+    db_conn.transaction(|| {
+        let now_utc = Utc::now().naive_utc();
+
+        if let Some(user_identity) =
+            db::get_user_identity(db_conn, &identity.provider, &identity.subject)?
+        {
+            db::update_user_identity_login(db_conn, user_identity.id, identity.email.as_deref())?;
+            db::update_user_login_time(db_conn, user_identity.user_id)?;
+
+            return db::get_user_by_id(db_conn, user_identity.user_id)?
+                .ok_or_else(|| ApiError::internal("Identity points to a missing user"));
+        }
+
+        let email = identity.email.to_owned().ok_or_else(|| {
+            ApiError::bad_request("The identity provider did not share an email address")
+        })?;
+
+        let unlinked_user = if identity.email_verified {
+            db::get_unlinked_user_by_email(db_conn, &email)?
+        } else {
+            None
+        };
+
+        let user = match unlinked_user {
+            Some(user) => {
+                log::info!(
+                    "Linking {} login to existing user {}",
+                    identity.provider,
+                    user.id
+                );
+                db::update_user_login_time(db_conn, user.id)?;
+                user
+            }
+            None => {
+                let user = models::User {
                     id: Uuid::new_v4(),
-                    name: auth0_user.name.to_owned(),
-                    email: auth0_user.email.to_owned(),
+                    name: identity.name.to_owned().unwrap_or_else(|| email.to_owned()),
+                    email: email.to_owned(),
                     created_at: now_utc,
                     last_login: now_utc,
                 };
-                let _user_saved = db::create_user(db_conn, &app_user);
-                return Ok(app_user);
+                db::create_user(db_conn, &user)?;
+                user
             }
-        }
-        Err(error) => {
-            log::error!("Error getting user! {}", error);
-            return Err(error);
-        }
-    };
+        };
+
+        db::create_user_identity(
+            db_conn,
+            &models::UserIdentity {
+                id: Uuid::new_v4(),
+                user_id: user.id,
+                provider: identity.provider.to_owned(),
+                subject: identity.subject.to_owned(),
+                email: Some(email),
+                created_at: now_utc,
+                last_login: now_utc,
+            },
+        )?;
+
+        Ok(user)
+    })
 }
 
+// GET /login/{provider}/callback
 pub async fn login_callback(
     req: HttpRequest,
+    tera: web::Data<Tera>,
     pool: web::Data<types::DBPool>,
-    oauth: web::Data<types::OAuthClient>,
+    providers: web::Data<oidc::OidcProviders>,
+    provider_name: web::Path<String>,
     params: web::Query<types::AuthRequest>,
     id: Identity,
-) -> Result<HttpResponse, Error> {
-    let code = AuthorizationCode::new(params.code.clone());
-    let _state = CsrfToken::new(params.state.clone());
+    session: Session,
+) -> HttpResponse {
+    let params = params.into_inner();
+    let provider_name = provider_name.into_inner();
+
+    // Take the login state out of the session, it is only good for one attempt
+    let login_state = session.get::<LoginState>(LOGIN_STATE_KEY).ok().flatten();
+    session.remove(LOGIN_STATE_KEY);
+
+    if let Some(error) = params.error {
+        log::warn!(
+            "Identity provider returned an error! {}: {}",
+            error,
+            params.error_description.unwrap_or_default()
+        );
+        return render_login_error(&tera, "The login was cancelled or denied.");
+    }
+
+    let (code, state) = match (params.code, params.state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return render_login_error(&tera, "The login response was incomplete."),
+    };
+
+    let login_state = match login_state {
+        Some(login_state)
+            if login_state.state == state && login_state.provider == provider_name =>
+        {
+            login_state
+        }
+        _ => {
+            log::warn!("OAuth state mismatch on login callback");
+            return render_login_error(&tera, "The login link has expired, please try again.");
+        }
+    };
+
     let redirect_url = req
-        .url_for("login_callback", &[""])
-        .expect("Redirect URL not found");
+        .url_for("login_callback", &[provider_name.as_str()])
+        .expect("Redirect URL not found")
+        .to_string();
+    let session_req = new_session(&req, Uuid::nil());
 
-    // Exchange the code with a token.
-    let token = &oauth
-        .exchange_code(code)
-        .set_redirect_uri(Cow::Owned(
-            RedirectUrl::new(redirect_url.to_string()).unwrap(),
-        ))
-        .request(http_client)
-        .expect("exchange_code failed");
+    // Token exchange and JWKS validation block as well, they run along with the Diesel code
+    let user_session = web::block(move || -> Result<_, ApiError> {
+        let provider = providers
+            .get(&login_state.provider)
+            .ok_or_else(|| ApiError::bad_request("Unknown login provider"))?;
+        let identity = provider.finish_login(
+            &redirect_url,
+            code,
+            login_state.pkce_verifier,
+            login_state.nonce,
+        )?;
 
-    let auth0_user = get_user(token.access_token());
+        let conn = pool.get()?;
+        let user = login_user(&conn, &identity)?;
 
-    // log::info!("Hello {} with {}", auth0_user.name, user_email);
+        let user_session = models::Session {
+            user_id: user.id,
+            ..session_req
+        };
+        db::create_session(&conn, &user_session)?;
 
-    let conn = pool.get().expect("couldn't get db connection from pool");
+        Ok((user, user_session))
+    })
+    .await;
 
-    // use web::block to offload blocking Diesel code without blocking server thread
-    let user = web::block(move || login_user(&conn, &auth0_user))
-        .await
-        .map_err(|e| {
-            log::error!("Error saving user to db! {}", e);
-            HttpResponse::InternalServerError().finish()
-        })?;
+    let (user, user_session) = match user_session {
+        Ok(user_session) => user_session,
+        Err(error) => {
+            log::error!("Error logging in with {}! {}", provider_name, error);
+            return render_login_error(&tera, "We could not complete the login, please try again.");
+        }
+    };
 
-    let user_string = serde_json::to_string(&user).unwrap();
-    id.remember(user_string);
+    // Only the session id goes in the cookie, everything else stays on the server
+    id.remember(user_session.id.to_string());
     log::info!("Logged in as {}!", &user.id);
 
-    Ok(HttpResponse::TemporaryRedirect()
+    HttpResponse::TemporaryRedirect()
         .header("location", "/")
-        .finish())
+        .finish()
 }
 
-pub async fn search_igdb_games(
+#[derive(Deserialize)]
+pub struct LocalLoginForm {
+    email: String,
+    password: String,
+}
+
+/// Address of a named page for links in emails, on the configured public URL.
+fn public_link(req: &HttpRequest, public_url: &PublicUrl, name: &str) -> Result<String, ApiError> {
+    req.url_for_static(name)
+        .map(|url| public_url.join(url.path()))
+        .map_err(ApiError::internal)
+}
+
+enum LocalLogin {
+    LoggedIn(models::Session),
+    Unverified,
+    InvalidCredentials,
+    Throttled,
+}
+
+// POST /account/login
+pub async fn login_local(
     req: HttpRequest,
+    tera: web::Data<Tera>,
+    pool: web::Data<types::DBPool>,
+    providers: web::Data<oidc::OidcProviders>,
+    auth_config: web::Data<AuthConfig>,
+    form: web::Form<LocalLoginForm>,
     id: Identity,
-    igdb_client: web::Data<IGDB>,
-) -> Result<HttpResponse, Error> {
-    if let Some(_user) = is_logged_in(id) {
-    } else {
-        return Ok(HttpResponse::Unauthorized().finish());
-    }
+) -> HttpResponse {
+    let form = form.into_inner();
+    let email = accounts::normalize_email(&form.email);
+    let session_req = new_session(&req, Uuid::nil());
+    let ip_address = session_req.ip_address.to_owned().unwrap_or_default();
 
-    let query_str = req.query_string(); // "name=ferret"
-    let qs = QString::from(query_str);
-    let keyword = qs.get("keyword"); // "ferret"
+    // Password hashing is slow on purpose, keep it off the server thread along with Diesel
+    let outcome = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let window_start = accounts::failed_logins_window_start(Utc::now().naive_utc());
+        let (failed_by_email, failed_by_ip) =
+            db::count_failed_logins_since(&conn, &email, &ip_address, window_start)?;
+        if accounts::is_login_throttled(failed_by_email, failed_by_ip) {
+            return Ok(LocalLogin::Throttled);
+        }
 
-    if keyword.is_none() {
-        return Ok(HttpResponse::BadRequest().finish());
-    }
+        let valid_credential = match db::get_credential_by_email(&conn, &email)? {
+            Some(credential) => Some(credential).filter(|credential| {
+                accounts::verify_password(&form.password, &credential.password_hash)
+            }),
+            None => {
+                // Unknown emails must not answer faster than wrong passwords
+                accounts::check_dummy_password(&form.password);
+                None
+            }
+        };
+        let credential = match valid_credential {
+            Some(credential) => credential,
+            None => {
+                let failed_login = accounts::new_failed_login(&email, &ip_address);
+                db::create_failed_login(&conn, &failed_login, window_start)?;
+                return Ok(LocalLogin::InvalidCredentials);
+            }
+        };
+        db::delete_failed_logins_by_email(&conn, &email)?;
 
-    let search_keyword = keyword.unwrap();
-    let games = igdb_client.search_games(search_keyword).unwrap();
+        let user = db::get_user_by_id(&conn, credential.user_id)?
+            .ok_or_else(|| ApiError::internal("Credential points to a missing user"))?;
 
-    return Ok(HttpResponse::Ok().json(games));
-}
+        // New links only come from registering again, which also replaces the password
+        if credential.email_verified_at.is_none() {
+            return Ok(LocalLogin::Unverified);
+        }
 
-pub async fn add_games_to_wishlist(
-    pool: web::Data<types::DBPool>,
-    id: Identity,
-    str_igdb_game: String,
-) -> Result<HttpResponse, Error> {
-    match is_logged_in(id) {
-        None => return Ok(HttpResponse::Unauthorized().finish()),
-        Some(user) => {
-            // use web::block to offload blocking Diesel code without blocking server thread
-            Ok(web::block(move || {
-                let igdb_games: Vec<IGDBGame> = serde_json::from_str(&str_igdb_game).unwrap();
-                let mut wished_games: Vec<models::WishedGame> = Vec::new();
+        db::update_user_login_time(&conn, user.id)?;
+        let user_session = models::Session {
+            user_id: user.id,
+            ..session_req
+        };
+        db::create_session(&conn, &user_session)?;
 
-                for game in igdb_games.iter() {
-                    wished_games.push(models::WishedGame {
-                        id: Uuid::new_v4(),
-                        user_id: user.id.to_owned(),
-                        title: game.name.to_owned(),
-                        igdb_id: game.id.to_owned(),
-                        added_on: Utc::now().naive_utc(),
-                        igdb_info: serde_json::to_value(game).unwrap(),
-                        pc_release_date: game.get_pc_release_date(),
-                    });
-                }
+        Ok(LocalLogin::LoggedIn(user_session))
+    })
+    .await;
 
-                let conn = pool.get().expect("couldn't get db connection from pool");
-                let _added = match db::add_games_to_wishlist(&conn, &wished_games) {
-                    Ok(added) => added,
-                    Err(e) => return Err(e),
-                };
+    match outcome {
+        Ok(LocalLogin::LoggedIn(user_session)) => {
+            // Only the session id goes in the cookie, everything else stays on the server
+            id.remember(user_session.id.to_string());
+            log::info!("Logged in as {}!", &user_session.user_id);
 
-                Ok(wished_games)
-            })
-            .await
-            .map(|wished_games| HttpResponse::Ok().json(wished_games))
-            .map_err(|error| {
-                log::error!("Error saving games to wishlist! {}", error);
-                HttpResponse::InternalServerError().finish()
-            })?)
+            HttpResponse::SeeOther().header("location", "/").finish()
+        }
+        Ok(LocalLogin::Unverified) => render_message(
+            &tera,
+            "Confirm your email",
+            "Follow the link we sent you to confirm your email address and then log in. If it has expired, register again to get a new one.",
+        ),
+        Ok(LocalLogin::InvalidCredentials) => render_login_page(
+            &tera,
+            &providers,
+            &auth_config,
+            Some("The email or password is not right."),
+        ),
+        Ok(LocalLogin::Throttled) => render_login_page(
+            &tera,
+            &providers,
+            &auth_config,
+            Some("Too many failed logins, please wait a few minutes and try again."),
+        ),
+        Err(error) => {
+            log::error!("Error logging in with password! {}", error);
+            render_login_error(&tera, "We could not complete the login, please try again.")
         }
     }
 }
 
-pub async fn get_games_in_wishlist(
+#[derive(Deserialize)]
+pub struct RegisterForm {
+    name: String,
+    email: String,
+    password: String,
+}
+
+fn render_register_page(
+    tera: &Tera,
+    form: Option<&RegisterForm>,
+    error: Option<&str>,
+) -> HttpResponse {
+    let mut tera_data = page_context();
+    tera_data.insert(
+        "name",
+        &form.map(|form| form.name.as_str()).unwrap_or_default(),
+    );
+    tera_data.insert(
+        "email",
+        &form.map(|form| form.email.as_str()).unwrap_or_default(),
+    );
+    tera_data.insert("error", &error);
+    tera_data.insert("min_password_length", &accounts::MIN_PASSWORD_LENGTH);
+
+    let status = match error {
+        Some(_) => StatusCode::BAD_REQUEST,
+        None => StatusCode::OK,
+    };
+    render_page(tera, "register.html", &tera_data, status)
+}
+
+// GET /account/register
+pub async fn register_form(tera: web::Data<Tera>, id: Identity) -> HttpResponse {
+    if let Some(_id) = id.identity() {
+        return HttpResponse::TemporaryRedirect()
+            .header("location", "/")
+            .finish();
+    }
+
+    render_register_page(&tera, None, None)
+}
+
+// POST /account/register
+pub async fn register(
+    req: HttpRequest,
+    tera: web::Data<Tera>,
     pool: web::Data<types::DBPool>,
-    id: Identity,
-) -> Result<HttpResponse, Error> {
-    match is_logged_in(id) {
-        None => return Ok(HttpResponse::Unauthorized().finish()),
-        Some(user) => {
-            // use web::block to offload blocking Diesel code without blocking server thread
-            Ok(web::block(move || {
-                let conn = pool.get().expect("couldn't get db connection from pool");
-                let wished_games = match db::get_games_from_wishlist(&conn, user.id) {
-                    Ok(games) => games,
-                    Err(e) => return Err(e),
-                };
+    public_url: web::Data<PublicUrl>,
+    account_mailer: web::Data<AccountMailer>,
+    form: web::Form<RegisterForm>,
+) -> HttpResponse {
+    let form = form.into_inner();
+    let name = form.name.trim().to_string();
+    let email = accounts::normalize_email(&form.email);
 
-                Ok(wished_games)
-            })
-            .await
-            .map(|wished_games| HttpResponse::Ok().json(wished_games))
-            .map_err(|error| {
-                log::error!("Error getting games from wishlist! {}", error);
-                HttpResponse::InternalServerError().finish()
-            })?)
+    let invalid = if name.is_empty() {
+        Some("Please tell us your name.".to_string())
+    } else if !accounts::is_valid_email(&email) {
+        Some("Please enter a valid email address.".to_string())
+    } else {
+        accounts::validate_password(&form.password).err()
+    };
+    if let Some(error) = invalid {
+        return render_register_page(&tera, Some(&form), Some(&error));
+    }
+
+    let verify_url = public_link(&req, &public_url, "verify_email");
+    let forgot_password_url = public_link(&req, &public_url, "forgot_password");
+    let password = form.password.to_owned();
+
+    // Password hashing is slow on purpose, keep it off the server thread along with Diesel
+    let registered = web::block(move || -> Result<_, ApiError> {
+        // Hash first so taken emails don't answer faster
+        let password_hash = accounts::hash_password(&password)?;
+
+        let conn = pool.get()?;
+        if let Some(credential) = db::get_credential_by_email(&conn, &email)? {
+            // Tell the owner instead of the visitor, the page must not reveal who has an account
+            let user = db::get_user_by_id(&conn, credential.user_id)?
+                .ok_or_else(|| ApiError::internal("Credential points to a missing user"))?;
+            if credential.email_verified_at.is_none() {
+                // Nobody proved they own the email yet, the latest registration takes the
+                // account over. Otherwise whoever registered first would know the password
+                // of the account the owner confirms.
+                let (credential_token, token) =
+                    accounts::new_token(user.id, accounts::PURPOSE_VERIFY_EMAIL);
+                conn.transaction(|| -> Result<_, ApiError> {
+                    db::update_credential_password(&conn, user.id, &password_hash)?;
+                    db::update_user_name(&conn, user.id, &name)?;
+                    db::revoke_credential_tokens(&conn, user.id, accounts::PURPOSE_VERIFY_EMAIL)?;
+                    db::create_credential_token(&conn, &credential_token)?;
+                    Ok(())
+                })?;
+                account_mailer
+                    .send_verification(&user, &format!("{}?token={}", verify_url?, token))?;
+            } else {
+                account_mailer.send_account_exists(&user, &forgot_password_url?)?;
+            }
+
+            return Ok(());
         }
+
+        let now_utc = Utc::now().naive_utc();
+        let user = models::User {
+            id: Uuid::new_v4(),
+            name,
+            email: email.to_owned(),
+            created_at: now_utc,
+            last_login: now_utc,
+        };
+        let credential = models::Credential {
+            user_id: user.id,
+            email,
+            password_hash,
+            email_verified_at: None,
+            created_at: now_utc,
+            updated_on: now_utc,
+        };
+        let (credential_token, token) =
+            accounts::new_token(user.id, accounts::PURPOSE_VERIFY_EMAIL);
+
+        conn.transaction(|| -> Result<_, ApiError> {
+            db::create_user(&conn, &user)?;
+            db::create_credential(&conn, &credential)?;
+            db::create_credential_token(&conn, &credential_token)?;
+            Ok(())
+        })?;
+        account_mailer.send_verification(&user, &format!("{}?token={}", verify_url?, token))?;
+
+        Ok(())
+    })
+    .await;
+
+    if let Err(error) = registered {
+        log::error!("Error registering account! {}", error);
+        return render_login_error(&tera, "We could not create your account, please try again.");
     }
+
+    // Same answer for taken emails, their owner gets an email instead
+    render_message(
+        &tera,
+        "Check your inbox",
+        "We sent you an email to confirm your address. Follow the link in it and then log in.",
+    )
 }
 
-// DELETE /api/wishlist/{gameId}
-pub async fn remove_game_from_wishlist(
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    token: String,
+}
+
+// GET /account/verify
+pub async fn verify_email(
+    tera: web::Data<Tera>,
     pool: web::Data<types::DBPool>,
-    id: Identity,
-    path: web::Path<Uuid>,
-) -> Result<HttpResponse, Error> {
-    match is_logged_in(id) {
-        None => return Ok(HttpResponse::Unauthorized().finish()),
-        Some(user) => {
-            let game_id = path.into_inner();
-            let conn = pool.get().expect("couldn't get db connection from pool");
+    query: web::Query<TokenQuery>,
+) -> HttpResponse {
+    let token_hash = api_tokens::hash_token(&query.token);
 
-            // use web::block to offload blocking Diesel code without blocking server thread
-            let _remove =
-                web::block(move || db::remove_game_from_wishlist(&conn, user.id, game_id))
-                    .await
-                    .map_err(|e| {
-                        log::error!("Error removing game from wishlist! {}", e);
-                        HttpResponse::InternalServerError().finish()
-                    })?;
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let verified = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        match db::take_credential_token(&conn, &token_hash, accounts::PURPOSE_VERIFY_EMAIL)? {
+            Some(credential_token) => {
+                db::mark_credential_verified(&conn, credential_token.user_id)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    })
+    .await;
 
-            Ok(HttpResponse::NoContent().finish())
+    match verified {
+        Ok(true) => render_message(
+            &tera,
+            "Email confirmed",
+            "Thanks for confirming your email address, you can log in now.",
+        ),
+        Ok(false) => render_login_error(&tera, "This link is invalid or has expired."),
+        Err(error) => {
+            log::error!("Error verifying email! {}", error);
+            render_login_error(&tera, "We could not confirm your email, please try again.")
         }
     }
 }
 
 #[derive(Deserialize)]
-pub struct EpicGamesLogin {
-    sid: String,
+pub struct ForgotPasswordForm {
+    email: String,
 }
 
-// GET /connect/epicgames/login
-pub async fn login_via_epicgames(
-    id: Identity,
-    pool: web::Data<types::DBPool>,
-    login_info: web::Json<EpicGamesLogin>,
-) -> Result<HttpResponse, Error> {
-    match is_logged_in(id) {
-        None => return Ok(HttpResponse::Unauthorized().finish()),
-        Some(user) => {
-            let epic_games = EpicGames::new().unwrap();
+// GET /account/forgot-password
+pub async fn forgot_password_form(tera: web::Data<Tera>) -> HttpResponse {
+    render_page(
+        &tera,
+        "forgot_password.html",
+        &page_context(),
+        StatusCode::OK,
+    )
+}
 
-            let auth_code = epic_games.get_exchange_token(&login_info.sid).unwrap();
+// POST /account/forgot-password
+pub async fn forgot_password(
+    req: HttpRequest,
+    tera: web::Data<Tera>,
+    pool: web::Data<types::DBPool>,
+    public_url: web::Data<PublicUrl>,
+    account_mailer: web::Data<AccountMailer>,
+    form: web::Form<ForgotPasswordForm>,
+) -> HttpResponse {
+    let email = accounts::normalize_email(&form.email);
+    let reset_url = public_link(&req, &public_url, "reset_password");
 
-            let token = epic_games.get_login_tokens(&auth_code).unwrap();
-            let display_name = token.display_name.clone();
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let sent = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let credential = match db::get_credential_by_email(&conn, &email)? {
+            Some(credential) => credential,
+            None => return Ok(()),
+        };
+        let user = db::get_user_by_id(&conn, credential.user_id)?
+            .ok_or_else(|| ApiError::internal("Credential points to a missing user"))?;
 
-            let game_store = models::GameStore {
-                id: Uuid::new_v4(),
-                user_id: user.id.to_owned(),
-                added_on: Utc::now().naive_utc(),
-                updated_on: Utc::now().naive_utc(),
-                store_name: "epicgames".to_string(),
-                store_token: serde_json::to_value(token).unwrap(),
-                store_user_name: display_name,
-            };
+        let (credential_token, token) =
+            accounts::new_token(user.id, accounts::PURPOSE_RESET_PASSWORD);
+        db::create_credential_token(&conn, &credential_token)?;
+        account_mailer.send_password_reset(&user, &format!("{}?token={}", reset_url?, token))?;
 
-            let conn = pool.get().unwrap();
-            // use web::block to offload blocking Diesel code without blocking server thread
-            let _save = web::block(move || db::save_epicgames_login(&conn, &game_store))
-                .await
-                .map_err(|e| {
-                    log::error!("Error saving epicgames info db! {}", e);
-                    HttpResponse::InternalServerError().finish()
-                })?;
+        Ok(())
+    })
+    .await;
 
-            Ok(HttpResponse::Ok().finish())
-        }
+    if let Err(error) = sent {
+        log::error!("Error sending password reset! {}", error);
+        return render_login_error(&tera, "We could not send the email, please try again.");
     }
+
+    // Same answer either way, this must not tell which emails have an account
+    render_message(
+        &tera,
+        "Check your inbox",
+        "If there is an account for this email, we sent it a link to reset the password.",
+    )
 }
 
+#[derive(Deserialize)]
+pub struct ResetPasswordForm {
+    token: String,
+    password: String,
+}
 
-// GET /library/{store_name}/settings
-pub async fn get_library_settings(
-    id: Identity,
+fn render_reset_page(tera: &Tera, token: &str, error: Option<&str>) -> HttpResponse {
+    let mut tera_data = page_context();
+    tera_data.insert("token", token);
+    tera_data.insert("error", &error);
+    tera_data.insert("min_password_length", &accounts::MIN_PASSWORD_LENGTH);
+
+    let status = match error {
+        Some(_) => StatusCode::BAD_REQUEST,
+        None => StatusCode::OK,
+    };
+    render_page(tera, "reset_password.html", &tera_data, status)
+}
+
+// GET /account/reset-password
+pub async fn reset_password_form(
+    tera: web::Data<Tera>,
+    query: web::Query<TokenQuery>,
+) -> HttpResponse {
+    // The token is only checked, and used up, when the form is sent
+    render_reset_page(&tera, &query.token, None)
+}
+
+// POST /account/reset-password
+pub async fn reset_password(
+    tera: web::Data<Tera>,
     pool: web::Data<types::DBPool>,
-    store_name: web::Path<String>
-) -> Result<HttpResponse, Error> {
-    match is_logged_in(id) {
-        None => return Ok(HttpResponse::Unauthorized().finish()),
-        Some(user) => {
-            // use web::block to offload blocking Diesel code without blocking server thread
-            Ok(web::block(move || {
-                let store_name = store_name.into_inner();
+    form: web::Form<ResetPasswordForm>,
+) -> HttpResponse {
+    let form = form.into_inner();
+    if let Err(error) = accounts::validate_password(&form.password) {
+        return render_reset_page(&tera, &form.token, Some(&error));
+    }
 
-                // Get Epic Library Settings
-                let conn = pool.get().expect("couldn't get db connection from pool");
+    let token_hash = api_tokens::hash_token(&form.token);
+    let password = form.password;
 
-                let game_store = match db::get_game_store_account(&conn, user.id, &store_name) {
-                    Ok(account) => account,
-                    Err(e) => return Err(e),
-                };
+    // Password hashing is slow on purpose, keep it off the server thread along with Diesel
+    let reset = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let password_hash = accounts::hash_password(&password)?;
 
-                Ok(game_store)
-            })
-            .await
-            .map(|game_store| HttpResponse::Ok().json(game_store))
-            .map_err(|error| {
-                log::error!("Error getting games from wishlist! {}", error);
-                HttpResponse::InternalServerError().finish()
-            })?)
+        conn.transaction(|| -> Result<_, ApiError> {
+            let credential_token = match db::take_credential_token(
+                &conn,
+                &token_hash,
+                accounts::PURPOSE_RESET_PASSWORD,
+            )? {
+                Some(credential_token) => credential_token,
+                None => return Ok(false),
+            };
+
+            db::update_credential_password(&conn, credential_token.user_id, &password_hash)?;
+            // Following the link proves they own the email
+            db::mark_credential_verified(&conn, credential_token.user_id)?;
+            // Whoever knew the old password gets logged out
+            db::remove_user_sessions(&conn, credential_token.user_id)?;
+
+            Ok(true)
+        })
+    })
+    .await;
+
+    match reset {
+        Ok(true) => render_message(
+            &tera,
+            "Password changed",
+            "Your password was changed and all your sessions were logged out. Log in with the new password.",
+        ),
+        Ok(false) => render_login_error(&tera, "This link is invalid or has expired."),
+        Err(error) => {
+            log::error!("Error resetting password! {}", error);
+            render_login_error(&tera, "We could not change your password, please try again.")
         }
     }
 }
 
-// DELETE /library/{store_name}/settings
-pub async fn disconnect_library(
-    id: Identity,
+// POST /logout
+pub async fn logout(
     pool: web::Data<types::DBPool>,
-    store_name: web::Path<String>
-) -> Result<HttpResponse, Error> {
-    match is_logged_in(id) {
-        None => return Ok(HttpResponse::Unauthorized().finish()),
-        Some(user) => {
-            let store_name = store_name.into_inner();
-            let conn = pool.get().expect("couldn't get db connection from pool");
-
-            // use web::block to offload blocking Diesel code without blocking server thread
-            let _remove =
-                web::block(move || db::remove_game_store(&conn, user.id, &store_name))
-                    .await
-                    .map_err(|e| {
-                        log::error!("Error removing game from wishlist! {}", e);
-                        HttpResponse::InternalServerError().finish()
-                    })?;
-
-            Ok(HttpResponse::NoContent().finish())
-        }
+    user: Option<CurrentUser>,
+    id: Identity,
+) -> Result<HttpResponse, ApiError> {
+    // Requests with an API token have no session to end
+    let session = user.and_then(|user| user.session_id().map(|session_id| (user.id, session_id)));
+    if let Some((usr_id, session_id)) = session {
+        // use web::block to offload blocking Diesel code without blocking server thread
+        web::block(move || -> Result<_, ApiError> {
+            let conn = pool.get()?;
+            Ok(db::remove_session(&conn, usr_id, session_id)?)
+        })
+        .await?;
     }
+    id.forget();
+
+    Ok(HttpResponse::SeeOther()
+        .header("location", "/login")
+        .finish())
 }
 
+pub async fn search_igdb_games(
+    req: HttpRequest,
+    user: CurrentUser,
+    igdb_client: web::Data<IGDB>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_READ)?;
+
+    let query_str = req.query_string(); // "name=ferret"
+    let qs = QString::from(query_str);
+    let search_keyword = match qs.get("keyword") {
+        Some(keyword) => keyword, // "ferret"
+        None => return Err(ApiError::bad_request("The keyword query parameter is required")),
+    };
 
+    let games = igdb_client.search_games(search_keyword).map_err(|error| {
+        ApiError::new(ErrorCode::IgdbUnavailable, "Could not search IGDB").with_cause(error)
+    })?;
 
-// POST /library/{store_name}/sync
-pub async fn sync_game_library(
-    id: Identity,
+    Ok(HttpResponse::Ok().json(games))
+}
+
+// POST /api/wishlist
+pub async fn add_games_to_wishlist(
     pool: web::Data<types::DBPool>,
-    store_name: web::Path<String>
-) -> Result<HttpResponse, Error> {
-    let EpicGames = "epicgames".to_string();
-    match is_logged_in(id) {
-        None => return Ok(HttpResponse::Unauthorized().finish()),
-        Some(user) => {
-            let store_name = store_name.into_inner();
-            let celery_app = match tasks::get_celery_app().await {
-                Ok(app) => app,
-                Err(error) => {
-                    log::error!("Error creating celery app! {}", error);
-                    return Ok(HttpResponse::InternalServerError().finish())
-                }
-            };
+    user: CurrentUser,
+    igdb_client: web::Data<IGDB>,
+    new_games: web::Json<Vec<wishlist::NewWishedGame>>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
 
-            let task_future = match store_name {
-                x if x == EpicGames => {
-                    match celery_app.send_task(tasks::sync_epicgames_library::new(user.id.to_owned())).await {
-                        Ok(task) => task.task_id,
-                        Err(error) => {
-                            log::error!("Error sending task to celery app! {}", error);
-                            "E000".to_string()
-                        }
-                    }
-                },
-                _ => {
-                    log::error!("Unknown store name! {}", store_name);
-                    "E101".to_string()
-                },
-            };            
+    let new_games = new_games.into_inner();
+    if new_games.len() > wishlist::MAX_NEW_GAMES {
+        return Err(ApiError::bad_request(format!(
+            "At most {} games can be added at once",
+            wishlist::MAX_NEW_GAMES
+        )));
+    }
+
+    let mut igdb_ids: Vec<i32> = new_games.iter().map(|game| game.igdb_id).collect();
+    igdb_ids.sort_unstable();
+    igdb_ids.dedup();
 
-            celery_app.close()
-            .await
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let added_games = web::block(move || -> Result<Vec<wishlist::AddedGame>, ApiError> {
+        let igdb_games: HashMap<i32, IGDBGame> = igdb_client
+            .get_games_by_ids(&igdb_ids)
             .map_err(|error| {
-                log::error!("Error closing celery app! {}", error);
-                HttpResponse::InternalServerError().finish()
-            })?;
+                ApiError::new(
+                    ErrorCode::IgdbUnavailable,
+                    "Could not fetch the games from IGDB",
+                )
+                .with_cause(error)
+            })?
+            .into_iter()
+            .map(|game| (game.id, game))
+            .collect();
 
-            Ok(HttpResponse::NoContent().finish())
-        }
-    }
+        let conn = pool.get()?;
+        let wished_ids: HashSet<i32> = db::get_wished_igdb_ids(&conn, user.id, &igdb_ids)?
+            .into_iter()
+            .collect();
+        let added_games = wishlist::new_wished_games(user.id, new_games, &igdb_games, &wished_ids);
+
+        let wished_games: Vec<models::WishedGame> = added_games
+            .iter()
+            .filter_map(|added_game| match added_game {
+                wishlist::AddedGame::Added(game) => Some(game.to_owned()),
+                _ => None,
+            })
+            .collect();
+        db::add_games_to_wishlist(&conn, &wished_games)?;
+
+        Ok(added_games)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(added_games))
+}
+
+// GET /api/wishlist?sort=release_date&order=asc&status=wishlist&release=unreleased&platform=win&list=&tag=&q=&cursor=&limit=50
+pub async fn get_games_in_wishlist(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    params: web::Query<wishlist::WishlistParams>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_READ)?;
+
+    let query = wishlist::parse_params(params.into_inner()).map_err(ApiError::bad_request)?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let wished_games = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let games = db::get_wishlist_page(&conn, user.id, &query)?;
+        let game_ids: Vec<Uuid> = games.iter().map(|game| game.id).collect();
+        let game_lists = db::get_lists_of_games(&conn, &game_ids)?;
+        let game_tags = db::get_tags_of_games(&conn, &game_ids)?;
+
+        Ok(wishlist::into_page(&query, games, &game_lists, &game_tags))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(wished_games))
+}
+
+// PATCH /api/wishlist/{game_id}
+pub async fn update_wished_game(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+    changes: web::Json<wishlist::WishedGameChanges>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let game_id = path.into_inner();
+    let changes = changes.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let wished_game = web::block(move || -> Result<models::WishedGame, ApiError> {
+        let conn = pool.get()?;
+        conn.transaction(|| {
+            let mut game = db::get_wished_game(&conn, user.id, game_id)?
+                .ok_or_else(|| ApiError::not_found("Game is not in the wishlist"))?;
+            wishlist::apply_changes(&mut game, changes, Utc::now().naive_utc())
+                .map_err(|message| ApiError::new(ErrorCode::InvalidGameChanges, message))?;
+            // Gift claims only make sense while the game is still wished for
+            if game.status != wishlist::GameStatus::Wishlist.as_str() {
+                db::remove_gift_claims_for_game(&conn, game.id)?;
+            }
+
+            Ok(db::update_wished_game(&conn, &game)?)
+        })
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(wished_game))
+}
+
+// DELETE /api/wishlist/{gameId}
+pub async fn remove_game_from_wishlist(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let game_id = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::remove_game_from_wishlist(&conn, user.id, game_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// GET /api/export?format=json|csv
+pub async fn export_wishlist(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    params: web::Query<export::ExportParams>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_READ)?;
+
+    let format = params.format.unwrap_or(export::ExportFormat::Json);
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let wishlist_export = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let games = db::get_games_from_wishlist(&conn, user.id)?;
+        let game_ids: Vec<Uuid> = games.iter().map(|game| game.id).collect();
+        let lists = db::get_user_game_lists(&conn, user.id)?;
+        let tags = db::get_user_tags(&conn, user.id)?;
+        let game_lists = db::get_lists_of_games(&conn, &game_ids)?;
+        let game_tags = db::get_tags_of_games(&conn, &game_ids)?;
+
+        Ok(export::build_export(
+            games,
+            &lists,
+            &tags,
+            &game_lists,
+            &game_tags,
+        ))
+    })
+    .await?;
+
+    let file_name = format!(
+        "playday-wishlist-{}",
+        wishlist_export.exported_at.format("%Y-%m-%d")
+    );
+    let response = match format {
+        export::ExportFormat::Json => HttpResponse::Ok()
+            .header(
+                "content-disposition",
+                format!("attachment; filename=\"{}.json\"", file_name),
+            )
+            .json(wishlist_export),
+        export::ExportFormat::Csv => HttpResponse::Ok()
+            .content_type("text/csv; charset=UTF-8")
+            .header(
+                "content-disposition",
+                format!("attachment; filename=\"{}.csv\"", file_name),
+            )
+            .body(export::write_csv(&wishlist_export)?),
+    };
+
+    Ok(response)
+}
+
+// POST /api/imports
+pub async fn add_import(
+    pool: web::Data<types::DBPool>,
+    celery_app: web::Data<types::CeleryApp>,
+    user: CurrentUser,
+    new_import: web::Json<imports::NewImport>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let new_import = new_import.into_inner();
+    let rows = imports::parse_rows(&new_import)
+        .map_err(|message| ApiError::new(ErrorCode::InvalidImport, message))?;
+    let import =
+        imports::new_import(user.id, new_import.source, &rows).map_err(ApiError::internal)?;
+    let import_id = import.id;
+
+    let db_pool = pool.clone();
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let import = web::block(move || -> Result<models::Import, ApiError> {
+        let conn = db_pool.get()?;
+        db::create_import(&conn, &import)?;
+
+        Ok(import)
+    })
+    .await?;
+
+    let queued = celery_app
+        .send_task(|| tasks::resolve_import::new(import_id))
+        .await;
+
+    if let Err(error) = queued {
+        // Nothing would ever pick it up, don't leave it pending
+        web::block(move || -> Result<_, ApiError> {
+            let conn = pool.get()?;
+            Ok(db::fail_import(
+                &conn,
+                import_id,
+                imports::STATUS_FAILED,
+                "The import could not be started",
+            )?)
+        })
+        .await?;
+
+        return Err(ApiError::new(
+            ErrorCode::TaskQueueUnavailable,
+            "Could not start the import",
+        )
+        .with_cause(error));
+    }
+
+    Ok(HttpResponse::Accepted().json(imports::to_report(&import)))
+}
+
+// GET /api/imports/{import_id}
+pub async fn get_import(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_READ)?;
+
+    let import_id = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let import = web::block(move || -> Result<models::Import, ApiError> {
+        let conn = pool.get()?;
+        let import = db::get_import(&conn, user.id, import_id)?
+            .ok_or_else(|| ApiError::not_found("Import not found"))?;
+
+        let now_utc = Utc::now().naive_utc();
+        if !imports::is_stale(&import, now_utc) {
+            return Ok(import);
+        }
+        // The worker lost it, fail it so the user knows to start over
+        db::fail_stale_import(
+            &conn,
+            import_id,
+            &imports::WAITING_STATUSES,
+            imports::stale_cutoff(now_utc),
+            imports::STATUS_FAILED,
+            imports::STALE_IMPORT_ERROR,
+        )?;
+        db::get_import(&conn, user.id, import_id)?
+            .ok_or_else(|| ApiError::not_found("Import not found"))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(imports::to_report(&import)))
+}
+
+/// Puts imported games on the lists and tags they had in the export, matched by name.
+/// Lists and tags the user doesn't have yet are created.
+fn label_imported_games(
+    conn: &PgConnection,
+    usr_id: Uuid,
+    games: &[(Uuid, &imports::ImportRow)],
+) -> Result<(), ApiError> {
+    let now_utc = Utc::now().naive_utc();
+    let mut lists: HashMap<String, Uuid> = db::get_user_game_lists(conn, usr_id)?
+        .into_iter()
+        .map(|list| (list.name, list.id))
+        .collect();
+    let mut tags: HashMap<String, Uuid> = db::get_user_tags(conn, usr_id)?
+        .into_iter()
+        .map(|tag| (tag.name, tag.id))
+        .collect();
+
+    for (game_id, row) in games.iter() {
+        let (list_names, tag_names) = imports::row_labels(row);
+
+        for list_name in list_names.iter() {
+            let list_name = match wishlist::normalize_name(list_name) {
+                Ok(list_name) => list_name,
+                Err(_) => continue,
+            };
+            let list_id = match lists.get(&list_name) {
+                Some(list_id) => *list_id,
+                None => {
+                    let list = models::GameList {
+                        id: Uuid::new_v4(),
+                        user_id: usr_id,
+                        name: list_name,
+                        created_on: now_utc,
+                    };
+                    db::create_game_list(conn, &list)?;
+                    lists.insert(list.name, list.id);
+                    list.id
+                }
+            };
+            db::add_game_to_list(
+                conn,
+                &models::GameListEntry {
+                    list_id,
+                    wished_game_id: *game_id,
+                    added_on: now_utc,
+                },
+            )?;
+        }
+
+        for tag_name in tag_names.iter() {
+            let tag_name = match wishlist::normalize_name(tag_name) {
+                Ok(tag_name) => tag_name,
+                Err(_) => continue,
+            };
+            let tag_id = match tags.get(&tag_name) {
+                Some(tag_id) => *tag_id,
+                None => {
+                    let tag = models::Tag {
+                        id: Uuid::new_v4(),
+                        user_id: usr_id,
+                        name: tag_name,
+                        created_on: now_utc,
+                    };
+                    db::create_tag(conn, &tag)?;
+                    tags.insert(tag.name, tag.id);
+                    tag.id
+                }
+            };
+            db::add_tag_to_game(
+                conn,
+                &models::WishedGameTag {
+                    tag_id,
+                    wished_game_id: *game_id,
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+// POST /api/imports/{import_id}/commit
+pub async fn commit_import(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    igdb_client: web::Data<IGDB>,
+    path: web::Path<Uuid>,
+    choices: web::Json<imports::CommitChoices>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let import_id = path.into_inner();
+    let choices = choices.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let result = web::block(move || -> Result<imports::CommitResult, ApiError> {
+        let conn = pool.get()?;
+        let import = db::get_import(&conn, user.id, import_id)?
+            .ok_or_else(|| ApiError::not_found("Import not found"))?;
+        if import.status != imports::STATUS_REVIEW {
+            return Err(ApiError::new(
+                ErrorCode::ImportNotReady,
+                format!(
+                    "Only imports in review can be committed, this one is {}",
+                    import.status
+                ),
+            ));
+        }
+
+        let report = imports::to_report(&import);
+        let selected = imports::selected_rows(&report.results, &choices)
+            .map_err(|message| ApiError::new(ErrorCode::InvalidImport, message))?;
+
+        let selected_ids: Vec<i32> = selected.iter().map(|(_, igdb_id)| *igdb_id).collect();
+        let wished_ids: HashSet<i32> = db::get_wished_igdb_ids(&conn, user.id, &selected_ids)?
+            .into_iter()
+            .collect();
+        let mut result = imports::CommitResult::default();
+        let (already_wished, selected): (Vec<(usize, i32)>, Vec<(usize, i32)>) = selected
+            .into_iter()
+            .partition(|(_, igdb_id)| wished_ids.contains(igdb_id));
+        result.already_wished = already_wished.len();
+
+        // The report only has enough to pick a game, the full game comes from IGDB
+        let igdb_ids: Vec<i32> = selected.iter().map(|(_, igdb_id)| *igdb_id).collect();
+        let igdb_games: HashMap<i32, IGDBGame> = igdb_client
+            .get_games_by_ids(&igdb_ids)
+            .map_err(|error| {
+                ApiError::new(
+                    ErrorCode::IgdbUnavailable,
+                    "Could not fetch the games from IGDB",
+                )
+                .with_cause(error)
+            })?
+            .into_iter()
+            .map(|game| (game.id, game))
+            .collect();
+
+        let mut games: Vec<models::WishedGame> = Vec::new();
+        let mut labeled: Vec<(Uuid, &imports::ImportRow)> = Vec::new();
+        for (row, igdb_id) in selected {
+            let input = match report
+                .results
+                .iter()
+                .find(|row_result| row_result.row == row)
+            {
+                Some(row_result) => &row_result.input,
+                None => continue,
+            };
+            let game = igdb_games
+                .get(&igdb_id)
+                .ok_or_else(|| "The game is not on IGDB anymore".to_string())
+                .and_then(|igdb_game| imports::new_imported_game(user.id, input, igdb_game));
+
+            match game {
+                Ok(game) => {
+                    labeled.push((game.id, input));
+                    games.push(game);
+                }
+                Err(reason) => result.failed.push(imports::RowFailure { row, reason }),
+            }
+        }
+
+        conn.transaction(|| {
+            // Of two commits running at once, only the first one gets to add the games
+            if !db::change_import_status(
+                &conn,
+                import_id,
+                imports::STATUS_REVIEW,
+                imports::STATUS_COMMITTED,
+            )? {
+                return Err(ApiError::new(
+                    ErrorCode::ImportNotReady,
+                    "The import was committed already",
+                ));
+            }
+            db::add_games_to_wishlist(&conn, &games)?;
+            label_imported_games(&conn, user.id, &labeled)
+        })?;
+        result.added = games.len();
+
+        Ok(result)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+#[derive(Deserialize)]
+pub struct NamedItem {
+    name: String,
+}
+
+// GET /api/lists
+pub async fn get_game_lists(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_READ)?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let lists = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::get_user_game_lists(&conn, user.id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(lists))
+}
+
+// POST /api/lists
+pub async fn add_game_list(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    new_list: web::Json<NamedItem>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let list = models::GameList {
+        id: Uuid::new_v4(),
+        user_id: user.id.to_owned(),
+        name: wishlist::normalize_name(&new_list.name).map_err(ApiError::bad_request)?,
+        created_on: Utc::now().naive_utc(),
+    };
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let list = web::block(move || -> Result<models::GameList, ApiError> {
+        let conn = pool.get()?;
+        db::create_game_list(&conn, &list)?;
+
+        Ok(list)
+    })
+    .await?;
+
+    Ok(HttpResponse::Created().json(list))
+}
+
+// PATCH /api/lists/{list_id}
+pub async fn rename_game_list(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+    changes: web::Json<NamedItem>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let list_id = path.into_inner();
+    let name = wishlist::normalize_name(&changes.name).map_err(ApiError::bad_request)?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let list = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        db::rename_game_list(&conn, user.id, list_id, &name)?
+            .ok_or_else(|| ApiError::not_found("List not found"))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(list))
+}
+
+// DELETE /api/lists/{list_id}
+pub async fn remove_game_list(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let list_id = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::remove_game_list(&conn, user.id, list_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Makes sure the list and the game both belong to the user.
+fn check_list_and_game(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    list_id: Uuid,
+    game_id: Uuid,
+) -> Result<(), ApiError> {
+    db::get_game_list(db_conn, usr_id, list_id)?
+        .ok_or_else(|| ApiError::not_found("List not found"))?;
+    db::get_wished_game(db_conn, usr_id, game_id)?
+        .ok_or_else(|| ApiError::not_found("Game is not in the wishlist"))?;
+
+    Ok(())
+}
+
+// PUT /api/lists/{list_id}/games/{game_id}
+pub async fn add_game_to_list(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let (list_id, game_id) = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        check_list_and_game(&conn, user.id, list_id, game_id)?;
+
+        Ok(db::add_game_to_list(
+            &conn,
+            &models::GameListEntry {
+                list_id,
+                wished_game_id: game_id,
+                added_on: Utc::now().naive_utc(),
+            },
+        )?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// DELETE /api/lists/{list_id}/games/{game_id}
+pub async fn remove_game_from_list(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let (list_id, game_id) = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        check_list_and_game(&conn, user.id, list_id, game_id)?;
+
+        Ok(db::remove_game_from_list(&conn, list_id, game_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// GET /api/tags
+pub async fn get_tags(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_READ)?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let tags = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::get_user_tags(&conn, user.id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(tags))
+}
+
+// POST /api/tags
+pub async fn add_tag(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    new_tag: web::Json<NamedItem>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let tag = models::Tag {
+        id: Uuid::new_v4(),
+        user_id: user.id.to_owned(),
+        name: wishlist::normalize_name(&new_tag.name).map_err(ApiError::bad_request)?,
+        created_on: Utc::now().naive_utc(),
+    };
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let tag = web::block(move || -> Result<models::Tag, ApiError> {
+        let conn = pool.get()?;
+        db::create_tag(&conn, &tag)?;
+
+        Ok(tag)
+    })
+    .await?;
+
+    Ok(HttpResponse::Created().json(tag))
+}
+
+// PATCH /api/tags/{tag_id}
+pub async fn rename_tag(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+    changes: web::Json<NamedItem>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let tag_id = path.into_inner();
+    let name = wishlist::normalize_name(&changes.name).map_err(ApiError::bad_request)?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let tag = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        db::rename_tag(&conn, user.id, tag_id, &name)?
+            .ok_or_else(|| ApiError::not_found("Tag not found"))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(tag))
+}
+
+// DELETE /api/tags/{tag_id}
+pub async fn remove_tag(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let tag_id = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::remove_tag(&conn, user.id, tag_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Makes sure the tag and the game both belong to the user.
+fn check_tag_and_game(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    tag_id: Uuid,
+    game_id: Uuid,
+) -> Result<(), ApiError> {
+    db::get_tag(db_conn, usr_id, tag_id)?.ok_or_else(|| ApiError::not_found("Tag not found"))?;
+    db::get_wished_game(db_conn, usr_id, game_id)?
+        .ok_or_else(|| ApiError::not_found("Game is not in the wishlist"))?;
+
+    Ok(())
+}
+
+// PUT /api/tags/{tag_id}/games/{game_id}
+pub async fn tag_game(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let (tag_id, game_id) = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        check_tag_and_game(&conn, user.id, tag_id, game_id)?;
+
+        Ok(db::add_tag_to_game(
+            &conn,
+            &models::WishedGameTag {
+                tag_id,
+                wished_game_id: game_id,
+            },
+        )?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// DELETE /api/tags/{tag_id}/games/{game_id}
+pub async fn untag_game(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WISHLIST_WRITE)?;
+
+    let (tag_id, game_id) = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        check_tag_and_game(&conn, user.id, tag_id, game_id)?;
+
+        Ok(db::remove_tag_from_game(&conn, tag_id, game_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Serialize)]
+pub struct ShareLinkWithUrl {
+    #[serde(flatten)]
+    share_link: models::ShareLink,
+    url: String,
+}
+
+fn with_share_url(
+    req: &HttpRequest,
+    share_link: models::ShareLink,
+) -> Result<ShareLinkWithUrl, ApiError> {
+    let url = req
+        .url_for("share_page", &[&share_link.token])
+        .map_err(ApiError::internal)?;
+
+    Ok(ShareLinkWithUrl {
+        share_link,
+        url: url.to_string(),
+    })
+}
+
+// GET /api/shares
+pub async fn get_share_links(
+    req: HttpRequest,
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let share_links = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::get_user_share_links(&conn, user.id)?)
+    })
+    .await?;
+
+    let share_links = share_links
+        .into_iter()
+        .map(|share_link| with_share_url(&req, share_link))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(HttpResponse::Ok().json(share_links))
+}
+
+// POST /api/shares
+pub async fn add_share_link(
+    req: HttpRequest,
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    new_link: web::Json<sharing::NewShareLink>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    let share_link = sharing::new_share_link(user.id, new_link.into_inner());
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let share_link = web::block(move || -> Result<models::ShareLink, ApiError> {
+        let conn = pool.get()?;
+        if let Some(list_id) = share_link.list_id {
+            db::get_game_list(&conn, user.id, list_id)?
+                .ok_or_else(|| ApiError::not_found("List not found"))?;
+        }
+        db::create_share_link(&conn, &share_link)?;
+
+        Ok(share_link)
+    })
+    .await?;
+
+    Ok(HttpResponse::Created().json(with_share_url(&req, share_link)?))
+}
+
+// DELETE /api/shares/{share_id}
+pub async fn remove_share_link(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    let share_id = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::remove_share_link(&conn, user.id, share_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+const GIFT_CLAIMS_KEY: &str = "gift_claims";
+
+/// The new claim, or why the visitor could not claim the game.
+type ClaimOutcome = Result<Uuid, (StatusCode, &'static str)>;
+
+/// Claims made from this browser, so the visitor can take them back.
+fn own_gift_claims(session: &Session) -> Vec<Uuid> {
+    session
+        .get::<Vec<Uuid>>(GIFT_CLAIMS_KEY)
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+fn render_share_message(
+    tera: &Tera,
+    share_url: &str,
+    message: &str,
+    status: StatusCode,
+) -> HttpResponse {
+    let mut tera_data = page_context();
+    tera_data.insert("share_url", share_url);
+    tera_data.insert("message", message);
+
+    render_page(tera, "share_message.html", &tera_data, status)
+}
+
+// GET /share/{token}
+pub async fn share_page(
+    tera: web::Data<Tera>,
+    pool: web::Data<types::DBPool>,
+    session: Session,
+    user: Option<CurrentUser>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let token = path.into_inner();
+    let share_token = token.clone();
+    let own_claims = own_gift_claims(&session);
+    let viewer_id = user.map(|user| user.id);
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let shared = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let share_link = match db::get_share_link_by_token(&conn, &share_token)? {
+            Some(share_link) => share_link,
+            None => return Ok(None),
+        };
+        let owner = db::get_user_by_id(&conn, share_link.user_id)?
+            .ok_or_else(|| ApiError::internal("Share link points to a missing user"))?;
+        let list_name = match share_link.list_id {
+            Some(list_id) => db::get_game_list(&conn, owner.id, list_id)?.map(|list| list.name),
+            None => None,
+        };
+
+        let is_owner = viewer_id == Some(owner.id);
+        let games = db::get_shared_games(&conn, &share_link)?;
+        // The owner does not get to see who is buying what
+        let claims = if is_owner {
+            Vec::new()
+        } else {
+            let game_ids: Vec<Uuid> = games.iter().map(|game| game.id).collect();
+            db::get_gift_claims_for_games(&conn, &game_ids)?
+        };
+        let games: Vec<sharing::SharedGame> = games
+            .iter()
+            .map(|game| {
+                let claim = claims.iter().find(|claim| claim.wished_game_id == game.id);
+                sharing::to_shared_game(&share_link, game, claim, &own_claims)
+            })
+            .collect();
+
+        Ok(Some((owner.name, list_name, is_owner, games)))
+    })
+    .await?;
+
+    let mut tera_data = page_context();
+    let (owner_name, list_name, is_owner, games) = match shared {
+        Some(shared) => shared,
+        None => {
+            return Ok(render_page(
+                &tera,
+                "share_not_found.html",
+                &tera_data,
+                StatusCode::NOT_FOUND,
+            ))
+        }
+    };
+    tera_data.insert("owner_name", &owner_name);
+    tera_data.insert("list_name", &list_name);
+    tera_data.insert("token", &token);
+    tera_data.insert("is_owner", &is_owner);
+    tera_data.insert("games", &games);
+
+    Ok(render_page(&tera, "share.html", &tera_data, StatusCode::OK))
+}
+
+// POST /share/{token}/claims/{game_id}
+pub async fn claim_gift(
+    req: HttpRequest,
+    tera: web::Data<Tera>,
+    pool: web::Data<types::DBPool>,
+    client_config: web::Data<ClientConfig>,
+    session: Session,
+    user: Option<CurrentUser>,
+    path: web::Path<(String, Uuid)>,
+    form: web::Form<sharing::ClaimForm>,
+) -> Result<HttpResponse, ApiError> {
+    let (token, game_id) = path.into_inner();
+    let share_url = req
+        .url_for("share_page", &[&token])
+        .map_err(ApiError::internal)?
+        .to_string();
+
+    let ip_hash = sharing::hash_claimer_ip(
+        &client_config.ip_hash_key,
+        client_ip(&req).unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+    );
+    let claim = match sharing::new_gift_claim(game_id, form.into_inner().claimer_name, ip_hash) {
+        Ok(claim) => claim,
+        Err(message) => {
+            return Ok(render_share_message(
+                &tera,
+                &share_url,
+                &message,
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    let viewer_id = user.map(|user| user.id);
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let claimed = web::block(move || -> Result<ClaimOutcome, ApiError> {
+        let conn = pool.get()?;
+        let share_link = match db::get_share_link_by_token(&conn, &token)? {
+            Some(share_link) => share_link,
+            None => {
+                return Ok(Err((
+                    StatusCode::NOT_FOUND,
+                    "This wishlist is not shared anymore.",
+                )))
+            }
+        };
+        if viewer_id == Some(share_link.user_id) {
+            return Ok(Err((
+                StatusCode::BAD_REQUEST,
+                "You can not claim your own games.",
+            )));
+        }
+        let games = db::get_shared_games(&conn, &share_link)?;
+        if !games.iter().any(|game| game.id == game_id) {
+            return Ok(Err((
+                StatusCode::NOT_FOUND,
+                "This game is not on the wishlist anymore.",
+            )));
+        }
+
+        let since = sharing::claims_window_start(Utc::now().naive_utc());
+        if db::count_gift_claims_by_ip_since(&conn, &claim.claimer_ip_hash, since)?
+            >= sharing::MAX_CLAIMS_PER_DAY
+        {
+            return Ok(Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                "You claimed a lot of games today, please try again tomorrow.",
+            )));
+        }
+        if !db::create_gift_claim(&conn, &claim)? {
+            return Ok(Err((
+                StatusCode::CONFLICT,
+                "Someone else claimed this game already.",
+            )));
+        }
+
+        Ok(Ok(claim.id))
+    })
+    .await?;
+
+    match claimed {
+        Ok(claim_id) => {
+            let mut own_claims = own_gift_claims(&session);
+            own_claims.push(claim_id);
+            if let Err(error) = session.set(GIFT_CLAIMS_KEY, own_claims) {
+                log::error!("Error saving gift claims to session! {}", error);
+            }
+
+            Ok(HttpResponse::SeeOther()
+                .header("location", share_url)
+                .finish())
+        }
+        Err((status, message)) => Ok(render_share_message(&tera, &share_url, message, status)),
+    }
+}
+
+// POST /share/{token}/claims/{game_id}/release
+pub async fn release_gift_claim(
+    req: HttpRequest,
+    pool: web::Data<types::DBPool>,
+    session: Session,
+    path: web::Path<(String, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    let (token, game_id) = path.into_inner();
+    let share_url = req
+        .url_for("share_page", &[&token])
+        .map_err(ApiError::internal)?
+        .to_string();
+
+    let mut own_claims = own_gift_claims(&session);
+    let claim_ids = own_claims.clone();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let released = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        // Only the browser that made the claim can release it
+        match db::get_gift_claims_for_games(&conn, &[game_id])?
+            .into_iter()
+            .find(|claim| claim_ids.contains(&claim.id))
+        {
+            Some(claim) => {
+                db::remove_gift_claim(&conn, claim.id)?;
+                Ok(Some(claim.id))
+            }
+            None => Ok(None),
+        }
+    })
+    .await?;
+
+    if let Some(claim_id) = released {
+        own_claims.retain(|id| *id != claim_id);
+        if let Err(error) = session.set(GIFT_CLAIMS_KEY, own_claims) {
+            log::error!("Error saving gift claims to session! {}", error);
+        }
+    }
+
+    Ok(HttpResponse::SeeOther()
+        .header("location", share_url)
+        .finish())
+}
+
+/// The group as seen by one of its members.
+fn get_member_group(
+    db_conn: &PgConnection,
+    usr_id: Uuid,
+    group_id: Uuid,
+) -> Result<models::Group, ApiError> {
+    db::get_group(db_conn, usr_id, group_id)?.ok_or_else(|| ApiError::not_found("Group not found"))
+}
+
+fn require_owner(group: &models::Group, usr_id: Uuid) -> Result<(), ApiError> {
+    if group.owner_id != usr_id {
+        return Err(ApiError::new(
+            ErrorCode::Forbidden,
+            "Only the owner of the group can do this",
+        ));
+    }
+
+    Ok(())
+}
+
+// GET /api/groups
+pub async fn get_groups(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_GROUPS_READ)?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let summaries = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let user_groups = db::get_user_groups(&conn, user.id)?;
+        let group_ids: Vec<Uuid> = user_groups.iter().map(|group| group.id).collect();
+        let members = db::get_members_of_groups(&conn, &group_ids)?;
+
+        let summaries: Vec<groups::GroupSummary> = user_groups
+            .into_iter()
+            .map(|group| groups::GroupSummary {
+                member_count: members
+                    .iter()
+                    .filter(|member| member.group_id == group.id)
+                    .count() as i64,
+                group,
+            })
+            .collect();
+
+        Ok(summaries)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(summaries))
+}
+
+// POST /api/groups
+pub async fn add_group(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    new_group: web::Json<groups::NewGroup>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_GROUPS_WRITE)?;
+
+    let group =
+        groups::new_group(user.id, new_group.into_inner()).map_err(ApiError::bad_request)?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let group = web::block(move || -> Result<models::Group, ApiError> {
+        let conn = pool.get()?;
+        db::create_group(&conn, &group)?;
+
+        Ok(group)
+    })
+    .await?;
+
+    Ok(HttpResponse::Created().json(groups::GroupSummary {
+        group,
+        member_count: 1,
+    }))
+}
+
+// PATCH /api/groups/{group_id}
+pub async fn update_group(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+    changes: web::Json<groups::GroupChanges>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_GROUPS_WRITE)?;
+
+    let group_id = path.into_inner();
+    let changes = changes.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let group = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let mut group = get_member_group(&conn, user.id, group_id)?;
+        require_owner(&group, user.id)?;
+
+        groups::apply_changes(&mut group, changes).map_err(ApiError::bad_request)?;
+        db::update_group(&conn, &group)?;
+
+        Ok(group)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(group))
+}
+
+// DELETE /api/groups/{group_id}
+pub async fn remove_group(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_GROUPS_WRITE)?;
+
+    let group_id = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let group = get_member_group(&conn, user.id, group_id)?;
+        require_owner(&group, user.id)?;
+
+        Ok(db::remove_group(&conn, user.id, group_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// GET /api/groups/{group_id}/members
+pub async fn get_group_members(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_GROUPS_READ)?;
+
+    let group_id = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let members = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        get_member_group(&conn, user.id, group_id)?;
+
+        Ok(db::get_group_members(&conn, group_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(members))
+}
+
+// DELETE /api/groups/{group_id}/members/{user_id}
+pub async fn remove_group_member(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_GROUPS_WRITE)?;
+
+    let (group_id, member_id) = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let group = get_member_group(&conn, user.id, group_id)?;
+        // Members can leave on their own, everyone else is removed by the owner
+        if member_id != user.id {
+            require_owner(&group, user.id)?;
+        }
+        if member_id == group.owner_id {
+            return Err(ApiError::bad_request(
+                "The owner can not leave the group, delete it instead",
+            ));
+        }
+
+        Ok(db::remove_group_member(&conn, group_id, member_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Serialize)]
+pub struct CreatedGroupInvite {
+    #[serde(flatten)]
+    invite: dtos::GroupInvite,
+    /// Only ever returned here, we keep nothing but the hash.
+    url: String,
+}
+
+// GET /api/groups/{group_id}/invites
+pub async fn get_group_invites(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_GROUPS_READ)?;
+
+    let group_id = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let invites = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        get_member_group(&conn, user.id, group_id)?;
+
+        Ok(db::get_group_invites(&conn, group_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(invites))
+}
+
+// POST /api/groups/{group_id}/invites
+pub async fn add_group_invite(
+    req: HttpRequest,
+    pool: web::Data<types::DBPool>,
+    public_url: web::Data<PublicUrl>,
+    invite_mailer: Option<web::Data<groups::InviteMailer>>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+    new_invite: web::Json<groups::NewInvite>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_GROUPS_WRITE)?;
+
+    let group_id = path.into_inner();
+    let (invite, token) = groups::new_invite(group_id, user.id, new_invite.into_inner().email)
+        .map_err(ApiError::bad_request)?;
+    if invite.email.is_some() && invite_mailer.is_none() {
+        return Err(ApiError::bad_request(
+            "Email is not set up on this server, share an invite link instead",
+        ));
+    }
+
+    let url = format!(
+        "{}?token={}",
+        public_link(&req, &public_url, "join_group")?,
+        token
+    );
+    let invite_url = url.clone();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let invite = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let group = get_member_group(&conn, user.id, group_id)?;
+
+        if let (Some(email), Some(invite_mailer)) = (&invite.email, invite_mailer) {
+            // Members can share links, only the owner makes the server email people
+            require_owner(&group, user.id)?;
+            let window_start = groups::invite_emails_window_start(Utc::now().naive_utc());
+            let sent_in_window = db::count_invite_emails_since(&conn, user.id, window_start)?;
+            if groups::is_invite_email_throttled(sent_in_window) {
+                return Err(ApiError::new(
+                    ErrorCode::TooManyInvites,
+                    "You sent too many invites today, share an invite link instead",
+                ));
+            }
+
+            db::create_group_invite(&conn, &invite)?;
+            db::create_invite_email(&conn, &groups::new_invite_email(user.id), window_start)?;
+            invite_mailer.send_invite(email, &user.name, &group, &invite_url)?;
+        } else {
+            db::create_group_invite(&conn, &invite)?;
+        }
+
+        Ok(dtos::GroupInvite {
+            id: invite.id,
+            group_id: invite.group_id,
+            email: invite.email,
+            created_by: invite.created_by,
+            expires_at: invite.expires_at,
+            created_on: invite.created_on,
+        })
+    })
+    .await?;
+
+    Ok(HttpResponse::Created().json(CreatedGroupInvite { invite, url }))
+}
+
+// DELETE /api/groups/{group_id}/invites/{invite_id}
+pub async fn remove_group_invite(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_GROUPS_WRITE)?;
+
+    let (group_id, invite_id) = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let group = get_member_group(&conn, user.id, group_id)?;
+        // Whoever sent the invite can take it back, the owner can revoke any of them
+        let is_creator = db::get_group_invites(&conn, group_id)?
+            .iter()
+            .any(|invite| invite.id == invite_id && invite.created_by == user.id);
+        if !is_creator {
+            require_owner(&group, user.id)?;
+        }
+
+        Ok(db::remove_group_invite(&conn, group_id, invite_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// GET /api/groups/{group_id}/games?sort=overlap&min_members=2
+pub async fn get_group_games(
+    pool: web::Data<types::DBPool>,
+    user: CurrentUser,
+    path: web::Path<Uuid>,
+    params: web::Query<groups::GroupGamesParams>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_GROUPS_READ)?;
+
+    let group_id = path.into_inner();
+    let params = params.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let group_games = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        get_member_group(&conn, user.id, group_id)?;
+        let members = db::get_group_members(&conn, group_id)?;
+        let games = db::get_group_wishlist_games(&conn, group_id)?;
+
+        Ok(groups::merge_wishlists(&members, games, &params))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(group_games))
+}
+
+/// The invite behind `token_hash`, if `user` may still use it to join.
+fn find_usable_invite(
+    conn: &PgConnection,
+    token_hash: &str,
+    user: &models::User,
+) -> Result<Result<models::GroupInvite, &'static str>, ApiError> {
+    let invite = match db::get_group_invite_by_hash(conn, token_hash)? {
+        Some(invite) => invite,
+        None => return Ok(Err("This invite is invalid or has expired.")),
+    };
+    if !groups::can_accept(&invite, &user.email) {
+        return Ok(Err("This invite was sent to a different email address."));
+    }
+    if db::count_group_members(conn, invite.group_id)? >= groups::MAX_MEMBERS {
+        return Ok(Err("This group is full."));
+    }
+
+    Ok(Ok(invite))
+}
+
+fn render_login_to_join(tera: &Tera) -> HttpResponse {
+    render_message(
+        tera,
+        "Log in to join",
+        "Log in to Playday first, then open the invite link again.",
+    )
+}
+
+// GET /groups/join?token=
+pub async fn join_group_form(
+    tera: web::Data<Tera>,
+    pool: web::Data<types::DBPool>,
+    user: Option<CurrentUser>,
+    query: web::Query<TokenQuery>,
+) -> HttpResponse {
+    let user = match user {
+        Some(user) => user,
+        None => return render_login_to_join(&tera),
+    };
+    let token = query.into_inner().token;
+    let token_hash = api_tokens::hash_token(&token);
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let group = web::block(move || -> Result<Result<models::Group, &'static str>, ApiError> {
+        let conn = pool.get()?;
+        let invite = match find_usable_invite(&conn, &token_hash, &user)? {
+            Ok(invite) => invite,
+            Err(message) => return Ok(Err(message)),
+        };
+
+        match db::get_group_by_id(&conn, invite.group_id)? {
+            Some(group) => Ok(Ok(group)),
+            None => Ok(Err("This invite is invalid or has expired.")),
+        }
+    })
+    .await;
+
+    match group {
+        // Joining only happens on the POST, opening the link must not change anything
+        Ok(Ok(group)) => {
+            let mut tera_data = page_context();
+            tera_data.insert("group_name", &group.name);
+            tera_data.insert("token", &token);
+
+            render_page(&tera, "join_group.html", &tera_data, StatusCode::OK)
+        }
+        Ok(Err(message)) => render_message(&tera, "Could not join the group", message),
+        Err(error) => {
+            log::error!("Error loading group invite! {}", error);
+            render_message(
+                &tera,
+                "Could not join the group",
+                "Something went wrong, please try again.",
+            )
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct JoinGroupForm {
+    token: String,
+}
+
+// POST /groups/join
+pub async fn join_group(
+    tera: web::Data<Tera>,
+    pool: web::Data<types::DBPool>,
+    user: Option<CurrentUser>,
+    form: web::Form<JoinGroupForm>,
+) -> HttpResponse {
+    let user = match user {
+        Some(user) => user,
+        None => return render_login_to_join(&tera),
+    };
+    let token_hash = api_tokens::hash_token(&form.token);
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let joined = web::block(move || -> Result<Result<(), &'static str>, ApiError> {
+        let conn = pool.get()?;
+        let invite = match find_usable_invite(&conn, &token_hash, &user)? {
+            Ok(invite) => invite,
+            Err(message) => return Ok(Err(message)),
+        };
+
+        conn.transaction(|| -> Result<_, ApiError> {
+            db::add_group_member(
+                &conn,
+                &models::GroupMember {
+                    group_id: invite.group_id,
+                    user_id: user.id,
+                    joined_on: Utc::now().naive_utc(),
+                },
+            )?;
+            // Email invites are single use, links keep working until they expire
+            if invite.email.is_some() {
+                db::remove_group_invite(&conn, invite.group_id, invite.id)?;
+            }
+            Ok(())
+        })?;
+
+        Ok(Ok(()))
+    })
+    .await;
+
+    match joined {
+        Ok(Ok(())) => HttpResponse::SeeOther().header("location", "/").finish(),
+        Ok(Err(message)) => render_message(&tera, "Could not join the group", message),
+        Err(error) => {
+            log::error!("Error joining group! {}", error);
+            render_message(
+                &tera,
+                "Could not join the group",
+                "Something went wrong, please try again.",
+            )
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EpicGamesLogin {
+    sid: String,
+}
+
+// GET /connect/epicgames/login
+pub async fn login_via_epicgames(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    login_info: web::Json<EpicGamesLogin>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    let store_login_failed = |error: anyhow::Error| {
+        ApiError::new(ErrorCode::StoreLoginFailed, "Could not log in to Epic Games").with_cause(error)
+    };
+    let epic_games = EpicGames::new().map_err(store_login_failed)?;
+
+    let auth_code = epic_games
+        .get_exchange_token(&login_info.sid)
+        .map_err(store_login_failed)?;
+
+    let token = epic_games
+        .get_login_tokens(&auth_code)
+        .map_err(store_login_failed)?;
+    let display_name = token.display_name.clone();
+
+    let game_store = models::GameStore {
+        id: Uuid::new_v4(),
+        user_id: user.id.to_owned(),
+        added_on: Utc::now().naive_utc(),
+        updated_on: Utc::now().naive_utc(),
+        store_name: "epicgames".to_string(),
+        store_token: serde_json::to_value(token).map_err(ApiError::internal)?,
+        store_user_name: display_name,
+    };
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::save_epicgames_login(&conn, &game_store)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+
+// GET /library/{store_name}/settings
+pub async fn get_library_settings(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    store_name: web::Path<String>
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_LIBRARY_READ)?;
+
+    let store_name = store_name.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let game_store = web::block(move || -> Result<_, ApiError> {
+        // Get Epic Library Settings
+        let conn = pool.get()?;
+        Ok(db::get_game_store_account(&conn, user.id, &store_name)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(game_store))
+}
+
+// DELETE /library/{store_name}/settings
+pub async fn disconnect_library(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    store_name: web::Path<String>
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    let store_name = store_name.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::remove_game_store(&conn, user.id, &store_name)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// POST /library/{store_name}/sync
+pub async fn sync_game_library(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    celery_app: web::Data<types::CeleryApp>,
+    store_name: web::Path<String>
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_LIBRARY_SYNC)?;
+
+    let store_name = store_name.into_inner();
+
+    if store_name != library::STORE_EPICGAMES {
+        return Err(ApiError::new(
+            ErrorCode::UnknownStore,
+            format!("Syncing {} is not supported", store_name),
+        ));
+    }
+
+    let db_pool = pool.clone();
+    let run_store_name = store_name.clone();
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let (mut run, is_new) = web::block(move || -> Result<_, ApiError> {
+        let conn = db_pool.get()?;
+        // One sync at a time, asking again while one runs gives back that run
+        if let Some(run) = db::get_latest_sync_run(&conn, user.id, &run_store_name)? {
+            if library::is_active(&run, Utc::now().naive_utc()) {
+                return Ok((run, false));
+            }
+            // A lost run would hold the unique index forever
+            if !library::is_finished(&run) {
+                db::finish_sync_run(
+                    &conn,
+                    run.id,
+                    library::STATUS_FAILED,
+                    Some(library::STALE_RUN_ERROR),
+                )?;
+            }
+        }
+
+        let run = library::new_sync_run(user.id, &run_store_name);
+        if db::create_sync_run(&conn, &run)? {
+            return Ok((run, true));
+        }
+
+        // Another request started one in the meantime
+        let run = db::get_latest_sync_run(&conn, user.id, &run_store_name)?
+            .ok_or_else(|| ApiError::internal("The running library sync went missing"))?;
+        Ok((run, false))
+    })
+    .await?;
+    if !is_new {
+        return Ok(HttpResponse::Ok().json(run));
+    }
+
+    let run_id = run.id;
+    let sent = celery_app
+        .send_task(|| tasks::sync_epicgames_library::new(run_id))
+        .await;
+
+    run.task_id = sent
+        .as_ref()
+        .ok()
+        .map(|async_result| async_result.task_id.to_owned());
+    let task_id = run.task_id.clone();
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        match task_id {
+            Some(task_id) => db::set_sync_run_task(&conn, run_id, &task_id)?,
+            // Nothing would ever pick it up, don't leave it queued
+            None => db::finish_sync_run(
+                &conn,
+                run_id,
+                library::STATUS_FAILED,
+                Some("The library sync could not be started"),
+            )?,
+        };
+
+        Ok(())
+    })
+    .await?;
+    sent.map_err(|error| {
+        ApiError::new(
+            ErrorCode::TaskQueueUnavailable,
+            "Could not start the library sync",
+        )
+        .with_cause(error)
+    })?;
+
+    Ok(HttpResponse::Accepted()
+        .header(
+            "location",
+            format!("/api/library/{}/sync/{}", store_name, run_id),
+        )
+        .json(run))
+}
+
+// GET /library/{store_name}/sync/{run_id}
+pub async fn get_sync_run(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    path: web::Path<(String, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_LIBRARY_READ)?;
+
+    let (store_name, run_id) = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let run = web::block(move || -> Result<models::SyncRun, ApiError> {
+        let conn = pool.get()?;
+        db::get_sync_run(&conn, user.id, &store_name, run_id)?
+            .ok_or_else(|| ApiError::not_found("Sync run not found"))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(run))
+}
+
+/// Where the event stream of a sync run is at. `last_update` is the `updated_on` of the
+/// run sent last, a new event only goes out when it changes.
+struct SyncRunEvents {
+    pool: web::Data<types::DBPool>,
+    usr_id: Uuid,
+    store_name: String,
+    run_id: Uuid,
+    first: Option<models::SyncRun>,
+    last_update: Option<NaiveDateTime>,
+    idle_polls: u32,
+    done: bool,
+}
+
+impl SyncRunEvents {
+    fn progress_event(&mut self, run: &models::SyncRun) -> Result<web::Bytes, ApiError> {
+        self.last_update = Some(run.updated_on);
+        self.idle_polls = 0;
+        self.done = !library::is_active(run, Utc::now().naive_utc());
+
+        let data = serde_json::to_string(run).map_err(ApiError::internal)?;
+        Ok(web::Bytes::from(format!(
+            "event: progress\ndata: {}\n\n",
+            data
+        )))
+    }
+}
+
+// GET /library/{store_name}/sync/{run_id}/events
+pub async fn sync_run_events(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    path: web::Path<(String, Uuid)>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_LIBRARY_READ)?;
+
+    let (store_name, run_id) = path.into_inner();
+
+    let db_pool = pool.clone();
+    let run_store_name = store_name.clone();
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let run = web::block(move || -> Result<models::SyncRun, ApiError> {
+        let conn = db_pool.get()?;
+        db::get_sync_run(&conn, user.id, &run_store_name, run_id)?
+            .ok_or_else(|| ApiError::not_found("Sync run not found"))
+    })
+    .await?;
+
+    let state = SyncRunEvents {
+        pool,
+        usr_id: run.user_id,
+        store_name,
+        run_id,
+        first: Some(run),
+        last_update: None,
+        idle_polls: 0,
+        done: false,
+    };
+
+    // Every change of the run goes out as a `progress` event with the run as data. The
+    // stream ends once the run finished, clients should close it then too.
+    let events = stream::unfold(state, |mut state| async move {
+        if let Some(run) = state.first.take() {
+            let event = state.progress_event(&run);
+            return Some((event, state));
+        }
+        if state.done {
+            return None;
+        }
+
+        loop {
+            rt::time::delay_for(Duration::from_millis(library::PROGRESS_POLL_MS)).await;
+
+            let pool = state.pool.clone();
+            let (usr_id, run_id) = (state.usr_id, state.run_id);
+            let store_name = state.store_name.clone();
+            // use web::block to offload blocking Diesel code without blocking server thread
+            let run = web::block(move || -> Result<_, ApiError> {
+                let conn = pool.get()?;
+                Ok(db::get_sync_run(&conn, usr_id, &store_name, run_id)?)
+            })
+            .await;
+
+            let run = match run {
+                Ok(Some(run)) => run,
+                Ok(None) => return None,
+                Err(error) => {
+                    state.done = true;
+                    return Some((Err(ApiError::from(error)), state));
+                }
+            };
+
+            if state.last_update != Some(run.updated_on) {
+                let event = state.progress_event(&run);
+                return Some((event, state));
+            }
+            // Stale runs never finish, there is nothing more to wait for
+            if !library::is_active(&run, Utc::now().naive_utc()) {
+                return None;
+            }
+
+            state.idle_polls += 1;
+            if state.idle_polls >= library::KEEP_ALIVE_POLLS {
+                state.idle_polls = 0;
+                return Some((Ok(web::Bytes::from_static(b": keep-alive\n\n")), state));
+            }
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("cache-control", "no-cache")
+        .streaming(Box::pin(events)))
+}
+
+#[derive(Deserialize)]
+pub struct NewWebhook {
+    url: String,
+    payload_format: PayloadFormat,
+}
+
+// GET /api/webhooks
+pub async fn get_webhooks(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WEBHOOKS_READ)?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let hooks = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::get_user_webhooks(&conn, user.id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(hooks))
+}
+
+// POST /api/webhooks
+pub async fn add_webhook(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    new_hook: web::Json<NewWebhook>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WEBHOOKS_WRITE)?;
+
+    let new_hook = new_hook.into_inner();
+    let hook_url = new_hook.url.to_owned();
+    // Resolving the host blocks as well
+    let resolved = web::block(move || webhooks::resolve_url(&hook_url)).await;
+    if let Err(error) = resolved {
+        return Err(ApiError::new(
+            ErrorCode::InvalidWebhookUrl,
+            "Webhook URL must be an http(s) URL of a public host",
+        )
+        .with_cause(error));
+    }
+
+    // The secret is only ever returned here, listing webhooks leaves it out.
+    let webhook = models::Webhook {
+        id: Uuid::new_v4(),
+        url: new_hook.url,
+        payload_format: new_hook.payload_format.as_str().to_string(),
+        secret: webhooks::generate_secret(),
+        added_on: Utc::now().naive_utc(),
+        user_id: user.id.to_owned(),
+    };
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let webhook = web::block(move || -> Result<models::Webhook, ApiError> {
+        let conn = pool.get()?;
+        db::create_webhook(&conn, &webhook)?;
+
+        Ok(webhook)
+    })
+    .await?;
+
+    Ok(HttpResponse::Created().json(webhook))
+}
+
+// DELETE /api/webhooks/{webhook_id}
+pub async fn remove_webhook(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WEBHOOKS_WRITE)?;
+
+    let webhook_id = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::remove_webhook(&conn, user.id, webhook_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// GET /api/webhooks/{webhook_id}/deliveries
+pub async fn get_webhook_deliveries(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WEBHOOKS_READ)?;
+
+    let webhook_id = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let deliveries = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        match db::get_user_webhook(&conn, user.id, webhook_id)? {
+            Some(webhook) => Ok(db::get_webhook_deliveries(&conn, webhook.id)?),
+            None => Err(ApiError::not_found("Webhook not found")),
+        }
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+// POST /api/webhooks/{webhook_id}/test
+pub async fn test_webhook(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    celery_app: web::Data<types::CeleryApp>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_WEBHOOKS_WRITE)?;
+
+    let webhook_id = path.into_inner();
+
+    let db_pool = pool.clone();
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let delivery = web::block(move || -> Result<_, ApiError> {
+        let conn = db_pool.get()?;
+        let webhook = match db::get_user_webhook(&conn, user.id, webhook_id)? {
+            Some(webhook) => webhook,
+            None => return Err(ApiError::not_found("Webhook not found")),
+        };
+
+        let payload = webhooks::build_payload(
+            webhooks::EVENT_TEST,
+            "Hello from Playday! Your webhook is working.",
+            serde_json::json!({ "webhook_id": webhook.id }),
+        );
+        let event_key = format!("{}:{}", webhooks::EVENT_TEST, Uuid::new_v4());
+        let delivery = webhooks::new_delivery(&webhook, webhooks::EVENT_TEST, &event_key, payload);
+        db::create_webhook_delivery(&conn, &delivery)?;
+
+        Ok(delivery)
+    })
+    .await?;
+
+    // Sent by the worker like every other delivery, its outcome shows up in the deliveries
+    let delivery_id = delivery.id;
+    let queued = celery_app
+        .send_task(|| tasks::deliver_webhook::new(delivery_id))
+        .await;
+
+    if let Err(error) = queued {
+        let mut failed_delivery = delivery;
+        failed_delivery.status = webhooks::STATUS_FAILED.to_string();
+        failed_delivery.last_error = Some("The test could not be queued".to_string());
+        web::block(move || -> Result<_, ApiError> {
+            let conn = pool.get()?;
+            Ok(db::update_webhook_delivery(&conn, &failed_delivery)?)
+        })
+        .await?;
+
+        return Err(ApiError::new(
+            ErrorCode::TaskQueueUnavailable,
+            "Could not send the test delivery",
+        )
+        .with_cause(error));
+    }
+
+    Ok(HttpResponse::Accepted().json(delivery))
+}
+
+// GET /api/settings
+pub async fn get_settings(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_SETTINGS_READ)?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let usr_settings = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let usr_settings = db::get_user_settings(&conn, user.id)?;
+
+        Ok(usr_settings.unwrap_or_else(|| settings::default_settings(user.id)))
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(usr_settings))
+}
+
+// PUT /api/settings
+pub async fn update_settings(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    update: web::Json<settings::SettingsUpdate>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_scope(api_tokens::SCOPE_SETTINGS_WRITE)?;
+
+    let usr_settings = settings::apply_update(user.id, update.into_inner())
+        .map_err(|error| ApiError::new(ErrorCode::InvalidSettings, error))?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let usr_settings = web::block(move || -> Result<models::UserSettings, ApiError> {
+        let conn = pool.get()?;
+        db::save_user_settings(&conn, &usr_settings)?;
+
+        Ok(usr_settings)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(usr_settings))
+}
+
+#[derive(Serialize)]
+pub struct SessionInfo {
+    #[serde(flatten)]
+    session: models::Session,
+    current: bool,
+}
+
+// GET /api/sessions
+pub async fn get_sessions(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+) -> Result<HttpResponse, ApiError> {
+    let current_session_id = user.require_session()?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let sessions = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        let (idle_cutoff, max_age_cutoff) = session_cutoffs(Utc::now().naive_utc());
+        db::remove_expired_sessions(&conn, user.id, idle_cutoff, max_age_cutoff)?;
+
+        Ok(db::get_user_sessions(&conn, user.id)?)
+    })
+    .await?;
+
+    let sessions: Vec<SessionInfo> = sessions
+        .into_iter()
+        .map(|session| SessionInfo {
+            current: session.id == current_session_id,
+            session,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+// DELETE /api/sessions
+pub async fn remove_all_sessions(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    id: Identity,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::remove_user_sessions(&conn, user.id)?)
+    })
+    .await?;
+    id.forget();
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// DELETE /api/sessions/{session_id}
+pub async fn remove_session(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    path: web::Path<Uuid>,
+    id: Identity,
+) -> Result<HttpResponse, ApiError> {
+    let current_session_id = user.require_session()?;
+    let session_id = path.into_inner();
+    if session_id == current_session_id {
+        id.forget();
+    }
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::remove_session(&conn, user.id, session_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Serialize)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    api_token: dtos::ApiToken,
+    /// Only ever returned here, we keep nothing but the hash.
+    token: String,
+}
+
+// GET /api/tokens
+pub async fn get_api_tokens(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let tokens = web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::get_user_api_tokens(&conn, user.id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+// POST /api/tokens
+pub async fn add_api_token(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    new_token: web::Json<api_tokens::NewApiToken>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+
+    let (api_token, token) = api_tokens::create_token(user.id, new_token.into_inner())
+        .map_err(|error| ApiError::new(ErrorCode::InvalidApiToken, error))?;
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let api_token = web::block(move || -> Result<models::ApiToken, ApiError> {
+        let conn = pool.get()?;
+        db::create_api_token(&conn, &api_token)?;
+
+        Ok(api_token)
+    })
+    .await?;
+
+    Ok(HttpResponse::Created().json(CreatedApiToken {
+        api_token: dtos::ApiToken {
+            id: api_token.id,
+            name: api_token.name,
+            token_prefix: api_token.token_prefix,
+            scopes: api_token.scopes,
+            created_at: api_token.created_at,
+            last_used_at: api_token.last_used_at,
+        },
+        token,
+    }))
+}
+
+// DELETE /api/tokens/{token_id}
+pub async fn remove_api_token(
+    user: CurrentUser,
+    pool: web::Data<types::DBPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    user.require_session()?;
+    let token_id = path.into_inner();
+
+    // use web::block to offload blocking Diesel code without blocking server thread
+    web::block(move || -> Result<_, ApiError> {
+        let conn = pool.get()?;
+        Ok(db::remove_api_token(&conn, user.id, token_id)?)
+    })
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Serialize)]
+pub struct Readiness {
+    database: bool,
+    broker: tasks::ProducerStatus,
+}
+
+// GET /ready
+pub async fn readiness(
+    pool: web::Data<types::DBPool>,
+    celery_app: web::Data<types::CeleryApp>,
+) -> HttpResponse {
+    // use web::block to offload blocking Diesel code without blocking server thread
+    let database = web::block(move || pool.get().map(|_| ())).await.is_ok();
+    let broker = celery_app.status().await;
+
+    let status = if database && broker.connected {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    HttpResponse::build(status).json(Readiness { database, broker })
+}
+
+/// Turns extractor failures (bad JSON, path or query) into `ApiError`s so every
+/// `/api` response has the same error shape.
+pub fn extractor_error<E: std::fmt::Display>(error: E, _req: &HttpRequest) -> Error {
+    ApiError::bad_request(error.to_string()).into()
 }